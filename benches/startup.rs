@@ -0,0 +1,74 @@
+//! Parses the config and scans the sessions in-process, then runs the
+//! greeter with `--bench-startup` and reads back the frame timings it
+//! prints. Like the greeter itself, the latter needs a display to draw on.
+
+use std::{process::Command, time::Duration};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, SamplingMode};
+use eguigreeter::{config, sessions};
+use freedesktop_desktop_entry::DesktopEntry;
+
+/// Runs the greeter once and returns the time it printed on the line for
+/// `name` in `section`, the first one given on that line
+fn run(section: &str, name: &str) -> Duration {
+    let output = Command::new(env!("CARGO_BIN_EXE_eguigreeter"))
+        .arg("--bench-startup")
+        .output()
+        .expect("Failed to run the greeter");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "The greeter failed:\n{}", stderr);
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix(section)?.trim().strip_prefix(name))
+        .find_map(|rest| rest.split_whitespace().next().map(parse))
+        .unwrap_or_else(|| panic!("The greeter didn't time {}:\n{}", name, stderr))
+}
+
+/// Parses a duration as `Debug` prints it, e.g. `1.5ms`
+fn parse(text: &str) -> Duration {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let value: f64 = text[..split].parse().unwrap();
+    let scale = match &text[split..] {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "µs" => 1e-6,
+        "ns" => 1e-9,
+        unit => panic!("Unknown unit {}", unit),
+    };
+    Duration::from_secs_f64(value * scale)
+}
+
+fn startup(c: &mut Criterion) {
+    c.bench_function("config parse", |b| {
+        b.iter(|| config::Config::load(black_box(config::DEFAULT_PATH)))
+    });
+    c.bench_function("session scan", |b| {
+        b.iter(|| {
+            let raw = sessions::read();
+            let entries: Vec<DesktopEntry> = raw
+                .iter()
+                .filter_map(|(bytes, path)| DesktopEntry::decode(path, bytes).ok())
+                .collect();
+            sessions::strip(&entries, &[], None).len()
+        })
+    });
+
+    let mut group = c.benchmark_group("startup");
+    // Every iteration starts the whole greeter
+    group.sample_size(10).sampling_mode(SamplingMode::Flat);
+    for (bench, section, name) in [
+        ("first frame", "startup:", "total "),
+        ("frame ui", "frames:", "ui "),
+        ("frame", "frames:", "frame "),
+    ] {
+        group.bench_function(bench, |b| {
+            b.iter_custom(|iters| (0..iters).map(|_| run(section, name)).sum())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, startup);
+criterion_main!(benches);
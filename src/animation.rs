@@ -0,0 +1,107 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use egui::{ColorImage, Context, TextureHandle};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frame,
+};
+
+use crate::textures;
+
+/// An animated GIF, APNG or WebP background played without mpv
+pub struct Animation {
+    frames: Vec<(ColorImage, Duration)>,
+    current: usize,
+    texture: Option<TextureHandle>,
+    next_frame_at: Instant,
+    /// Holds the first frame while set
+    pub paused: bool,
+}
+
+impl Animation {
+    /// Decodes `path` if it is an animation with more than one frame
+    pub fn open(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        let reader = || File::open(path).map(BufReader::new).ok();
+        let frames: Vec<Frame> = match infer::get_from_path(path).ok()??.mime_type() {
+            "image/gif" => GifDecoder::new(reader()?)
+                .ok()?
+                .into_frames()
+                .collect_frames()
+                .ok()?,
+            "image/apng" | "image/png" => {
+                let decoder = PngDecoder::new(reader()?).ok()?;
+                if !decoder.is_apng() {
+                    return None;
+                }
+                decoder.apng().into_frames().collect_frames().ok()?
+            }
+            "image/webp" => WebPDecoder::new(reader()?)
+                .ok()?
+                .into_frames()
+                .collect_frames()
+                .ok()?,
+            _ => return None,
+        };
+        if frames.len() < 2 {
+            return None;
+        }
+
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let delay = Duration::from(frame.delay()).max(Duration::from_millis(20));
+                let buffer = frame.into_buffer();
+                (
+                    ColorImage::from_rgba_unmultiplied(
+                        [buffer.width() as usize, buffer.height() as usize],
+                        buffer.as_raw(),
+                    ),
+                    delay,
+                )
+            })
+            .collect();
+
+        Some(Animation {
+            frames,
+            current: 0,
+            texture: None,
+            next_frame_at: Instant::now(),
+            paused: false,
+        })
+    }
+
+    /// Advances to the next frame when it is due and paints the current one
+    /// behind every window
+    pub fn show(&mut self, ctx: &Context) {
+        let now = Instant::now();
+        if self.paused && self.current != 0 {
+            self.current = 0;
+            self.texture = None;
+        }
+        if self.texture.is_none() || (!self.paused && now >= self.next_frame_at) {
+            if self.texture.is_some() {
+                self.current = (self.current + 1) % self.frames.len();
+            }
+            let (image, delay) = &self.frames[self.current];
+            match &mut self.texture {
+                Some(texture) => texture.set(image.clone()),
+                None => self.texture = Some(ctx.load_texture("background", image.clone())),
+            }
+            self.next_frame_at = now + *delay;
+        }
+
+        if let Some(texture) = &self.texture {
+            textures::paint_cover(ctx, texture);
+        }
+    }
+
+    pub fn next_frame_at(&self) -> Instant {
+        self.next_frame_at
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+
+use greetd_client::{ErrorType, Response};
+
+use crate::{
+    config::UsernamePolicy,
+    outbox::{Outbox, Request},
+    users,
+};
+
+/// Where requests to greetd go
+pub trait Sink {
+    fn send(&self, request: Request);
+}
+
+/// What greetd's session is up to, as of its last answer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// There's no session, greetd drops it after any error
+    Idle,
+    /// greetd asked something and waits for the answer
+    Prompted,
+    /// Authenticated, waiting for a command to start
+    Authenticated,
+    Started,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Create,
+    Answer,
+    Start,
+    Cancel,
+}
+
+/// The conversation with greetd. Requests are sent one at a time, each only
+/// once the one before was answered, so there's never more than one message
+/// from greetd to handle. Answers to requests that were cancelled, and to
+/// the cancellations themselves, are dropped.
+pub struct Auth<S: Sink = Outbox> {
+    sink: S,
+    /// Checked on every session created, whoever asks for it
+    policy: UsernamePolicy,
+    state: State,
+    /// The request greetd has yet to answer, and whether the answer is stale
+    in_flight: Option<(Kind, bool)>,
+    /// Requests to send once the one in flight is answered
+    queue: VecDeque<Request>,
+}
+
+/// What greetd answers a wrong password with, for failing the made up prompt
+/// unknown users get. It answers nothing that was sent, so it's handled
+/// without going through [`Auth::receive`].
+pub fn fake_failure() -> Response {
+    Response::Error {
+        error_type: ErrorType::AuthError,
+        description: String::new(),
+    }
+}
+
+impl<S: Sink> Auth<S> {
+    pub fn new(sink: S, policy: UsernamePolicy) -> Self {
+        Auth {
+            sink,
+            policy,
+            state: State::Idle,
+            in_flight: None,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Whether greetd has answered everything sent to it
+    pub fn settled(&self) -> bool {
+        self.in_flight.is_none()
+    }
+
+    fn push(&mut self, request: Request) {
+        if self.in_flight.is_some() {
+            self.queue.push_back(request);
+            return;
+        }
+        let kind = match request {
+            Request::CreateSession(_) => Kind::Create,
+            Request::AuthenticationResponse(_) => Kind::Answer,
+            Request::StartSession(_) => Kind::Start,
+            Request::CancelSession => Kind::Cancel,
+        };
+        self.in_flight = Some((kind, false));
+        self.sink.send(request);
+    }
+
+    /// Starts logging in as `username`, cancelling whatever session there
+    /// was. Returns why if the username policy refuses the account, sending
+    /// nothing.
+    pub fn create_session(&mut self, username: &str) -> Result<(), &'static str> {
+        users::allowed(username, &self.policy)?;
+        self.cancel_session();
+        self.push(Request::CreateSession(username.to_string()));
+        Ok(())
+    }
+
+    /// Answers greetd's question. Returns `false`, sending nothing, if
+    /// there's no question waiting for an answer.
+    pub fn authentication_response(&mut self, response: Option<&str>) -> bool {
+        if self.state != State::Prompted || !self.settled() {
+            return false;
+        }
+        self.push(Request::AuthenticationResponse(
+            response.map(str::to_string),
+        ));
+        true
+    }
+
+    /// Starts the authenticated session. Returns `false`, sending nothing,
+    /// if there's none.
+    pub fn start_session(&mut self, cmd: Vec<String>) -> bool {
+        if self.state != State::Authenticated || !self.settled() {
+            return false;
+        }
+        self.push(Request::StartSession(cmd));
+        true
+    }
+
+    /// Drops the session, along with anything greetd has yet to answer
+    pub fn cancel_session(&mut self) {
+        self.queue.clear();
+        match &mut self.in_flight {
+            // greetd won't have a session once that's answered
+            Some((Kind::Cancel, _)) => {}
+            Some((_, stale)) => {
+                *stale = true;
+                self.push(Request::CancelSession);
+            }
+            None if self.state == State::Idle => {}
+            None => self.push(Request::CancelSession),
+        }
+    }
+
+    /// Forgets about the request in flight and everything queued, for when
+    /// greetd stopped answering. The connection can't be used after this,
+    /// a late answer would be taken for the answer to the next request.
+    pub fn abandon(&mut self) {
+        self.in_flight = None;
+        self.queue.clear();
+        self.state = State::Idle;
+    }
+
+    /// Takes in a message from greetd. Returns it unless it's stale or only
+    /// acknowledges a cancellation.
+    pub fn receive(&mut self, response: Response) -> Option<Response> {
+        let (kind, stale) = match self.in_flight.take() {
+            Some(in_flight) => in_flight,
+            None => {
+                eprintln!("Ignoring a message from greetd that answers nothing");
+                return None;
+            }
+        };
+        self.state = match (kind, &response) {
+            (Kind::Cancel, _) | (_, Response::Error { .. }) => State::Idle,
+            (_, Response::AuthMessage { .. }) => State::Prompted,
+            (Kind::Start, _) | (_, Response::Finish) => State::Started,
+            (_, Response::Success) => State::Authenticated,
+        };
+        if let Some(next) = self.queue.pop_front() {
+            self.push(next);
+        }
+        (!stale && kind != Kind::Cancel).then(|| response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use greetd_client::{AuthMessageType, ErrorType};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    impl Sink for RefCell<VecDeque<Request>> {
+        fn send(&self, request: Request) {
+            self.borrow_mut().push_back(request);
+        }
+    }
+
+    /// What the user does, or greetd answering the oldest request
+    #[derive(Clone, Debug)]
+    enum Action {
+        Login,
+        Submit,
+        Start,
+        Cancel,
+        Answer(u8),
+    }
+
+    fn action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            Just(Action::Login),
+            Just(Action::Submit),
+            Just(Action::Start),
+            Just(Action::Cancel),
+            any::<u8>().prop_map(Action::Answer),
+        ]
+    }
+
+    /// greetd's side: the session it has, and what it answers `request` with,
+    /// `pick` choosing among what it could say. Panics on requests greetd
+    /// would refuse as out of turn.
+    fn answer(session: &mut State, request: Request, pick: u8) -> Response {
+        let error = |error_type| Response::Error {
+            error_type,
+            description: String::new(),
+        };
+        let question = || Response::AuthMessage {
+            auth_message_type: match pick % 4 {
+                0 => AuthMessageType::Visible,
+                1 => AuthMessageType::Secret,
+                2 => AuthMessageType::Info,
+                _ => AuthMessageType::Error,
+            },
+            auth_message: String::new(),
+        };
+        let response = match request {
+            Request::CreateSession(_) => {
+                assert_eq!(*session, State::Idle, "created a session over another");
+                match pick % 6 {
+                    0 => Response::Success,
+                    1 => error(ErrorType::Error),
+                    _ => question(),
+                }
+            }
+            Request::AuthenticationResponse(_) => {
+                assert_eq!(*session, State::Prompted, "answered no question");
+                match pick % 6 {
+                    0 => Response::Success,
+                    1 => error(ErrorType::AuthError),
+                    _ => question(),
+                }
+            }
+            Request::StartSession(_) => {
+                assert_eq!(*session, State::Authenticated, "started no session");
+                match pick % 3 {
+                    0 => error(ErrorType::Error),
+                    _ => Response::Success,
+                }
+            }
+            Request::CancelSession => {
+                *session = State::Idle;
+                return Response::Success;
+            }
+        };
+        *session = match &response {
+            Response::AuthMessage { .. } => State::Prompted,
+            Response::Success if *session == State::Authenticated => State::Started,
+            Response::Success => State::Authenticated,
+            _ => State::Idle,
+        };
+        response
+    }
+
+    #[test]
+    fn fake_failure_answers_nothing() {
+        let mut auth = Auth::new(RefCell::new(VecDeque::new()), UsernamePolicy::default());
+        auth.create_session("nobody").unwrap();
+        auth.sink.borrow_mut().pop_front();
+        // greetd refusing the username goes on to the made up prompt
+        let refused = Response::Error {
+            error_type: ErrorType::Error,
+            description: String::new(),
+        };
+        assert!(auth.receive(refused).is_some());
+        assert_eq!(auth.state, State::Idle);
+        assert!(auth.settled());
+        // The prompt failing isn't greetd's answer to anything
+        assert!(auth.receive(fake_failure()).is_none());
+        assert_eq!(auth.state, State::Idle);
+        // and the next try starts over like after a wrong password
+        auth.create_session("nobody").unwrap();
+        assert!(matches!(
+            auth.sink.borrow_mut().pop_front(),
+            Some(Request::CreateSession(name)) if name == "nobody"
+        ));
+    }
+
+    #[test]
+    fn abandons_a_hung_request() {
+        let mut auth = Auth::new(RefCell::new(VecDeque::new()), UsernamePolicy::default());
+        auth.create_session("user").unwrap();
+        auth.cancel_session();
+        assert!(!auth.settled());
+        auth.abandon();
+        assert!(auth.settled());
+        assert_eq!(auth.state, State::Idle);
+        // Only the hung request was ever sent, the cancellation was dropped
+        assert_eq!(auth.sink.borrow().len(), 1);
+    }
+
+    proptest! {
+        #[test]
+        fn follows_greetd(actions in prop::collection::vec(action(), 0..64)) {
+            let mut auth = Auth::new(RefCell::new(VecDeque::new()), UsernamePolicy::default());
+            let mut session = State::Idle;
+            for action in actions {
+                match action {
+                    Action::Login => auth.create_session("user").unwrap(),
+                    Action::Submit => {
+                        auth.authentication_response(Some("password"));
+                    }
+                    Action::Start => {
+                        auth.start_session(Vec::new());
+                    }
+                    Action::Cancel => auth.cancel_session(),
+                    Action::Answer(pick) => {
+                        let request = auth.sink.borrow_mut().pop_front();
+                        if let Some(request) = request {
+                            let cancel = matches!(request, Request::CancelSession);
+                            let response = answer(&mut session, request, pick);
+                            let passed = auth.receive(response).is_some();
+                            prop_assert!(!(cancel && passed), "passed on a cancellation's answer");
+                        }
+                    }
+                }
+                let sent = auth.sink.borrow().len();
+                prop_assert!(sent <= 1, "{} requests unanswered", sent);
+                prop_assert_eq!(auth.settled(), sent == 0);
+                if auth.settled() {
+                    prop_assert_eq!(auth.state, session);
+                }
+            }
+        }
+
+        #[test]
+        fn drops_cancelled_answers(picks in prop::collection::vec(any::<u8>(), 2..8)) {
+            let mut auth = Auth::new(RefCell::new(VecDeque::new()), UsernamePolicy::default());
+            let mut session = State::Idle;
+            auth.create_session("user").unwrap();
+            auth.cancel_session();
+            for pick in picks {
+                let request = match auth.sink.borrow_mut().pop_front() {
+                    Some(request) => request,
+                    None => break,
+                };
+                prop_assert!(auth.receive(answer(&mut session, request, pick)).is_none());
+            }
+            prop_assert_eq!(auth.state, State::Idle);
+        }
+    }
+}
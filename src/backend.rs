@@ -0,0 +1,156 @@
+//! The windowing backend, which is the glutin and winit forks. It isn't
+//! ported to current winit and glutin: upstream winit has no KMS/DRM backend
+//! to run without a display server. The forks' types are re-exported as
+//! they are, so code using them still depends on the forks' API. Only
+//! context creation, telling the backends apart and handing out native
+//! handles live here.
+
+use std::ffi::c_void;
+
+pub use glutin::{
+    dpi, event, event_loop,
+    platform::{
+        run_return::EventLoopExtRunReturn,
+        unix::{EventLoopWindowTargetExtUnix, WindowExtUnix},
+    },
+    window,
+};
+
+use crate::config::{GlApi, GlContext};
+use event_loop::{EventLoop, EventLoopWindowTarget};
+
+/// The window and the GL context made current on it
+pub type Display = glutin::ContextWrapper<glutin::PossiblyCurrent, window::Window>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Drm,
+    Wayland,
+    X11,
+}
+
+impl Kind {
+    pub fn of<T>(target: &EventLoopWindowTarget<T>) -> Self {
+        if target.drm_calloop_handle().is_some() {
+            Kind::Drm
+        } else if target.is_wayland() {
+            Kind::Wayland
+        } else {
+            Kind::X11
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Drm => "drm",
+            Kind::Wayland => "wayland",
+            Kind::X11 => "x11",
+        }
+    }
+}
+
+/// The display server connection, for libraries that talk to it themselves
+pub enum NativeDisplay {
+    Wayland(*mut c_void),
+    X11(*mut c_void),
+}
+
+pub fn native_display<T>(
+    target: &EventLoopWindowTarget<T>,
+    window: &window::Window,
+) -> Option<NativeDisplay> {
+    if let Some(display) = target.wayland_display() {
+        Some(NativeDisplay::Wayland(display))
+    } else {
+        window.xlib_display().map(NativeDisplay::X11)
+    }
+}
+
+/// The GL context's own handles, for libraries that share it
+pub enum NativeContext {
+    Egl {
+        context: *const c_void,
+        display: *const c_void,
+    },
+    Glx {
+        context: *const c_void,
+        display: *mut c_void,
+    },
+}
+
+pub fn native_context(display: &Display) -> Option<NativeContext> {
+    use glutin::platform::{unix::RawHandle, ContextTraitExt};
+    match unsafe { display.raw_handle() } {
+        RawHandle::Egl(context) => Some(NativeContext::Egl {
+            context,
+            display: unsafe { display.get_egl_display() }?,
+        }),
+        RawHandle::Glx(context) => Some(NativeContext::Glx {
+            context: context as *const c_void,
+            display: display.window().xlib_display()?,
+        }),
+    }
+}
+
+fn build<T>(
+    event_loop: &EventLoop<T>,
+    context: &GlContext,
+    vsync: bool,
+    ten_bit: bool,
+) -> Result<glutin::WindowedContext<glutin::NotCurrent>, glutin::CreationError> {
+    use glutin::{Api, GlRequest};
+    let version = context.version.map(|[major, minor]| (major, minor));
+    let request = match (context.api, version) {
+        (GlApi::Latest, _) => GlRequest::Latest,
+        (GlApi::Auto, _) => GlRequest::GlThenGles {
+            opengl_version: (3, 0),
+            opengles_version: (2, 0),
+        },
+        (GlApi::Gl, None) => GlRequest::Specific(Api::OpenGl, (3, 0)),
+        (GlApi::Gles, None) => GlRequest::Specific(Api::OpenGlEs, (2, 0)),
+        (GlApi::Gl, Some(version)) => GlRequest::Specific(Api::OpenGl, version),
+        (GlApi::Gles, Some(version)) => GlRequest::Specific(Api::OpenGlEs, version),
+    };
+    let mut builder = glutin::ContextBuilder::new()
+        .with_vsync(vsync)
+        .with_gl(request)
+        .with_srgb(context.srgb);
+    if ten_bit {
+        builder = builder.with_pixel_format(30, 2);
+    }
+    builder.build_windowed(
+        window::WindowBuilder::new().with_resizable(true),
+        event_loop,
+    )
+}
+
+/// Opens the window with the first of `contexts` that can be created, each
+/// tried with a 10-bit surface first when `ten_bit` is set
+pub fn create_display<T>(
+    event_loop: &EventLoop<T>,
+    contexts: &[GlContext],
+    vsync: bool,
+    ten_bit: bool,
+) -> Result<Display, String> {
+    let mut contexts = contexts.iter().peekable();
+    let windowed = loop {
+        let context = contexts.next().ok_or("No GL context to try")?;
+        let windowed = if ten_bit {
+            build(event_loop, context, vsync, true).or_else(|e| {
+                eprintln!("No 10-bit surface available, falling back to SDR: {}", e);
+                build(event_loop, context, vsync, false)
+            })
+        } else {
+            build(event_loop, context, vsync, false)
+        };
+        match windowed {
+            Err(e) if contexts.peek().is_some() => {
+                eprintln!("Failed to create a GL context with {:?}: {}", context, e)
+            }
+            Err(e) => return Err(format!("Failed to create a GL context: {}", e)),
+            Ok(windowed) => break windowed,
+        }
+    };
+    unsafe { windowed.make_current() }
+        .map_err(|(_, e)| format!("Failed to make the GL context current: {}", e))
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use rand::prelude::IteratorRandom;
+use serde::Deserialize;
+
+use crate::{
+    animation::Animation,
+    backend::event_loop::EventLoopProxy,
+    cache::{self, MediaCache, MediaKind},
+    config::Fleet,
+    palette, trust, UserEvent,
+};
+
+#[derive(Deserialize, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Caption {
+    #[default]
+    None,
+    /// The background's file name
+    FileName,
+    /// The contents of `<background>.txt`, or the file name without one
+    Sidecar,
+}
+
+/// Resolves the `--background` argument to a file, picking one at random if
+/// it is a directory. The flag is true when the file was picked at random.
+///
+/// Files the media cache knows aren't pictures or videos are skipped, and
+/// the cache is refreshed in the background for next time.
+pub fn pick(path: &str) -> Option<(String, bool)> {
+    if is_stream(path) {
+        Some((path.to_string(), false))
+    } else if Path::new(path).is_dir() {
+        MediaCache::refresh_in_background(path.into());
+        let path = MediaCache::with(|cache| {
+            std::fs::read_dir(path)
+                .ok()?
+                .filter_map(|e| Some(e.ok()?.path()))
+                .filter(|p| cache.get(p) != Some(MediaKind::Other))
+                .choose(&mut rand::rngs::OsRng)
+        })?
+        .to_str()?
+        .to_string();
+        Some((path, true))
+    } else if Path::new(path).exists() {
+        Some((path.to_string(), false))
+    } else {
+        None
+    }
+}
+
+pub fn caption(path: &str, mode: Caption) -> Option<String> {
+    let file_name = || Some(Path::new(path).file_name()?.to_string_lossy().into_owned());
+    match mode {
+        Caption::None => None,
+        Caption::FileName => file_name(),
+        Caption::Sidecar => std::fs::read_to_string(format!("{}.txt", path))
+            .ok()
+            .map(|caption| caption.trim().to_string())
+            .or_else(file_name),
+    }
+}
+
+/// Whether `path` is a URL for mpv or GStreamer to stream, like
+/// `https://…/stream.m3u8` or `rtsp://…`
+pub fn is_stream(path: &str) -> bool {
+    path.contains("://")
+}
+
+pub fn is_image(path: &str) -> Option<bool> {
+    if is_stream(path) {
+        return Some(false);
+    }
+    let path = Path::new(path);
+    let kind = MediaCache::with(|cache| cache.get(path)).unwrap_or_else(|| cache::probe(path));
+    Some(kind == MediaKind::Image)
+}
+
+/// The cached thumbnail of `path` if it's a big picture, or `path` itself
+fn shown(path: &str) -> String {
+    MediaCache::with(|cache| cache.thumbnail(Path::new(path)))
+        .and_then(|thumbnail| Some(thumbnail.to_str()?.to_string()))
+        .unwrap_or_else(|| path.to_string())
+}
+
+pub enum Media {
+    Animation(Animation),
+    /// For the video backend, which has to be set up on the main thread
+    Video {
+        is_image: bool,
+    },
+}
+
+/// A background [`load`] got ready
+pub struct Loaded {
+    pub path: String,
+    /// Set when `path` was picked at random from a directory
+    pub random: bool,
+    pub caption: Option<String>,
+    /// The picture's dominant color, when accents come from the background
+    pub accent: Option<[u8; 3]>,
+    pub media: Media,
+}
+
+impl std::fmt::Debug for Loaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Loaded").field("path", &self.path).finish()
+    }
+}
+
+/// Picks, probes and decodes `background` on a separate thread and sends
+/// [`UserEvent::BackgroundLoaded`] when done, so a slow disk or network
+/// share doesn't hold up the login form
+pub fn load(
+    background: String,
+    caption_mode: Caption,
+    accent: bool,
+    fleet: Option<Fleet>,
+    proxy: EventLoopProxy<UserEvent>,
+) {
+    std::thread::spawn(move || {
+        let (path, random) = match pick(&background) {
+            Some(picked) => picked,
+            None => return,
+        };
+        // Only the directory was checked so far
+        if random && !trust::allowed(Path::new(&path), fleet.as_ref(), "background") {
+            return;
+        }
+        let caption = random.then(|| caption(&path, caption_mode)).flatten();
+        let path = shown(&path);
+        let media = match Animation::open(&path) {
+            Some(animation) => Media::Animation(animation),
+            None => match is_image(&path) {
+                Some(is_image) => Media::Video { is_image },
+                None => return,
+            },
+        };
+        let loaded = Loaded {
+            caption,
+            accent: accent.then(|| palette::dominant(&path)).flatten(),
+            path,
+            random,
+            media,
+        };
+        let _ = proxy.send_event(UserEvent::BackgroundLoaded(loaded));
+    });
+}
+
+/// Another picture or video from the background directory
+pub struct Rerolled {
+    pub path: String,
+    pub is_image: bool,
+    pub caption: Option<String>,
+    pub accent: Option<[u8; 3]>,
+}
+
+impl std::fmt::Debug for Rerolled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rerolled")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Picks another file from `dir` for the video backend on a separate thread
+/// like [`load`] does, then sends [`UserEvent::BackgroundRerolled`]
+pub fn reroll(dir: String, caption_mode: Caption, accent: bool, proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let path = match pick(&dir) {
+            Some((path, _)) => path,
+            None => return,
+        };
+        let is_image = match is_image(&path) {
+            Some(is_image) => is_image,
+            None => return,
+        };
+        let caption = caption(&path, caption_mode);
+        let path = shown(&path);
+        let rerolled = Rerolled {
+            accent: accent.then(|| palette::dominant(&path)).flatten(),
+            path,
+            is_image,
+            caption,
+        };
+        let _ = proxy.send_event(UserEvent::BackgroundRerolled(rerolled));
+    });
+}
@@ -0,0 +1,18 @@
+use unicode_bidi::BidiInfo;
+
+/// `text` in display order. egui lays text out left to right in the order
+/// it's stored, so right-to-left runs have to be reversed before being shown
+/// in labels. This only covers labels: text fields, usernames typed in
+/// them included, are left in logical order, as egui's caret can't follow
+/// reordered text. Nothing is shaped either, so Arabic shows its letters in
+/// their isolated forms.
+pub fn visual(text: &str) -> String {
+    let info = BidiInfo::new(text, None);
+    if !info.has_rtl() {
+        return text.to_string();
+    }
+    info.paragraphs
+        .iter()
+        .map(|para| info.reorder_line(para, para.range.clone()))
+        .collect()
+}
@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use zbus::{
+    blocking::{Connection, ConnectionBuilder, Proxy},
+    zvariant::{OwnedObjectPath, OwnedValue},
+};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+const AGENT_PATH: &str = "/org/eguigreeter/BluetoothAgent";
+
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// A keyboard BlueZ has seen
+#[derive(Clone, PartialEq)]
+pub struct Keyboard {
+    path: OwnedObjectPath,
+    pub name: String,
+    pub paired: bool,
+    pub connected: bool,
+}
+
+/// What the pairing panel shows
+#[derive(Clone, Default, PartialEq)]
+pub struct Status {
+    /// Whether there's an adapter to pair with at all
+    pub available: bool,
+    pub keyboards: Vec<Keyboard>,
+    /// Digits to type on the keyboard being paired, followed by Enter
+    pub passkey: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Looks for Bluetooth keyboards through BlueZ and pairs the one picked,
+/// for when there's no other way to type at the greeter. Passkeys are shown
+/// by an agent registered as display only, which refuses every device but
+/// the one picked. Discovery stops and the agent goes away when dropped.
+pub struct Pairing {
+    status: Arc<Mutex<Status>>,
+    pair: Option<Sender<OwnedObjectPath>>,
+    /// Closed once the worker has cleaned up after itself
+    done: Receiver<()>,
+}
+
+impl Pairing {
+    pub fn start(proxy: EventLoopProxy<UserEvent>) -> Pairing {
+        let status = Arc::new(Mutex::new(Status::default()));
+        let (pair, requests) = mpsc::channel();
+        let (finished, done) = mpsc::channel::<()>();
+        let shared = Shared {
+            status: status.clone(),
+            proxy: Mutex::new(proxy),
+            chosen: Mutex::new(None),
+        };
+        std::thread::spawn(move || {
+            let _finished = finished;
+            if let Err(e) = run(Arc::new(shared), requests) {
+                eprintln!("Failed to look for Bluetooth keyboards: {}", e);
+            }
+        });
+        Pairing {
+            status,
+            pair: Some(pair),
+            done,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn pair(&self, keyboard: &Keyboard) {
+        if let Some(pair) = &self.pair {
+            let _ = pair.send(keyboard.path.clone());
+        }
+    }
+}
+
+impl Drop for Pairing {
+    fn drop(&mut self) {
+        // Hanging up tells the worker to stop, unless it's stuck pairing
+        self.pair = None;
+        let _ = self.done.recv_timeout(Duration::from_secs(1));
+    }
+}
+
+struct Shared {
+    status: Arc<Mutex<Status>>,
+    proxy: Mutex<EventLoopProxy<UserEvent>>,
+    /// The keyboard picked in the panel, the only device the agent serves
+    chosen: Mutex<Option<OwnedObjectPath>>,
+}
+
+impl Shared {
+    fn update(&self, change: impl FnOnce(&mut Status)) {
+        let mut status = self.status.lock().unwrap();
+        let before = status.clone();
+        change(&mut status);
+        if *status != before {
+            let _ = self.proxy.lock().unwrap().send_event(UserEvent::Repaint);
+        }
+    }
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[dbus_error(prefix = "org.bluez.Error")]
+enum AgentError {
+    #[dbus_error(zbus_error)]
+    ZBus(zbus::Error),
+    Rejected(String),
+}
+
+struct Agent(Arc<Shared>);
+
+impl Agent {
+    fn show(&self, passkey: Option<String>) {
+        self.0.update(|status| status.passkey = passkey);
+    }
+
+    /// Refuses anything but the keyboard picked in the panel, devices in
+    /// range don't get to pair with the greeter on their own
+    fn check(&self, device: &OwnedObjectPath) -> Result<(), AgentError> {
+        if self.0.chosen.lock().unwrap().as_ref() == Some(device) {
+            Ok(())
+        } else {
+            Err(AgentError::Rejected(format!(
+                "{} wasn't picked",
+                device.as_str()
+            )))
+        }
+    }
+}
+
+#[zbus::dbus_interface(name = "org.bluez.Agent1")]
+impl Agent {
+    fn release(&self) {}
+
+    fn request_pin_code(&self, device: OwnedObjectPath) -> Result<String, AgentError> {
+        self.check(&device)?;
+        let mut bytes = [0u8; 4];
+        unsafe { libc::getrandom(bytes.as_mut_ptr().cast(), bytes.len(), 0) };
+        let pin = format!("{:06}", u32::from_ne_bytes(bytes) % 1_000_000);
+        self.show(Some(pin.clone()));
+        Ok(pin)
+    }
+
+    fn display_pin_code(&self, device: OwnedObjectPath, pincode: String) -> Result<(), AgentError> {
+        self.check(&device)?;
+        self.show(Some(pincode));
+        Ok(())
+    }
+
+    fn display_passkey(&self, device: OwnedObjectPath, passkey: u32, _entered: u16) {
+        if self.check(&device).is_ok() {
+            self.show(Some(format!("{:06}", passkey)));
+        }
+    }
+
+    // Accepting is all a display only agent can do, for the picked keyboard
+    fn request_confirmation(
+        &self,
+        device: OwnedObjectPath,
+        passkey: u32,
+    ) -> Result<(), AgentError> {
+        self.check(&device)?;
+        self.show(Some(format!("{:06}", passkey)));
+        Ok(())
+    }
+
+    fn request_authorization(&self, device: OwnedObjectPath) -> Result<(), AgentError> {
+        self.check(&device)
+    }
+
+    fn authorize_service(&self, device: OwnedObjectPath, _uuid: String) -> Result<(), AgentError> {
+        self.check(&device)
+    }
+
+    fn cancel(&self) {
+        self.show(None);
+    }
+}
+
+fn bluez<'a>(conn: &Connection, path: &'a str, interface: &'a str) -> zbus::Result<Proxy<'a>> {
+    Proxy::new(conn, "org.bluez", path, interface)
+}
+
+fn run(shared: Arc<Shared>, requests: Receiver<OwnedObjectPath>) -> zbus::Result<()> {
+    let conn = ConnectionBuilder::system()?
+        .serve_at(AGENT_PATH, Agent(shared.clone()))?
+        .build()?;
+    let objects = bluez(&conn, "/", "org.freedesktop.DBus.ObjectManager")?;
+    let managed = || objects.call::<_, _, ManagedObjects>("GetManagedObjects", &());
+    let adapter_path = match managed()?
+        .into_iter()
+        .find(|(_, interfaces)| interfaces.contains_key("org.bluez.Adapter1"))
+    {
+        Some((path, _)) => path,
+        None => return Ok(()),
+    };
+    let adapter = bluez(&conn, adapter_path.as_str(), "org.bluez.Adapter1")?;
+    // Bluetooth turned off is left off
+    if !adapter.get_property::<bool>("Powered")? {
+        return Ok(());
+    }
+    // Only serves pairings started here, it's not made the default agent
+    let manager = bluez(&conn, "/org/bluez", "org.bluez.AgentManager1")?;
+    let agent = OwnedObjectPath::try_from(AGENT_PATH).unwrap();
+    manager.call::<_, _, ()>("RegisterAgent", &(&agent, "DisplayOnly"))?;
+    adapter.call::<_, _, ()>("StartDiscovery", &())?;
+    shared.update(|status| status.available = true);
+
+    let result = discover(&shared, &conn, &requests, &managed);
+    let _ = adapter.call::<_, _, ()>("StopDiscovery", &());
+    let _ = manager.call::<_, _, ()>("UnregisterAgent", &(&agent,));
+    result
+}
+
+/// Lists keyboards and pairs the ones picked until the panel goes away
+fn discover(
+    shared: &Shared,
+    conn: &Connection,
+    requests: &Receiver<OwnedObjectPath>,
+    managed: impl Fn() -> zbus::Result<ManagedObjects>,
+) -> zbus::Result<()> {
+    loop {
+        let keyboards = managed()?
+            .into_iter()
+            .filter_map(|(path, interfaces)| {
+                let device = interfaces.get("org.bluez.Device1")?;
+                let text = |name: &str| device.get(name)?.downcast_ref::<str>().map(str::to_string);
+                let flag = |name: &str| {
+                    device
+                        .get(name)
+                        .and_then(|v| v.downcast_ref::<bool>().copied())
+                };
+                text("Icon").filter(|icon| icon == "input-keyboard")?;
+                Some(Keyboard {
+                    name: text("Alias").or_else(|| text("Address"))?,
+                    paired: flag("Paired").unwrap_or(false),
+                    connected: flag("Connected").unwrap_or(false),
+                    path,
+                })
+            })
+            .collect();
+        shared.update(|status| status.keyboards = keyboards);
+
+        let path = match requests.recv_timeout(Duration::from_secs(2)) {
+            Ok(path) => path,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        shared.update(|status| status.message = Some("Pairing…".to_string()));
+        *shared.chosen.lock().unwrap() = Some(path.clone());
+        let device = bluez(conn, path.as_str(), "org.bluez.Device1")?;
+        // Already paired keyboards only need connecting
+        let result = match device.get_property::<bool>("Paired") {
+            Ok(true) => Ok(()),
+            _ => device.call::<_, _, ()>("Pair", &()),
+        }
+        .and_then(|_| Ok(device.set_property("Trusted", true)?))
+        .and_then(|_| device.call::<_, _, ()>("Connect", &()));
+        *shared.chosen.lock().unwrap() = None;
+        shared.update(|status| {
+            status.passkey = None;
+            status.message = match result {
+                Ok(()) => None,
+                Err(e) => Some(format!("Pairing failed: {}", e)),
+            };
+        });
+    }
+}
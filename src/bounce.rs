@@ -0,0 +1,165 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{handoff, state::STATE_DIR, users::Passwd};
+
+/// The session the greeter last handed over to, so the next greeter can
+/// tell whether it came straight back
+#[derive(Serialize, Deserialize)]
+struct Started {
+    username: String,
+    session: String,
+    /// Seconds since the epoch
+    at: u64,
+}
+
+/// A session that exited with an error right after it started
+pub struct Bounce {
+    pub username: String,
+    pub session: String,
+    /// How it exited, e.g. `exited with status 1`
+    pub status: String,
+    /// The end of the session's log, oldest line first
+    pub log: Vec<String>,
+}
+
+fn path() -> PathBuf {
+    Path::new(STATE_DIR).join("last_session.toml")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Remembers that `session` was just started for `username`
+pub fn started(username: &str, session: &str) {
+    let started = Started {
+        username: username.to_string(),
+        session: session.to_string(),
+        at: now(),
+    };
+    let _ = std::fs::create_dir_all(STATE_DIR);
+    if let Err(e) = toml::to_string(&started)
+        .map_err(|e| e.to_string())
+        .and_then(|s| std::fs::write(path(), s).map_err(|e| e.to_string()))
+    {
+        eprintln!("Failed to save {}: {}", path().display(), e);
+    }
+}
+
+/// The user's entries in greetd's journal since `since`, which is where the
+/// session's stderr goes
+fn journal(since: u64, uid: u32, lines: usize) -> Vec<String> {
+    let output = Command::new("journalctl")
+        .args([
+            "--no-pager",
+            "--quiet",
+            "--output=cat",
+            &format!("--lines={}", lines),
+            &format!("--since=@{}", since),
+            &format!("_UID={}", uid),
+            "_SYSTEMD_UNIT=greetd.service",
+        ])
+        .output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to run journalctl: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Whether a session started at `at` ended too soon after to have been
+/// used, as of `now`
+fn quick(at: u64, now: u64, within: Duration) -> bool {
+    now.checked_sub(at)
+        .map_or(false, |lasted| Duration::from_secs(lasted) < within)
+}
+
+/// Takes the handoff stage's line, the last thing the session logs, off the
+/// end of `log`. `None` if the session didn't exit with an error.
+fn report(started: Started, mut log: Vec<String>) -> Option<Bounce> {
+    let status = log
+        .last()?
+        .strip_prefix(handoff::FAILED)?
+        .trim()
+        .to_string();
+    log.pop();
+    Some(Bounce {
+        username: started.username,
+        session: started.session,
+        status,
+        log,
+    })
+}
+
+/// The last session, if it ended less than `within` after it started and
+/// the handoff stage logged that it exited with an error, with the last
+/// `lines` lines it logged. The greeter's user has to be allowed to read the
+/// journal, e.g. by being in `systemd-journal`.
+pub fn check(within: Duration, lines: usize) -> Option<Bounce> {
+    let started: Started = toml::from_str(&std::fs::read_to_string(path()).ok()?).ok()?;
+    let _ = std::fs::remove_file(path());
+    if !quick(started.at, now(), within) {
+        return None;
+    }
+    let user = Passwd::lookup(&started.username)?;
+    let log = journal(started.at, user.uid, lines + 1);
+    report(started, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started() -> Started {
+        Started {
+            username: "alice".to_string(),
+            session: "Sway".to_string(),
+            at: 1000,
+        }
+    }
+
+    #[test]
+    fn only_quick_exits_count() {
+        let within = Duration::from_secs(10);
+        assert!(quick(1000, 1000, within));
+        assert!(quick(1000, 1009, within));
+        assert!(!quick(1000, 1010, within));
+        // A session that ran for hours isn't a crash, whatever its status
+        assert!(!quick(1000, 1000 + 3 * 3600, within));
+        // Nor is one from a clock that went backwards
+        assert!(!quick(1000, 999, within));
+    }
+
+    #[test]
+    fn reads_the_status_off_the_log() {
+        let log = vec![
+            "sway: cannot open display".to_string(),
+            format!("{} exited with status 1", handoff::FAILED),
+        ];
+        let bounce = report(started(), log).unwrap();
+        assert_eq!(bounce.username, "alice");
+        assert_eq!(bounce.status, "exited with status 1");
+        assert_eq!(bounce.log, ["sway: cannot open display"]);
+    }
+
+    #[test]
+    fn clean_exits_arent_reported() {
+        let log = vec!["sway: bye".to_string()];
+        assert!(report(started(), log).is_none());
+        assert!(report(started(), Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,26 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+/// Watches `path` for fleet tooling to drop notices into, sending
+/// [`UserEvent::Broadcast`] with the file's contents whenever it appears,
+/// changes or is removed
+pub fn watch(path: PathBuf, proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let mut last = None;
+        loop {
+            let current = path.metadata().and_then(|m| m.modified()).ok();
+            if current != last {
+                last = current;
+                let message = std::fs::read_to_string(&path)
+                    .ok()
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty());
+                if proxy.send_event(UserEvent::Broadcast(message)).is_err() {
+                    return;
+                }
+            }
+            std::thread::sleep(Duration::from_secs(2));
+        }
+    });
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fs::Metadata,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use image::{imageops::FilterType, ImageFormat};
+use infer::MatcherType;
+use serde::{Deserialize, Serialize};
+
+pub const CACHE_DIR: &str = "/var/cache/eguigreeter";
+
+/// Pictures bigger than this get a thumbnail to show instead
+const THUMBNAIL_OVER: u64 = 2 << 20;
+/// Big enough to fill a 4K screen
+const THUMBNAIL_SIZE: (u32, u32) = (3840, 2160);
+
+/// Read from disk once per run, the refresh thread updates it in place
+static SHARED: Mutex<Option<MediaCache>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Video,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Entry {
+    modified: u64,
+    len: u64,
+    kind: MediaKind,
+    /// A scaled down copy to show instead, for big pictures
+    #[serde(default)]
+    thumbnail: Option<PathBuf>,
+}
+
+/// Remembers what kind of media each file in the background directory is,
+/// so startup doesn't have to sniff every file, and keeps thumbnails of big
+/// pictures so it doesn't have to decode them either
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MediaCache {
+    entries: BTreeMap<String, Entry>,
+}
+
+fn modified(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn probe(path: &Path) -> MediaKind {
+    match infer::get_from_path(path).ok().flatten() {
+        Some(mime) if mime.matcher_type() == MatcherType::Image => MediaKind::Image,
+        Some(mime) if mime.matcher_type() == MatcherType::Video => MediaKind::Video,
+        _ => MediaKind::Other,
+    }
+}
+
+/// Writes a copy of the still picture at `path` that fits
+/// [`THUMBNAIL_SIZE`], returning where
+fn thumbnail(path: &Path, metadata: &Metadata) -> Option<PathBuf> {
+    let format = ImageFormat::from_path(path).ok()?;
+    if metadata.len() <= THUMBNAIL_OVER
+        || !matches!(
+            format,
+            ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+        )
+    {
+        return None;
+    }
+    let image = image::open(path).ok()?;
+    let (width, height) = THUMBNAIL_SIZE;
+    if image.width() <= width && image.height() <= height {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    let dir = Path::new(CACHE_DIR).join("thumbnails");
+    let thumbnail = dir.join(format!("{:016x}.jpg", hasher.finish()));
+    let temp = thumbnail.with_extension("jpg.tmp");
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|_| {
+            image
+                .resize(width, height, FilterType::Triangle)
+                .to_rgb8()
+                .save_with_format(&temp, ImageFormat::Jpeg)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        })
+        .and_then(|_| std::fs::rename(&temp, &thumbnail));
+    match result {
+        Ok(()) => Some(thumbnail),
+        Err(e) => {
+            eprintln!("Failed to save a thumbnail of {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+impl MediaCache {
+    fn path() -> PathBuf {
+        Path::new(CACHE_DIR).join("backgrounds.toml")
+    }
+
+    fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let path = Self::path();
+        let temp = path.with_extension("toml.tmp");
+        let result = std::fs::create_dir_all(CACHE_DIR)
+            .and_then(|_| {
+                let s = toml::to_string(self)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                std::fs::write(&temp, s)
+            })
+            .and_then(|_| std::fs::rename(&temp, &path));
+        if let Err(e) = result {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    /// Runs `f` on the cache, reading it from disk the first time
+    pub fn with<T>(f: impl FnOnce(&MediaCache) -> T) -> T {
+        f(SHARED.lock().unwrap().get_or_insert_with(MediaCache::load))
+    }
+
+    fn entry(&self, path: &Path) -> Option<&Entry> {
+        let metadata = path.metadata().ok()?;
+        let entry = self.entries.get(path.to_str()?)?;
+        (entry.modified == modified(&metadata) && entry.len == metadata.len()).then(|| entry)
+    }
+
+    /// The cached kind of `path`, if the file hasn't changed since it was probed
+    pub fn get(&self, path: &Path) -> Option<MediaKind> {
+        self.entry(path).map(|entry| entry.kind)
+    }
+
+    /// The thumbnail to show for `path`, if it has an up to date one
+    pub fn thumbnail(&self, path: &Path) -> Option<PathBuf> {
+        self.entry(path)?
+            .thumbnail
+            .clone()
+            .filter(|thumbnail| thumbnail.is_file())
+    }
+
+    /// Probes and makes thumbnails of every file in `dir` the cache doesn't
+    /// know about yet on a separate thread and writes the result back
+    pub fn refresh_in_background(dir: PathBuf) {
+        std::thread::spawn(move || {
+            let mut cache = MediaCache::with(MediaCache::clone);
+            let mut changed = false;
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for path in entries.filter_map(|e| Some(e.ok()?.path())) {
+                if cache.get(&path).is_some() {
+                    continue;
+                }
+                let (metadata, key) = match (path.metadata(), path.to_str()) {
+                    (Ok(metadata), Some(key)) => (metadata, key.to_string()),
+                    _ => continue,
+                };
+                let kind = probe(&path);
+                cache.entries.insert(
+                    key,
+                    Entry {
+                        modified: modified(&metadata),
+                        len: metadata.len(),
+                        kind,
+                        thumbnail: (kind == MediaKind::Image)
+                            .then(|| thumbnail(&path, &metadata))
+                            .flatten(),
+                    },
+                );
+                changed = true;
+            }
+            if changed {
+                cache.save();
+                *SHARED.lock().unwrap() = Some(cache);
+            }
+        });
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+use time::{
+    format_description::{
+        modifier::{Hour, Minute, Second},
+        Component, FormatItem,
+    },
+    OffsetDateTime, UtcOffset,
+};
+use tz::TimeZone;
+
+/// The current UTC offset of `timezone`
+pub fn offset(timezone: &TimeZone) -> Option<UtcOffset> {
+    let offset = timezone.find_current_local_time_type().ok()?.ut_offset();
+    UtcOffset::from_whole_seconds(offset).ok()
+}
+
+/// The current UTC offset of a zone like `Asia/Tokyo` or `UTC`
+pub fn zone_offset(zone: &str) -> Option<UtcOffset> {
+    offset(&TimeZone::from_posix_tz(zone).ok()?)
+}
+
+/// Formats the current time at `offset` as a 12-hour clock
+pub fn format(offset: UtcOffset, seconds: bool) -> String {
+    let hour = FormatItem::Component(Component::Hour({
+        let mut h = Hour::default();
+        h.is_12_hour_clock = true;
+        h
+    }));
+    let minute = FormatItem::Component(Component::Minute(Minute::default()));
+    let second = FormatItem::Component(Component::Second(Second::default()));
+    let items: &[FormatItem] = if seconds {
+        &[
+            hour,
+            FormatItem::Literal(b":"),
+            minute,
+            FormatItem::Literal(b":"),
+            second,
+        ]
+    } else {
+        &[hour, FormatItem::Literal(b":"), minute]
+    };
+    OffsetDateTime::now_utc()
+        .to_offset(offset)
+        .format(items)
+        .unwrap_or_else(|_| "??:??".to_string())
+}
+
+/// When the clock next changes, on the next second or minute boundary. Zones
+/// are whole minutes off UTC, so every clock ticks at the same time.
+pub fn next_tick(seconds: bool) -> Instant {
+    let now = OffsetDateTime::now_utc();
+    let into_second = Duration::from_nanos(now.nanosecond() as u64);
+    let left = if seconds {
+        Duration::from_secs(1) - into_second
+    } else {
+        Duration::from_secs(60 - now.second() as u64) - into_second
+    };
+    Instant::now() + left
+}
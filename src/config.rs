@@ -0,0 +1,1140 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{background::Caption, trust, user_data::UserInfo};
+
+pub const DEFAULT_PATH: &str = "/etc/greetd/eguigreeter.toml";
+pub const DEFAULT_WRAPPER: &str = "/etc/ly/wsetup.sh";
+
+/// Every field has a serde default, [`Config::default`] is the config of an
+/// empty file
+#[derive(Deserialize)]
+pub struct Config {
+    /// Session names listed here are shown first, in this order
+    #[serde(default)]
+    pub session_priority: Vec<String>,
+    /// Locale used for translated strings, defaults to `LC_ALL`/`LC_MESSAGES`/`LANG`
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Start sessions through `systemd-run --user --scope` so they get their own cgroup
+    #[serde(default)]
+    pub systemd_scope: bool,
+    /// Script the session's `Exec` is passed to, an empty string runs it directly
+    #[serde(default)]
+    pub wrapper: Option<String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Extra environment variables for the started session
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Clear the typed username after a failed login instead of keeping it
+    #[serde(default)]
+    pub clear_username_on_failure: bool,
+    #[serde(default)]
+    pub fade_in: FadeIn,
+    #[serde(default)]
+    pub plymouth: Plymouth,
+    #[serde(default)]
+    pub cursor: Cursor,
+    #[serde(default)]
+    pub login_window: LoginWindow,
+    #[serde(default)]
+    pub numlock: NumLock,
+    #[serde(default)]
+    pub username_policy: UsernamePolicy,
+    #[serde(default)]
+    pub background: Background,
+    #[serde(default)]
+    pub video: VideoConfig,
+    #[serde(default)]
+    pub weather: Weather,
+    /// Show uptime, kernel version and boot time in the bottom right corner
+    #[serde(default)]
+    pub info_line: bool,
+    /// Extra clocks shown under the main one
+    #[serde(default)]
+    pub clocks: Vec<WorldClock>,
+    #[serde(default)]
+    pub curfew: Option<Curfew>,
+    /// File whose contents are shown as a banner while it exists
+    #[serde(default)]
+    pub broadcast_file: Option<PathBuf>,
+    /// Experimental, only enabled when configured
+    #[serde(default)]
+    pub qr_login: Option<QrLogin>,
+    /// Seconds to wait for greetd to answer a password before giving up and
+    /// restarting the greeter on a new connection
+    #[serde(default = "Config::default_auth_timeout")]
+    pub auth_timeout: u64,
+    /// What to do once greetd reports the session has taken over
+    #[serde(default)]
+    pub after_finish: AfterFinish,
+    /// Built-in theme name (`dark`, `light`, `high-contrast`, `solarized`) or
+    /// path to a theme file with colors, fonts and layout
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Rhai script defining `on_frame`, `on_auth_result` or `widget` functions
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+    /// Unix socket serving Prometheus metrics over HTTP
+    #[serde(default)]
+    pub metrics_socket: Option<PathBuf>,
+    /// Seconds the form stays disabled after a failed login, like PAM's
+    /// fail delay
+    #[serde(default = "Config::default_fail_delay")]
+    pub fail_delay: f32,
+    /// Only show the login form after a key chord, like Windows' Ctrl+Alt+Del
+    #[serde(default)]
+    pub secure_attention: Option<SecureAttention>,
+    /// Answer usernames greetd or `username_policy` refuse outright with a
+    /// password prompt that always fails, so valid usernames can't be probed
+    /// from the greeter. Faces and full names are not shown either
+    #[serde(default)]
+    pub hide_unknown_users: bool,
+    /// Pressing Escape twice within this many seconds restarts the greeter,
+    /// 0 (the default) disables it
+    #[serde(default)]
+    pub escape_restart: f32,
+    #[serde(default)]
+    pub magnifier: Magnifier,
+    /// Reading prompts and errors aloud through speech-dispatcher
+    #[serde(default)]
+    pub speech: Speech,
+    #[serde(default)]
+    pub font_fallback: FontFallback,
+    #[serde(default)]
+    pub branding: Branding,
+    /// Only load themes and backgrounds an unprivileged account can't have
+    /// swapped, for managed fleets
+    #[serde(default)]
+    pub fleet: Option<Fleet>,
+    /// Show seconds on the clocks, which wakes the greeter every second
+    #[serde(default)]
+    pub clock_seconds: bool,
+    /// Wait for the display's vertical blank before showing a frame
+    #[serde(default = "Config::yes")]
+    pub vsync: bool,
+    /// Frames per second the greeter draws at most, videos included. 0 leaves
+    /// it to vsync, which on fast panels means their full refresh rate.
+    #[serde(default)]
+    pub max_fps: u32,
+    /// What kind of GL context to ask for, for drivers that misbehave with the
+    /// default one
+    #[serde(default)]
+    pub gl: GlContext,
+    /// Calibration to show the greeter with, matching the session's
+    #[serde(default)]
+    pub icc: Icc,
+    #[serde(default)]
+    pub screenshot: Screenshot,
+    /// Remote assistance over VNC, off unless configured
+    #[serde(default)]
+    pub vnc: Option<Vnc>,
+    #[serde(default)]
+    pub session_crash: SessionCrash,
+    #[serde(default)]
+    pub user_data: UserData,
+    #[serde(default)]
+    pub handoff: Handoff,
+    #[serde(default)]
+    pub on_screen_keyboard: OnScreenKeyboard,
+    #[serde(default)]
+    pub input_limits: InputLimits,
+    /// Answer secret prompts on a numeric pad, for PIN-based PAM modules.
+    /// Users can be switched either way through `user_data`.
+    #[serde(default)]
+    pub pin_pad: bool,
+    /// Answer secret prompts by drawing a pattern on a 3×3 grid, sent as the
+    /// dots' numbers; needs a PAM module that expects that. Per user like
+    /// `pin_pad`, and wins over it.
+    #[serde(default)]
+    pub pattern_unlock: bool,
+    /// Offer to pair a Bluetooth keyboard when no keyboard is plugged in.
+    /// Needs Bluetooth to be on already, the greeter doesn't power it up.
+    #[serde(default)]
+    pub bluetooth_pairing: bool,
+    /// Seconds without input before the screen goes black, 0 never. Input or
+    /// the machine waking up, e.g. over the network, brings it back.
+    #[serde(default)]
+    pub blank_after: f32,
+    /// Fill in the username last logged in with on this seat
+    #[serde(default = "Config::yes")]
+    pub remember_username: bool,
+    /// Pass the accessibility settings chosen at the greeter on to the
+    /// session, as `A11Y_HIGH_CONTRAST`, `A11Y_REDUCE_MOTION`,
+    /// `A11Y_SCREEN_READER`, `A11Y_ON_SCREEN_KEYBOARD` and `A11Y_TEXT_SCALE`
+    #[serde(default)]
+    pub accessibility_env: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        toml::Value::Table(toml::value::Table::new())
+            .try_into()
+            .expect("every config field has a default")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct VideoConfig {
+    /// mpv's `hwdec` option, e.g. `vaapi`, `nvdec`, `auto-safe` or `no`
+    pub hwdec: String,
+    /// Ask for a 10-bit surface and let mpv output HDR on it
+    pub hdr: bool,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        VideoConfig {
+            hwdec: "auto-safe".to_string(),
+            hdr: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Background {
+    /// Used when `--background` isn't given. `shader:<path>` renders a
+    /// fragment shader instead of a picture or video
+    pub path: Option<String>,
+    /// How much to darken the background, from 0 to 1
+    pub dim: f32,
+    /// Saturation multiplier for mpv backgrounds, 0 is greyscale
+    pub saturation: f32,
+    /// Caption shown in the bottom left corner for randomly picked backgrounds
+    pub caption: Caption,
+    /// Seconds to wait for a network stream background to connect
+    pub stream_timeout: u32,
+    /// Local picture shown when a network stream can't be played
+    pub fallback: Option<String>,
+    /// Hold video backgrounds on their first frame while UPower reports
+    /// running on battery
+    pub pause_on_battery: bool,
+    /// Derive the accent and window tint from the picture's dominant color
+    pub accent_from_image: bool,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background {
+            caption: Caption::None,
+            path: None,
+            dim: 0.0,
+            saturation: 1.0,
+            stream_timeout: 10,
+            fallback: None,
+            pause_on_battery: true,
+            accent_from_image: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct UsernamePolicy {
+    /// Strip leading and trailing whitespace
+    pub trim: bool,
+    /// Lowercase the username before logging in
+    pub lowercase: bool,
+    /// Refuse usernames with whitespace in them
+    pub reject_spaces: bool,
+    /// Accounts that can't log in at the greeter
+    pub deny_users: Vec<String>,
+    /// If not empty, the only accounts that can log in at the greeter
+    pub allow_users: Vec<String>,
+    /// Refuse accounts in `/etc/passwd` with a lower UID, like system accounts
+    pub min_uid: Option<u32>,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        UsernamePolicy {
+            trim: true,
+            lowercase: false,
+            reject_spaces: true,
+            deny_users: Vec::new(),
+            allow_users: Vec::new(),
+            min_uid: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NumLock {
+    On,
+    Off,
+    #[default]
+    Keep,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LoginWindow {
+    /// Fixed width of the login window, so it doesn't resize while typing
+    pub width: f32,
+    /// Width of the username and password fields
+    pub field_width: f32,
+    /// Width of the text caret in points
+    pub caret_width: f32,
+    /// Background color of selected text
+    pub selection_color: Option<[u8; 3]>,
+}
+
+impl Default for LoginWindow {
+    fn default() -> Self {
+        LoginWindow {
+            width: 320.0,
+            field_width: 200.0,
+            caret_width: 2.0,
+            selection_color: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Cursor {
+    /// XCursor theme, exported as `XCURSOR_THEME`
+    pub theme: Option<String>,
+    /// Cursor size in pixels, exported as `XCURSOR_SIZE`
+    pub size: Option<u32>,
+    /// Seconds without mouse movement before the cursor is hidden
+    pub hide_after: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Plymouth {
+    /// Take the display over from plymouth instead of waiting for it to exit
+    pub handoff: bool,
+    /// Keep the last splash frame on screen until the greeter has drawn
+    pub retain_splash: bool,
+    /// Color the screen is cleared to behind the background
+    pub color: [u8; 3],
+}
+
+impl Default for Plymouth {
+    fn default() -> Self {
+        Plymouth {
+            handoff: false,
+            retain_splash: true,
+            color: [0, 0, 0],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FadeIn {
+    /// Seconds the UI takes to fade in at startup, 0 disables it
+    pub duration: f32,
+    /// Fade the background in from black along with the UI
+    pub background: bool,
+}
+
+impl Default for FadeIn {
+    fn default() -> Self {
+        FadeIn {
+            duration: 0.3,
+            background: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run when a username is submitted
+    pub on_login: Option<String>,
+    /// Run when greetd reports an authentication error
+    pub on_auth_failure: Option<String>,
+    /// Run, and waited for up to 10 seconds, right before the session is
+    /// started
+    pub on_start_session: Option<String>,
+    /// Run once a session has stayed selected for a second, at most once per
+    /// session, to get it ready ahead of time. E.g. reading its binaries
+    /// into the page cache. Runs as the greeter's user like the others.
+    pub on_session_selected: Option<String>,
+}
+
+impl Config {
+    /// Loads the config merged with the `*.toml` fragments in `<path>.d` in
+    /// lexical order, falling back to the defaults if it's invalid. Invalid
+    /// configs also return a message saying what's wrong. Unknown keys are
+    /// only reported on stderr. In fleet mode, fragments root doesn't own
+    /// alone are skipped.
+    pub fn load(path: impl AsRef<Path>) -> (Self, Option<String>) {
+        let path = path.as_ref();
+        let mut fragments: Vec<PathBuf> = std::fs::read_dir(format!("{}.d", path.display()))
+            .into_iter()
+            .flatten()
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.extension().map(|ext| ext == "toml").unwrap_or(false))
+            .collect();
+        fragments.sort();
+
+        let mut merged = toml::Value::Table(toml::value::Table::new());
+        // Each file's keys, to point errors in the merged config to the
+        // file that set them
+        let mut sources = Vec::new();
+        for file in std::iter::once(path.to_path_buf()).chain(fragments) {
+            let contents = match std::fs::read_to_string(&file) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            // Fragments are only checked once the config turned fleet mode on
+            if merged.get("fleet").is_some() {
+                let trusted = trust::root_only(&file)
+                    .and_then(|_| file.parent().map_or(Ok(()), trust::root_only));
+                if let Err(e) = trusted {
+                    eprintln!("Ignoring {} in fleet mode: {}", file.display(), e);
+                    continue;
+                }
+            }
+            // Fragments may set only part of a section, so only their syntax
+            // is checked on its own
+            let value: toml::Value = match toml::from_str(&contents) {
+                Ok(value) => value,
+                Err(e) => {
+                    let message = format!("{}: {}", file.display(), e);
+                    eprintln!("{}, using the defaults", message);
+                    return (Config::default(), Some(message));
+                }
+            };
+            merge(&mut merged, value.clone());
+            sources.push((file, value));
+        }
+        // Skipping the config itself would leave fleet mode, so it's only
+        // reported
+        if merged.get("fleet").is_some() {
+            if let Err(e) = trust::root_only(path) {
+                eprintln!("{} can't be trusted in fleet mode: {}", path.display(), e);
+            }
+        }
+        match check(path, &sources, merged) {
+            Ok(mut config) => {
+                config.branding.validate();
+                config.drop_untrusted();
+                (config, None)
+            }
+            Err(message) => {
+                eprintln!("{}, using the defaults", message);
+                (Config::default(), Some(message))
+            }
+        }
+    }
+
+    /// In fleet mode, drops the script and logos that can't be trusted, the
+    /// way themes and backgrounds are refused
+    fn drop_untrusted(&mut self) {
+        let fleet = self.fleet.as_ref();
+        self.script = self
+            .script
+            .take()
+            .filter(|path| trust::allowed(path, fleet, "script"));
+        self.branding.logo = self
+            .branding
+            .logo
+            .take()
+            .filter(|path| trust::allowed(path, fleet, "logo"));
+        if let Some(sas) = &mut self.secure_attention {
+            sas.logo = sas
+                .logo
+                .take()
+                .filter(|path| trust::allowed(path, fleet, "logo"));
+        }
+    }
+
+    fn default_auth_timeout() -> u64 {
+        60
+    }
+
+    fn default_fail_delay() -> f32 {
+        2.0
+    }
+
+    fn yes() -> bool {
+        true
+    }
+
+    /// The configured locale without its encoding or modifier, e.g. `de_DE`
+    pub fn locale(&self) -> Option<String> {
+        let locale = self.locale.clone().or_else(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|l| !l.is_empty()))
+        })?;
+        let locale = locale.split(['.', '@']).next()?;
+        if locale == "C" || locale == "POSIX" {
+            None
+        } else {
+            Some(locale.to_string())
+        }
+    }
+}
+
+/// Deserializes the merged config, reporting unknown keys on stderr. Both
+/// point to the last of `sources` that set the key, or to `path`.
+fn check(
+    path: &Path,
+    sources: &[(PathBuf, toml::Value)],
+    merged: toml::Value,
+) -> Result<Config, String> {
+    let source = |key: &str| {
+        sources
+            .iter()
+            .rev()
+            .find(|(_, value)| lookup(value, key).is_some())
+            .map_or(path, |(file, _)| file.as_path())
+            .display()
+    };
+    let mut ignored = |key: serde_ignored::Path| {
+        let key = key.to_string();
+        eprintln!("{}: unknown key `{}`", source(&key), key);
+    };
+    serde_path_to_error::deserialize::<_, Config>(serde_ignored::Deserializer::new(
+        merged,
+        &mut ignored,
+    ))
+    .map_err(|e| {
+        let key = e.path().to_string();
+        if key == "." {
+            format!("{}: {}", path.display(), e.inner())
+        } else {
+            format!("{}: `{}`: {}", source(&key), key, e.inner())
+        }
+    })
+}
+
+/// The value at a dotted `key` such as `clocks.0.timezone`
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.').try_fold(value, |value, part| match value {
+        toml::Value::Table(table) => table.get(part),
+        toml::Value::Array(array) => array.get(part.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Merges tables key by key, anything else in `overlay` replaces what's in
+/// `base`, arrays included
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Weather {
+    pub enabled: bool,
+    /// Substituted for `{location}` in `url`
+    pub location: String,
+    /// Provider returning a short plain text report
+    pub url: String,
+    /// Seconds between fetches
+    pub interval: u64,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather {
+            enabled: false,
+            location: String::new(),
+            url: "https://wttr.in/{location}?format=%t+%C".to_string(),
+            interval: 1800,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WorldClock {
+    pub label: String,
+    /// Zone name like `Asia/Tokyo` or `UTC`
+    pub timezone: String,
+}
+
+#[derive(Deserialize)]
+pub struct Curfew {
+    /// Start of the quiet hours as `HH:MM`
+    pub start: String,
+    /// End of the quiet hours as `HH:MM`, may be before `start`
+    pub end: String,
+    /// Banner shown while the quiet hours are in effect
+    pub message: String,
+    /// Refuse logins instead of only showing the banner
+    #[serde(default)]
+    pub disable_login: bool,
+}
+
+#[derive(Deserialize)]
+pub struct QrLogin {
+    /// Base https URL serving `/approve` for the companion app and `/status` for
+    /// the greeter to poll
+    pub endpoint: String,
+    /// Seconds between polls of `/status`
+    #[serde(default = "QrLogin::default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl QrLogin {
+    fn default_poll_interval() -> u64 {
+        2
+    }
+}
+
+#[derive(Deserialize, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AfterFinish {
+    #[default]
+    Exit,
+    /// Show a "resuming session" splash for a moment before exiting
+    Splash,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SecureAttention {
+    /// e.g. `ctrl+alt+delete`. Only `ctrl+<letter>` chords work when input is
+    /// read from the TTY, others are ignored there
+    pub chord: String,
+    /// Shown on the idle screen
+    pub message: String,
+    /// Picture shown above the message
+    pub logo: Option<PathBuf>,
+}
+
+impl Default for SecureAttention {
+    fn default() -> Self {
+        SecureAttention {
+            chord: "ctrl+l".to_string(),
+            message: "Press Ctrl+L to log in".to_string(),
+            logo: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Magnifier {
+    /// Chord toggling the magnifier lens. Only `ctrl+<letter>` chords work
+    /// when input is read from the TTY
+    pub hotkey: String,
+    pub zoom: f32,
+    /// Lens size as a share of the shorter side of the screen
+    pub size: f32,
+}
+
+impl Default for Magnifier {
+    fn default() -> Self {
+        Magnifier {
+            hotkey: "ctrl+e".to_string(),
+            zoom: 2.0,
+            size: 0.3,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Speech {
+    /// Speak from the start instead of waiting for the hotkey
+    pub enabled: bool,
+    /// Chord turning speech on and off. Only `ctrl+<letter>` chords work when
+    /// input is read from the TTY
+    pub hotkey: String,
+    /// Say the hotkey out loud at startup, so it can be found without sight
+    pub announce: bool,
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Speech {
+            enabled: false,
+            hotkey: "ctrl+t".to_string(),
+            announce: true,
+        }
+    }
+}
+
+/// Fonts tried in order for glyphs the theme's font and egui's built-in ones
+/// lack. Colour emoji fonts can't be drawn, egui's own emoji are monochrome.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct FontFallback {
+    /// Searched recursively for fonts given by file name
+    pub dirs: Vec<PathBuf>,
+    /// Tried for every locale, after the locale's own
+    pub fonts: Vec<String>,
+    /// Tried first for a language (`ja`) or language and region (`zh_TW`)
+    pub locales: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for FontFallback {
+    fn default() -> Self {
+        // Noto's CJK collection holds Japanese, Korean, Simplified and
+        // Traditional Chinese faces in that order, which differ for shared
+        // characters
+        let cjk = |face: u32| vec![format!("NotoSansCJK-Regular.ttc#{}", face)];
+        FontFallback {
+            dirs: vec![
+                PathBuf::from("/usr/share/fonts"),
+                PathBuf::from("/usr/local/share/fonts"),
+            ],
+            fonts: [
+                "NotoSansArabic-Regular.ttf",
+                "NotoSansHebrew-Regular.ttf",
+                "NotoSansDevanagari-Regular.ttf",
+                "NotoSansBengali-Regular.ttf",
+                "NotoSansTamil-Regular.ttf",
+                "NotoSansThai-Regular.ttf",
+                "NotoSansCJK-Regular.ttc#0",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            locales: BTreeMap::from([
+                ("ja".to_string(), cjk(0)),
+                ("ko".to_string(), cjk(1)),
+                ("zh".to_string(), cjk(2)),
+                ("zh_TW".to_string(), cjk(3)),
+                ("zh_HK".to_string(), cjk(3)),
+            ]),
+        }
+    }
+}
+
+/// Strings and artwork for distributions and OEMs shipping the greeter under
+/// their own name, meant to be set in a `<config>.d` fragment:
+///
+/// ```toml
+/// [branding]
+/// product_name = "ExampleOS"
+/// logo = "/usr/share/pixmaps/exampleos.png"
+/// accent = [53, 132, 228]
+/// footer = "ExampleOS 12, property of Example Corp"
+/// support_url = "https://help.example.com/login"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Branding {
+    /// Titles the login window "Log in to <product_name>"
+    pub product_name: Option<String>,
+    /// Picture shown at the top of the login window, ignored if missing
+    pub logo: Option<PathBuf>,
+    /// Highlight color for selections and links, before
+    /// `login_window.selection_color`
+    pub accent: Option<[u8; 3]>,
+    /// Small print in the bottom left corner
+    pub footer: Option<String>,
+    /// Where to get help, shown while an error is up. Ignored unless http(s)
+    pub support_url: Option<String>,
+}
+
+impl Branding {
+    /// Drops a logo that doesn't exist and a support URL that isn't http(s),
+    /// leaving the rest of the config alone
+    fn validate(&mut self) {
+        if let Some(logo) = self.logo.take() {
+            if logo.is_file() {
+                self.logo = Some(logo);
+            } else {
+                eprintln!(
+                    "branding: logo {} doesn't exist, ignoring it",
+                    logo.display()
+                );
+            }
+        }
+        if let Some(url) = self.support_url.take() {
+            if url.starts_with("https://") || url.starts_with("http://") {
+                self.support_url = Some(url);
+            } else {
+                eprintln!(
+                    "branding: support_url {} isn't an http(s) URL, ignoring it",
+                    url
+                );
+            }
+        }
+    }
+}
+
+/// Locks down what the greeter loads, for machines managed centrally. The
+/// config fragments have to be owned and only writable by root, the same
+/// goes for plugins and their directory. Fonts, ICC profiles and cursor
+/// themes aren't checked.
+#[derive(Deserialize, Clone)]
+pub struct Fleet {
+    /// Themes, backgrounds, the script and logos have to be in here, owned
+    /// and only writable by root all the way down
+    #[serde(default = "Fleet::default_trusted_dir")]
+    pub trusted_dir: PathBuf,
+    /// Raw 32 byte ed25519 public key. When set, every theme, background,
+    /// script, logo and plugin also needs a raw 64 byte signature next to it
+    /// in `<file>.sig`
+    pub public_key: Option<PathBuf>,
+}
+
+impl Fleet {
+    fn default_trusted_dir() -> PathBuf {
+        PathBuf::from("/usr/share/eguigreeter")
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GlApi {
+    /// The newest the platform has, as picked by glutin
+    Latest,
+    /// Desktop GL 3.0, falling back to GLES 2.0
+    Auto,
+    Gl,
+    Gles,
+}
+
+/// ```toml
+/// [gl]
+/// api = "gles"
+/// version = [3, 0]
+/// ```
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct GlContext {
+    /// On X11 GL contexts come from GLX and GLES ones from EGL, so `gles`
+    /// is how to get EGL there
+    pub api: GlApi,
+    /// Lowest `[major, minor]` version to accept, only used with `api` set
+    /// to `gl` or `gles`
+    pub version: Option<[u8; 2]>,
+    /// Ask for an sRGB capable framebuffer
+    pub srgb: bool,
+}
+
+impl Default for GlContext {
+    fn default() -> Self {
+        GlContext {
+            api: GlApi::Latest,
+            version: None,
+            srgb: true,
+        }
+    }
+}
+
+impl GlContext {
+    /// This context followed by ever less demanding ones to retry with,
+    /// ending at GL 3.0 or GLES 2.0 without sRGB
+    pub fn fallbacks(&self) -> Vec<GlContext> {
+        let mut contexts = vec![self.clone()];
+        let mut next = self.clone();
+        let relaxations: [fn(&mut GlContext); 3] = [
+            |c| c.srgb = false,
+            |c| c.version = None,
+            |c| c.api = GlApi::Auto,
+        ];
+        for relax in relaxations {
+            relax(&mut next);
+            if contexts.last() != Some(&next) {
+                contexts.push(next.clone());
+            }
+        }
+        contexts
+    }
+}
+
+/// ```toml
+/// [icc]
+/// profile = "/usr/share/color/icc/studio.icc"
+///
+/// [icc.outputs]
+/// DP-1 = "/var/lib/colord/icc/dp-1.icc"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Icc {
+    /// Profile for outputs not listed in `outputs`
+    pub profile: Option<PathBuf>,
+    /// Profiles by output name
+    pub outputs: BTreeMap<String, PathBuf>,
+}
+
+impl Icc {
+    pub fn profile_for(&self, output: Option<&str>) -> Option<&Path> {
+        output
+            .and_then(|output| self.outputs.get(output))
+            .or(self.profile.as_ref())
+            .map(PathBuf::as_path)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Screenshot {
+    /// Chord saving the screen to `/var/lib/eguigreeter/screenshots`
+    pub hotkey: String,
+    /// Also take them through `org.eguigreeter.Greeter.Screenshot` on the
+    /// system bus
+    pub dbus: bool,
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Screenshot {
+            hotkey: "print".to_string(),
+            dbus: false,
+        }
+    }
+}
+
+/// Serves the greeter over VNC on a Unix socket only the greeter's user can
+/// open, without a password. Reach it by tunneling, e.g.
+/// `ssh -L 5900:/run/eguigreeter/vnc.sock root@host` and pointing a viewer at
+/// `localhost:5900`.
+///
+/// ```toml
+/// [vnc]
+/// socket = "/run/eguigreeter/vnc.sock"
+/// ```
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Vnc {
+    pub socket: PathBuf,
+    /// Only show the screen, ignoring the viewer's keyboard and mouse
+    pub view_only: bool,
+}
+
+impl Default for Vnc {
+    fn default() -> Self {
+        Vnc {
+            socket: PathBuf::from("/run/eguigreeter/vnc.sock"),
+            view_only: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SessionCrash {
+    /// Report sessions that exited with an error right after starting as
+    /// crashed, with their log. Sessions are then run through the handoff
+    /// stage to see how they exit. The report is only shown to the same
+    /// user, once they log in again.
+    pub enabled: bool,
+    /// Only sessions that exit within this many seconds of starting count
+    pub within: f32,
+    /// Log lines to show
+    pub lines: usize,
+}
+
+impl Default for SessionCrash {
+    fn default() -> Self {
+        SessionCrash {
+            enabled: false,
+            within: 10.0,
+            lines: 20,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum UserDataProvider {
+    /// Full names from GECOS, pictures from `~/.face`
+    Passwd,
+    AccountsService,
+    /// The `users` table below
+    Table,
+}
+
+/// ```toml
+/// [user_data]
+/// providers = ["table", "accountsservice", "passwd"]
+///
+/// [user_data.users.alice]
+/// full_name = "Alice Liddell"
+/// avatar = "/usr/share/avatars/alice.png"
+/// session = "sway"
+/// locale = "en_GB.UTF-8"
+/// ```
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct UserData {
+    /// Where full names, pictures, default sessions and locales come from.
+    /// Earlier providers win, later ones fill in the gaps.
+    pub providers: Vec<UserDataProvider>,
+    pub users: BTreeMap<String, UserInfo>,
+}
+
+impl Default for UserData {
+    fn default() -> Self {
+        UserData {
+            providers: vec![UserDataProvider::Passwd],
+            users: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Handoff {
+    /// Start sessions through the greeter binary's handoff stage, which
+    /// cleans up the environment, signals and terminal first
+    pub enabled: bool,
+    /// Environment variables to remove on top of the greeter's and the
+    /// service manager's own
+    pub scrub: Vec<String>,
+    /// Also lower the soft open file limit to what `select()` copes with.
+    /// Breaks software that raises its own soft limit expecting the
+    /// system's default.
+    pub cap_open_files: bool,
+}
+
+impl Default for Handoff {
+    fn default() -> Self {
+        Handoff {
+            enabled: false,
+            scrub: Vec::new(),
+            cap_open_files: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct OnScreenKeyboard {
+    pub show: ShowKeyboard,
+    /// How much larger buttons and fields get while it's shown, for fingers
+    pub touch_scale: f32,
+}
+
+impl Default for OnScreenKeyboard {
+    fn default() -> Self {
+        OnScreenKeyboard {
+            show: ShowKeyboard::default(),
+            touch_scale: 1.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ShowKeyboard {
+    /// When no keyboard is plugged in
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Longest input accepted, in characters; anything typed or pasted beyond
+/// that is cut off
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct InputLimits {
+    pub username: usize,
+    pub password: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        InputLimits {
+            username: 256,
+            password: 1024,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(s: &str) -> toml::Value {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn merges_tables_key_by_key() {
+        let mut base = value("auth_timeout = 30\n[weather]\nenabled = true\nlocation = \"Oslo\"");
+        merge(&mut base, value("[weather]\nlocation = \"Bergen\""));
+        assert_eq!(
+            base,
+            value("auth_timeout = 30\n[weather]\nenabled = true\nlocation = \"Bergen\"")
+        );
+    }
+
+    #[test]
+    fn replaces_arrays_whole() {
+        let mut base = value("session_priority = [\"sway\", \"gnome\"]");
+        merge(&mut base, value("session_priority = [\"plasma\"]"));
+        assert_eq!(base, value("session_priority = [\"plasma\"]"));
+    }
+
+    #[test]
+    fn looks_up_dotted_keys() {
+        let config = value(
+            "[[clocks]]\nlabel = \"HQ\"\ntimezone = \"UTC\"\n\
+             [[clocks]]\nlabel = \"Tokyo\"\ntimezone = \"Asia/Tokyo\"",
+        );
+        assert_eq!(
+            lookup(&config, "clocks.1.timezone"),
+            Some(&toml::Value::String("Asia/Tokyo".to_string()))
+        );
+        assert_eq!(lookup(&config, "clocks.2.timezone"), None);
+        assert_eq!(lookup(&config, "clocks.x"), None);
+    }
+
+    #[test]
+    fn defaults_to_an_empty_file() {
+        let config = Config::default();
+        assert_eq!(config.auth_timeout, 60);
+        assert!(config.remember_username);
+        assert!(config.clocks.is_empty());
+    }
+
+    #[test]
+    fn blames_the_file_that_set_a_bad_key() {
+        let path = Path::new("/etc/greetd/eguigreeter.toml");
+        let base = value("auth_timeout = 30");
+        let fragment = value("[weather]\nenabled = \"yes\"");
+        let mut merged = base.clone();
+        merge(&mut merged, fragment.clone());
+        let sources = vec![
+            (path.to_path_buf(), base),
+            (
+                PathBuf::from("/etc/greetd/eguigreeter.toml.d/weather.toml"),
+                fragment,
+            ),
+        ];
+        let error = check(path, &sources, merged).err().unwrap();
+        assert!(
+            error.starts_with("/etc/greetd/eguigreeter.toml.d/weather.toml: `weather.enabled`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn checks_a_good_config() {
+        let path = Path::new("/etc/greetd/eguigreeter.toml");
+        let merged = value("auth_timeout = 30\nremember_username = false");
+        let config = check(path, &[(path.to_path_buf(), merged.clone())], merged)
+            .ok()
+            .unwrap();
+        assert_eq!(config.auth_timeout, 30);
+        assert!(!config.remember_username);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    backtrace::Backtrace, cell::Cell, fmt::Write as _, panic::UnwindSafe, path::Path, sync::Mutex,
+};
+
+use time::{macros::format_description, OffsetDateTime};
+
+pub const REPORT_DIR: &str = "/var/log/eguigreeter";
+
+/// Facts about the running greeter included in crash reports
+static CONTEXT: Mutex<Vec<(&'static str, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Whether a panic on this thread is caught and dealt with, not a crash
+    static EXPECTED: Cell<bool> = Cell::new(false);
+}
+
+/// Records `value` under `key` for crash reports, replacing an earlier value
+pub fn note(key: &'static str, value: impl ToString) {
+    let mut context = CONTEXT.lock().unwrap();
+    context.retain(|(k, _)| *k != key);
+    context.push((key, value.to_string()));
+}
+
+/// Runs `f`, catching a panic in it as an expected failure rather than
+/// reporting it as a crash
+pub fn expected<R>(f: impl FnOnce() -> R + UnwindSafe) -> std::thread::Result<R> {
+    EXPECTED.with(|expected| expected.set(true));
+    let result = std::panic::catch_unwind(f);
+    EXPECTED.with(|expected| expected.set(false));
+    result
+}
+
+/// Installs a panic hook writing a timestamped report to [`REPORT_DIR`] and
+/// pointing to it on the VT, which is taken out of raw mode first
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        if EXPECTED.with(Cell::get) {
+            eprintln!("{}", info);
+            return;
+        }
+        let _ = crossterm::terminal::disable_raw_mode();
+        report("crashed", &info.to_string());
+    }));
+}
+
+/// Reports `message` the way a crash is, then exits with an error
+pub fn fail(message: &str) -> ! {
+    report("exited with an error", message);
+    std::process::exit(1);
+}
+
+fn report(what: &str, message: &str) {
+    let now = OffsetDateTime::now_utc();
+    let mut report = String::new();
+    writeln!(report, "eguigreeter {} {}", env!("CARGO_PKG_VERSION"), what).unwrap();
+    writeln!(report, "time: {}", now.unix_timestamp()).unwrap();
+    writeln!(report, "{}", message).unwrap();
+    writeln!(report).unwrap();
+    // A poisoned lock just means the panic happened while noting something
+    let context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    for (key, value) in context.iter() {
+        writeln!(report, "{}: {}", key, value).unwrap();
+    }
+    drop(context);
+    writeln!(report).unwrap();
+    writeln!(report, "{}", Backtrace::force_capture()).unwrap();
+
+    let name = now
+        .format(format_description!(
+            "crash-[year][month][day]-[hour][minute][second].txt"
+        ))
+        .unwrap();
+    let path = Path::new(REPORT_DIR).join(name);
+    let written = std::fs::create_dir_all(REPORT_DIR).and_then(|_| std::fs::write(&path, &report));
+    eprintln!("\r\n{}", message);
+    match written {
+        Ok(()) => eprintln!(
+            "eguigreeter {}, please attach {} to your bug report",
+            what,
+            path.display()
+        ),
+        Err(e) => {
+            eprintln!("eguigreeter {}, and writing a report failed: {}", what, e);
+            eprintln!("{}", report);
+        }
+    }
+}
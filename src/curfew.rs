@@ -0,0 +1,23 @@
+use time::{OffsetDateTime, Time, UtcOffset};
+
+use crate::config::Curfew;
+
+fn parse(hhmm: &str) -> Option<Time> {
+    let (h, m) = hhmm.split_once(':')?;
+    Time::from_hms(h.trim().parse().ok()?, m.trim().parse().ok()?, 0).ok()
+}
+
+/// Whether the current local time falls within the configured quiet hours,
+/// which may wrap around midnight
+pub fn active(curfew: &Curfew, offset: UtcOffset) -> bool {
+    let (start, end) = match (parse(&curfew.start), parse(&curfew.end)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return false,
+    };
+    let now = OffsetDateTime::now_utc().to_offset(offset).time();
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+/// Finds how long an account stays locked in messages from pam_faillock,
+/// pam_tally2 and the like, e.g. "(4 minutes left to unlock)" or
+/// "Account locked, try again in 30 seconds"
+pub fn retry_after(message: &str) -> Option<Duration> {
+    let message = message.to_lowercase();
+    if !message.contains("lock") {
+        return None;
+    }
+    let words: Vec<&str> = message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.windows(2).find_map(|pair| {
+        let n: u64 = pair[0].parse().ok()?;
+        let unit = match pair[1].trim_end_matches('s') {
+            "sec" | "second" => 1,
+            "min" | "minute" => 60,
+            "hour" => 3600,
+            _ => return None,
+        };
+        Some(Duration::from_secs(n * unit))
+    })
+}
+
+/// Formats the time left as `M:SS`
+pub fn countdown(left: Duration) -> String {
+    let secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
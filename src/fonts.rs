@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use egui::{FontData, FontDefinitions, FontFamily};
+use serde::{Deserialize, Serialize};
+
+use crate::{cache::CACHE_DIR, config::FontFallback};
+
+/// A font file to fall back on, and which face of it for collections
+pub struct Fallback {
+    path: PathBuf,
+    index: u32,
+}
+
+/// The font files under the configured directories by file name, kept in
+/// the cache along with when each directory last changed
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    roots: Vec<PathBuf>,
+    /// Nanoseconds since the epoch, 0 for directories that didn't exist
+    dirs: BTreeMap<String, u64>,
+    fonts: HashMap<String, PathBuf>,
+}
+
+fn modified(dir: &Path) -> u64 {
+    dir.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+impl Index {
+    fn path() -> PathBuf {
+        Path::new(CACHE_DIR).join("fonts.toml")
+    }
+
+    /// The index of `roots`, from the cache unless a directory changed
+    fn load(roots: &[PathBuf]) -> Self {
+        let cached = std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|s| toml::from_str::<Index>(&s).ok())
+            .filter(|index| {
+                index.roots == roots
+                    && index
+                        .dirs
+                        .iter()
+                        .all(|(dir, at)| modified(Path::new(dir)) == *at)
+            });
+        if let Some(index) = cached {
+            return index;
+        }
+        let mut index = Index {
+            roots: roots.to_vec(),
+            ..Index::default()
+        };
+        for dir in roots {
+            index.add_dir(dir);
+        }
+        index.save();
+        index
+    }
+
+    /// Symlinks to directories aren't followed, so a loop can't recurse
+    /// forever
+    fn add_dir(&mut self, dir: &Path) {
+        self.dirs
+            .insert(dir.to_string_lossy().into_owned(), modified(dir));
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => self.add_dir(&path),
+                Ok(_) => {
+                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                        self.fonts.entry(name.to_string()).or_insert(path);
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn save(&self) {
+        let path = Self::path();
+        let temp = path.with_extension("toml.tmp");
+        let result = std::fs::create_dir_all(CACHE_DIR)
+            .and_then(|_| {
+                let s = toml::to_string(self)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                std::fs::write(&temp, s)
+            })
+            .and_then(|_| std::fs::rename(&temp, &path));
+        if let Err(e) = result {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Resolves the fallback chain for `locale`: the fonts for its language and
+/// region, then for its language, then the ones for every locale. Fonts are
+/// file names found in the configured directories or absolute paths, with
+/// `#<n>` picking a face out of a collection. Missing fonts are skipped, as
+/// are files already in the chain with another face.
+pub fn resolve(fallback: &FontFallback, locale: Option<&str>) -> Vec<Fallback> {
+    let found = Index::load(&fallback.dirs).fonts;
+
+    let mut names: Vec<&str> = Vec::new();
+    if let Some(locale) = locale {
+        let language = locale.split('_').next().unwrap_or(locale);
+        for key in [locale, language] {
+            if let Some(fonts) = fallback.locales.get(key) {
+                names.extend(fonts.iter().map(String::as_str));
+            }
+        }
+    }
+    names.extend(fallback.fonts.iter().map(String::as_str));
+
+    let mut chain: Vec<Fallback> = Vec::new();
+    for name in names {
+        let (file, index) = match name.rsplit_once('#') {
+            Some((file, index)) => match index.parse() {
+                Ok(index) => (file, index),
+                Err(_) => {
+                    eprintln!("Invalid font face in {}", name);
+                    continue;
+                }
+            },
+            None => (name, 0),
+        };
+        let path = if Path::new(file).is_absolute() {
+            Some(PathBuf::from(file)).filter(|p| p.is_file())
+        } else {
+            found.get(file).cloned()
+        };
+        if let Some(path) = path {
+            // One face per file: a collection like Noto's CJK one is large,
+            // and its faces cover the same characters
+            if !chain.iter().any(|f| f.path == path) {
+                chain.push(Fallback { path, index });
+            }
+        }
+    }
+    chain
+}
+
+/// Appends `chain` to both font families, after the theme's font and egui's
+/// own, so glyphs those lack are drawn instead of boxes
+pub fn add_fallbacks(fonts: &mut FontDefinitions, chain: &[Fallback]) {
+    for fallback in chain {
+        let font = match std::fs::read(&fallback.path) {
+            Ok(font) => font,
+            Err(e) => {
+                eprintln!("Failed to read font {}: {}", fallback.path.display(), e);
+                continue;
+            }
+        };
+        let name = format!("{}#{}", fallback.path.display(), fallback.index);
+        let mut data = FontData::from_owned(font);
+        data.index = fallback.index;
+        fonts.font_data.insert(name.clone(), data);
+        for family in [FontFamily::Proportional, FontFamily::Monospace] {
+            fonts.families.entry(family).or_default().push(name.clone());
+        }
+    }
+}
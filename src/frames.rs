@@ -0,0 +1,71 @@
+use egui::{epaint::ClippedShape, TexturesDelta};
+use egui_glow::EguiGlow;
+
+use crate::backend::window::Window;
+
+/// Runs and paints egui like [`EguiGlow::run`] and [`EguiGlow::paint`], but
+/// keeps what was last painted around, so a frame that would come out the
+/// same as the one on screen can be skipped altogether
+#[derive(Default)]
+pub struct Frames {
+    shapes: Vec<ClippedShape>,
+    textures_delta: TexturesDelta,
+    painted: Option<(Vec<ClippedShape>, [u32; 2], f32)>,
+}
+
+impl Frames {
+    pub fn run(
+        &mut self,
+        egui_glow: &mut EguiGlow,
+        window: &Window,
+        run_ui: impl FnMut(&egui::Context),
+    ) -> bool {
+        let raw_input = egui_glow.egui_winit.take_egui_input(window);
+        let egui::FullOutput {
+            platform_output,
+            needs_repaint,
+            textures_delta,
+            shapes,
+        } = egui_glow.egui_ctx.run(raw_input, run_ui);
+        egui_glow
+            .egui_winit
+            .handle_platform_output(window, &egui_glow.egui_ctx, platform_output);
+        self.shapes = shapes;
+        self.textures_delta.append(textures_delta);
+        needs_repaint
+    }
+
+    /// Whether the last run produced exactly what is already on screen
+    pub fn unchanged(&self, egui_glow: &EguiGlow, window: &Window) -> bool {
+        let dimensions: [u32; 2] = window.inner_size().into();
+        self.textures_delta.is_empty()
+            && self.painted.as_ref().map_or(false, |(shapes, size, ppp)| {
+                *size == dimensions
+                    && *ppp == egui_glow.egui_ctx.pixels_per_point()
+                    && *shapes == self.shapes
+            })
+    }
+
+    /// Forgets the painted frame, for when something else drew over it
+    pub fn invalidate(&mut self) {
+        self.painted = None;
+    }
+
+    pub fn paint(&mut self, egui_glow: &mut EguiGlow, window: &Window) {
+        let shapes = std::mem::take(&mut self.shapes);
+        let mut textures_delta = std::mem::take(&mut self.textures_delta);
+        for (id, image_delta) in textures_delta.set {
+            egui_glow.painter.set_texture(id, &image_delta);
+        }
+        let dimensions: [u32; 2] = window.inner_size().into();
+        let pixels_per_point = egui_glow.egui_ctx.pixels_per_point();
+        let clipped_primitives = egui_glow.egui_ctx.tessellate(shapes.clone());
+        egui_glow
+            .painter
+            .paint_primitives(dimensions, pixels_per_point, &clipped_primitives);
+        for id in textures_delta.free.drain(..) {
+            egui_glow.painter.free_texture(id);
+        }
+        self.painted = Some((shapes, dimensions, pixels_per_point));
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use egui::{pos2, Pos2};
+
+use crate::backend::event::{Touch, TouchPhase};
+
+const LONG_PRESS: Duration = Duration::from_millis(600);
+/// Points a finger may wander and still count as pressing in place
+const SLOP: f32 = 12.0;
+/// Points a finger has to travel sideways for a swipe
+const SWIPE: f32 = 80.0;
+/// How much two fingers have to spread or close before a pinch is reported
+const PINCH: f32 = 1.1;
+
+pub enum Gesture {
+    LongPress(Pos2),
+    SwipeLeft,
+    SwipeRight,
+    /// Two fingers moved apart (above 1) or together (below 1) by this ratio
+    Pinch(f32),
+    /// A finger was lifted after pinching
+    PinchEnded,
+}
+
+/// Recognizes gestures from touchscreen and stylus input. Taps need nothing
+/// extra, egui already treats them as clicks.
+#[derive(Default)]
+pub struct Tracker {
+    start: Option<(u64, Pos2, Instant)>,
+    /// In physical pixels, so rescaling mid-pinch doesn't skew the distance
+    fingers: Vec<(u64, Pos2)>,
+    /// Distance between two fingers when the last pinch was reported
+    pinch: Option<f32>,
+    /// Whether a pinch was reported since two fingers went down
+    pinched: bool,
+}
+
+impl Tracker {
+    pub fn touch(&mut self, touch: &Touch, pixels_per_point: f32) -> Option<Gesture> {
+        let physical = pos2(touch.location.x as f32, touch.location.y as f32);
+        let pos = (physical.to_vec2() / pixels_per_point).to_pos2();
+        match touch.phase {
+            TouchPhase::Started => {
+                self.fingers.push((touch.id, physical));
+                self.pinch = self.spread();
+                // Only single finger gestures
+                self.start = match self.start {
+                    None => Some((touch.id, pos, Instant::now())),
+                    Some(_) => None,
+                };
+                None
+            }
+            TouchPhase::Moved => {
+                if let Some(finger) = self.fingers.iter_mut().find(|(id, _)| *id == touch.id) {
+                    finger.1 = physical;
+                }
+                let (last, now) = (self.pinch?, self.spread()?);
+                let ratio = now / last;
+                if ratio >= PINCH || ratio <= 1.0 / PINCH {
+                    self.pinch = Some(now);
+                    self.pinched = true;
+                    Some(Gesture::Pinch(ratio))
+                } else {
+                    None
+                }
+            }
+            TouchPhase::Cancelled => {
+                self.fingers.retain(|(id, _)| *id != touch.id);
+                self.pinch = None;
+                self.start = None;
+                std::mem::take(&mut self.pinched).then(|| Gesture::PinchEnded)
+            }
+            TouchPhase::Ended => {
+                self.fingers.retain(|(id, _)| *id != touch.id);
+                self.pinch = None;
+                if std::mem::take(&mut self.pinched) {
+                    return Some(Gesture::PinchEnded);
+                }
+                let (id, start, at) = self.start.take()?;
+                if id != touch.id {
+                    return None;
+                }
+                let delta = pos - start;
+                if delta.x.abs() >= SWIPE && delta.x.abs() > delta.y.abs() * 2.0 {
+                    Some(if delta.x < 0.0 {
+                        Gesture::SwipeLeft
+                    } else {
+                        Gesture::SwipeRight
+                    })
+                } else if delta.length() <= SLOP && at.elapsed() >= LONG_PRESS {
+                    Some(Gesture::LongPress(start))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Distance between the fingers, if exactly two are down
+    fn spread(&self) -> Option<f32> {
+        match self.fingers[..] {
+            [(_, a), (_, b)] => Some(a.distance(b).max(1.0)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    cell::RefCell,
+    num::NonZeroU32,
+    path::Path,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use egui::Context;
+use glow::HasContext;
+use gstreamer::{self as gst, prelude::*};
+use gstreamer_app as gst_app;
+use gstreamer_gl::{self as gst_gl, prelude::*};
+use gstreamer_gl_egl as gst_gl_egl;
+use gstreamer_gl_x11 as gst_gl_x11;
+use gstreamer_video as gst_video;
+
+use crate::{
+    backend::{
+        self,
+        event_loop::{EventLoopProxy, EventLoopWindowTarget},
+        Display, NativeContext,
+    },
+    background,
+    config::Config,
+    UserEvent,
+};
+
+/// Background video or picture decoded by GStreamer into GL textures shared
+/// with the window's context, which are blitted straight onto the window
+pub struct Video {
+    playbin: gst::Element,
+    /// The newest sample from the streaming thread, not drawn yet
+    sample: Arc<Mutex<Option<gst::Sample>>>,
+    /// The frame on screen, kept mapped until the next one replaces it
+    frame: RefCell<Option<gst_video::VideoFrame<gst_video::video_frame::Readable>>>,
+    /// The window's context as GStreamer sees it
+    context: gst_gl::GLContext,
+    gl: glow::Context,
+    framebuffer: glow::Framebuffer,
+}
+
+fn uri(path: &str) -> String {
+    if background::is_stream(path) {
+        return path.to_string();
+    }
+    let path = Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| path.into());
+    format!("file://{}", path.display())
+}
+
+/// Wraps the window's GL context for GStreamer, so its GL elements make
+/// contexts sharing textures with it
+fn wrap_context(
+    display: &Display,
+    gl: &glow::Context,
+) -> Option<(gst_gl::GLDisplay, gst_gl::GLContext)> {
+    let (handle, gl_display, platform) = match backend::native_context(display)? {
+        NativeContext::Egl { context, display } => (
+            context as usize,
+            unsafe { gst_gl_egl::GLDisplayEGL::with_egl_display(display as usize) }
+                .ok()?
+                .upcast::<gst_gl::GLDisplay>(),
+            gst_gl::GLPlatform::EGL,
+        ),
+        NativeContext::Glx { context, display } => (
+            context as usize,
+            unsafe { gst_gl_x11::GLDisplayX11::with_display(display as usize) }
+                .ok()?
+                .upcast::<gst_gl::GLDisplay>(),
+            gst_gl::GLPlatform::GLX,
+        ),
+    };
+    let api = if gl.version().is_embedded {
+        gst_gl::GLAPI::GLES2
+    } else {
+        gst_gl::GLAPI::OPENGL3
+    };
+    let context = unsafe { gst_gl::GLContext::new_wrapped(&gl_display, handle, platform, api) }?;
+    context.activate(true).ok()?;
+    context.fill_info().ok()?;
+    Some((gl_display, context))
+}
+
+impl Video {
+    pub fn new(
+        path: &str,
+        _is_image: bool,
+        config: &Config,
+        display: &Rc<Display>,
+        _target: &EventLoopWindowTarget<UserEvent>,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) -> Option<Self> {
+        gst::init().ok()?;
+        let gl = unsafe { glow::Context::from_loader_function(|c| display.get_proc_address(c)) };
+        let (gl_display, context) = match wrap_context(display, &gl) {
+            Some(wrapped) => wrapped,
+            None => {
+                eprintln!("Failed to share the GL context with GStreamer");
+                return None;
+            }
+        };
+
+        let playbin = gst::ElementFactory::make("playbin", None).ok()?;
+        let appsink = gst_app::AppSink::builder()
+            .caps(
+                &gst::Caps::builder("video/x-raw")
+                    .features(&["memory:GLMemory"])
+                    .field("format", "RGBA")
+                    .field("texture-target", "2D")
+                    .build(),
+            )
+            .build();
+        let sink = gst::ElementFactory::make("glsinkbin", None).ok()?;
+        sink.set_property("sink", &appsink);
+        let sample = Arc::new(Mutex::new(None));
+
+        let event_proxy = Mutex::new(proxy.clone());
+        let sink_sample = sample.clone();
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    *sink_sample.lock().unwrap() = Some(sample);
+                    let _ = event_proxy.lock().unwrap().send_event(UserEvent::Redraw);
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        playbin.set_property("uri", uri(path));
+        playbin.set_property("video-sink", &sink);
+        playbin.set_property(
+            "audio-sink",
+            &gst::ElementFactory::make("fakesink", None).ok()?,
+        );
+
+        let bus = playbin.bus()?;
+        // GStreamer's GL elements ask for the display and context to share
+        let app_context = context.clone();
+        bus.set_sync_handler(move |_, msg| {
+            if let gst::MessageView::NeedContext(need) = msg.view() {
+                let element = msg
+                    .src()
+                    .and_then(|src| src.clone().downcast::<gst::Element>().ok());
+                let context_type = need.context_type();
+                if let Some(element) = element {
+                    if context_type == "gst.gl.GLDisplay" {
+                        let context = gst::Context::new(context_type, true);
+                        context.set_gl_display(&gl_display);
+                        element.set_context(&context);
+                    } else if context_type == "gst.gl.app_context" {
+                        let mut context = gst::Context::new(context_type, true);
+                        context
+                            .get_mut()
+                            .unwrap()
+                            .structure_mut()
+                            .set("context", &app_context);
+                        element.set_context(&context);
+                    }
+                }
+            }
+            gst::BusSyncReply::Pass
+        });
+        let looping = playbin.downgrade();
+        let mut fallback = config
+            .background
+            .fallback
+            .clone()
+            .filter(|_| background::is_stream(path));
+        std::thread::spawn(move || {
+            for msg in bus.iter_timed(gst::ClockTime::NONE) {
+                match msg.view() {
+                    gst::MessageView::Eos(..) => {
+                        if let Some(playbin) = looping.upgrade() {
+                            let _ =
+                                playbin.seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO);
+                        }
+                    }
+                    gst::MessageView::Error(err) => {
+                        eprintln!("GStreamer background failed: {}", err.error());
+                        match (fallback.take(), looping.upgrade()) {
+                            (Some(fallback), Some(playbin)) => {
+                                let _ = playbin.set_state(gst::State::Ready);
+                                playbin.set_property("uri", uri(&fallback));
+                                let _ = playbin.set_state(gst::State::Playing);
+                            }
+                            _ => break,
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        let framebuffer = unsafe { gl.create_framebuffer() }.ok()?;
+
+        playbin.set_state(gst::State::Playing).ok()?;
+
+        Some(Video {
+            playbin,
+            sample,
+            frame: RefCell::new(None),
+            context,
+            gl,
+            framebuffer,
+        })
+    }
+
+    /// Decoder selection is left to GStreamer's autoplugging
+    pub fn poll_events(&mut self) {}
+
+    /// Holds the first frame while paused, e.g. while running on battery
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            let _ = self
+                .playbin
+                .seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO);
+            let _ = self.playbin.set_state(gst::State::Paused);
+        } else {
+            let _ = self.playbin.set_state(gst::State::Playing);
+        }
+    }
+
+    /// Maps the newest sample's texture, after the decoder's GL commands
+    /// that produced it
+    fn take_sample(&self) {
+        let sample = match self.sample.lock().unwrap().take() {
+            Some(sample) => sample,
+            None => return,
+        };
+        let info = sample
+            .caps()
+            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok());
+        let (info, buffer) = match (info, sample.buffer_owned()) {
+            (Some(info), Some(buffer)) => (info, buffer),
+            _ => return,
+        };
+        let decoder_context = buffer
+            .peek_memory(0)
+            .downcast_memory_ref::<gst_gl::GLBaseMemory>()
+            .map(|memory| memory.context().clone());
+        if let (Some(meta), Some(decoder_context)) =
+            (buffer.meta::<gst_gl::GLSyncMeta>(), decoder_context)
+        {
+            meta.set_sync_point(&decoder_context);
+        }
+        if let Ok(frame) = gst_video::VideoFrame::from_buffer_readable_gl(buffer, &info) {
+            *self.frame.borrow_mut() = Some(frame);
+        }
+    }
+
+    /// Blits the newest frame onto the window, cropped to fill it like mpv's
+    /// `panscan`
+    pub fn render(&self, width: u32, height: u32) {
+        self.take_sample();
+        let frame = self.frame.borrow();
+        let frame = match &*frame {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Some(meta) = frame.buffer().meta::<gst_gl::GLSyncMeta>() {
+            meta.wait(&self.context);
+        }
+        let texture = match frame.texture_id(0).and_then(NonZeroU32::new) {
+            Some(texture) => glow::NativeTexture(texture),
+            None => return,
+        };
+        let (frame_width, frame_height) = (frame.width() as f32, frame.height() as f32);
+        let scale = (width as f32 / frame_width).max(height as f32 / frame_height);
+        let (shown_width, shown_height) = (width as f32 / scale, height as f32 / scale);
+        let x = ((frame_width - shown_width) / 2.0) as i32;
+        let y = ((frame_height - shown_height) / 2.0) as i32;
+        unsafe {
+            let gl = &self.gl;
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.framebuffer_texture_2d(
+                glow::READ_FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
+            // The frame's first row is its top, GL's is the bottom
+            gl.blit_framebuffer(
+                x,
+                y,
+                x + shown_width as i32,
+                y + shown_height as i32,
+                0,
+                height as i32,
+                width as i32,
+                0,
+                glow::COLOR_BUFFER_BIT,
+                glow::LINEAR,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+    }
+
+    /// GStreamer draws in [`Video::render`], so there is nothing to add to
+    /// the UI
+    pub fn show(&mut self, _ctx: &Context) {}
+
+    /// Replaces the playing background with `path`
+    pub fn play(&self, path: &str, _is_image: bool) {
+        let _ = self.playbin.set_state(gst::State::Ready);
+        self.playbin.set_property("uri", uri(path));
+        let _ = self.playbin.set_state(gst::State::Playing);
+    }
+}
+
+impl Drop for Video {
+    fn drop(&mut self) {
+        let _ = self.playbin.set_state(gst::State::Null);
+        unsafe { self.gl.delete_framebuffer(self.framebuffer) };
+    }
+}
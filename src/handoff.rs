@@ -0,0 +1,178 @@
+use std::{
+    ffi::OsString,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::Path,
+    process::Command,
+};
+
+/// Makes the greeter binary run as the handoff stage instead
+pub const FLAG: &str = "--handoff";
+
+/// Starts the line logged when a session exits with an error, followed by
+/// how it exited
+pub const FAILED: &str = "eguigreeter: session";
+
+/// Signals a crash raises, unlike those that end sessions on logout
+const CRASH_SIGNALS: [libc::c_int; 7] = [
+    libc::SIGSEGV,
+    libc::SIGABRT,
+    libc::SIGBUS,
+    libc::SIGFPE,
+    libc::SIGILL,
+    libc::SIGTRAP,
+    libc::SIGSYS,
+];
+
+/// Variables only the greeter and the service manager running greetd have
+/// any business with
+const SCRUBBED: [&str; 9] = [
+    "JOURNAL_STREAM",
+    "INVOCATION_ID",
+    "NOTIFY_SOCKET",
+    "LISTEN_PID",
+    "LISTEN_FDS",
+    "LISTEN_FDNAMES",
+    "WATCHDOG_PID",
+    "WATCHDOG_USEC",
+    "MANAGERPID",
+];
+
+/// Prefixes `command` with the handoff stage, so greetd runs it through the
+/// greeter binary. Names in `scrub` are removed along with the defaults,
+/// `cap_open_files` lowers the open file limit and `report_exit` logs how the
+/// session exited if it failed.
+pub fn wrap(
+    command: Vec<String>,
+    scrub: &[String],
+    cap_open_files: bool,
+    report_exit: bool,
+) -> Vec<String> {
+    let mut wrapped = vec![
+        std::env::current_exe()
+            .map(|exe| exe.display().to_string())
+            .unwrap_or_else(|_| "eguigreeter".to_string()),
+        FLAG.to_string(),
+    ];
+    for name in scrub {
+        wrapped.extend(["--scrub".to_string(), name.clone()]);
+    }
+    if cap_open_files {
+        wrapped.push("--cap-open-files".to_string());
+    }
+    if report_exit {
+        wrapped.push("--report-exit".to_string());
+    }
+    wrapped.push("--".to_string());
+    wrapped.extend(command);
+    wrapped
+}
+
+/// Puts the terminal back into cooked mode, the greeter leaves it raw
+unsafe fn sane_tty(fd: libc::c_int) {
+    let mut termios = std::mem::zeroed::<libc::termios>();
+    if libc::isatty(fd) == 0 || libc::tcgetattr(fd, &mut termios) != 0 {
+        return;
+    }
+    termios.c_iflag |= libc::BRKINT | libc::ICRNL | libc::IXON;
+    termios.c_oflag |= libc::OPOST | libc::ONLCR;
+    termios.c_lflag |= libc::ISIG | libc::ICANON | libc::IEXTEN | libc::ECHO | libc::ECHOE;
+    libc::tcsetattr(fd, libc::TCSANOW, &termios);
+}
+
+/// Undoes what the session could inherit from greetd and the greeter, then
+/// execs the session: scrubs the environment, unblocks and resets signals,
+/// optionally brings the open file limit back to what `select()` copes with,
+/// restores the terminal and changes to the home directory. Only returns on
+/// failure. With `--report-exit` the session runs as a child instead, and a
+/// line starting with [`FAILED`] goes to stderr if it fails.
+pub fn run(mut args: impl Iterator<Item = OsString>) -> std::io::Error {
+    let mut scrub: Vec<OsString> = SCRUBBED.iter().map(OsString::from).collect();
+    let mut cap_open_files = false;
+    let mut report_exit = false;
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--scrub") => scrub.extend(args.next()),
+            Some("--cap-open-files") => cap_open_files = true,
+            Some("--report-exit") => report_exit = true,
+            Some("--") => break,
+            _ => {
+                return std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unexpected handoff argument {:?}", arg),
+                )
+            }
+        }
+    }
+    let program = match args.next() {
+        Some(program) => program,
+        None => return std::io::Error::new(std::io::ErrorKind::InvalidInput, "nothing to run"),
+    };
+
+    let mut command = Command::new(program);
+    command.args(args);
+    for (name, _) in std::env::vars_os() {
+        let greeter_own = name
+            .to_str()
+            .map(|name| name.starts_with("EGUIGREETER_"))
+            .unwrap_or(false);
+        if greeter_own || scrub.contains(&name) {
+            command.env_remove(name);
+        }
+    }
+    if let Some(home) = std::env::var_os("HOME").filter(|home| Path::new(home).is_dir()) {
+        command.current_dir(home);
+    }
+
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::sigset_t>();
+        libc::sigemptyset(&mut set);
+        libc::sigprocmask(libc::SIG_SETMASK, &set, std::ptr::null_mut());
+        // The classic signals, the real-time ones are glibc's business
+        for signal in 1..=libc::SIGSYS {
+            if signal != libc::SIGKILL && signal != libc::SIGSTOP {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+
+        let mut limit = std::mem::zeroed::<libc::rlimit>();
+        if cap_open_files
+            && libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) == 0
+            && limit.rlim_cur > libc::FD_SETSIZE as libc::rlim_t
+        {
+            limit.rlim_cur = libc::FD_SETSIZE as libc::rlim_t;
+            libc::setrlimit(libc::RLIMIT_NOFILE, &limit);
+        }
+
+        sane_tty(libc::STDIN_FILENO);
+    }
+
+    if !report_exit {
+        return command.exec();
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => return e,
+    };
+    // Like system(), leave the terminal's signals to the session
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+    let status = match child.wait() {
+        Ok(status) => status,
+        Err(e) => return e,
+    };
+    match (status.code(), status.signal()) {
+        (Some(0), _) => {}
+        (Some(code), _) => eprintln!("{} exited with status {}", FAILED, code),
+        (None, Some(signal)) if CRASH_SIGNALS.contains(&signal) => {
+            eprintln!("{} was killed by signal {}", FAILED, signal)
+        }
+        _ => {}
+    }
+    std::process::exit(
+        status
+            .code()
+            .unwrap_or_else(|| 128 + status.signal().unwrap_or_default()),
+    )
+}
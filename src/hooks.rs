@@ -0,0 +1,63 @@
+use std::{
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+/// Hooks still running after this long are killed
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs an admin supplied hook through `sh -c` with the username and, once
+/// one is picked, the session exported. The hook is waited for on a
+/// separate thread, which sends [`UserEvent::HookFinished`] through `done`
+/// once it exits or is killed, straight away if there's no hook.
+pub fn run(
+    hook: Option<&str>,
+    username: &str,
+    session: Option<&str>,
+    done: Option<EventLoopProxy<UserEvent>>,
+) {
+    let finished = move || {
+        if let Some(done) = done {
+            let _ = done.send_event(UserEvent::HookFinished);
+        }
+    };
+    let hook = match hook {
+        Some(hook) => hook.to_string(),
+        None => return finished(),
+    };
+    let mut command = Command::new("/bin/sh");
+    command
+        .args(["-c", &hook])
+        .env("EGUIGREETER_USERNAME", username)
+        .stdin(Stdio::null());
+    if let Some(session) = session.filter(|session| !session.is_empty()) {
+        command.env("EGUIGREETER_SESSION", session);
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to run hook `{}`: {}", hook, e);
+            return finished();
+        }
+    };
+    std::thread::spawn(move || {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            match child.try_wait() {
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(50))
+                }
+                Ok(None) => {
+                    eprintln!("Hook `{}` took too long, killing it", hook);
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break;
+                }
+                _ => break,
+            }
+        }
+        finished();
+    });
+}
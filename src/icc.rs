@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use glow::HasContext;
+
+use crate::shader;
+
+/// Entries in the lookup table, one per 8-bit channel value
+const LUT_SIZE: usize = 256;
+
+const FRAGMENT: &str = r#"
+uniform sampler2D frame;
+uniform sampler2D lut;
+out vec4 color;
+
+void main() {
+    vec3 c = texelFetch(frame, ivec2(gl_FragCoord.xy), 0).rgb;
+    ivec3 i = ivec3(round(c * 255.0));
+    color = vec4(
+        texelFetch(lut, ivec2(i.r, 0), 0).r,
+        texelFetch(lut, ivec2(i.g, 0), 0).g,
+        texelFetch(lut, ivec2(i.b, 0), 0).b,
+        1.0
+    );
+}
+"#;
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The calibration curves in an ICC profile's `vcgt` tag, the part colord
+/// and dispwin load into the video card's gamma ramps once a session starts,
+/// resampled to an RGBA table. Profiles without one have nothing to apply.
+pub fn curves(path: impl AsRef<Path>) -> Result<Vec<[u8; 4]>, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let invalid = || "Not a valid ICC profile".to_string();
+    let count = u32_at(&data, 128).ok_or_else(invalid)? as usize;
+    let tag = (0..count)
+        .map(|i| 132 + i * 12)
+        .find(|&entry| data.get(entry..entry + 4) == Some(b"vcgt"))
+        .ok_or("The profile has no vcgt calibration curves")?;
+    let offset = u32_at(&data, tag + 4).ok_or_else(invalid)? as usize;
+    let vcgt = data.get(offset..).ok_or_else(invalid)?;
+
+    // The curve of channel `c` at `x`, both 0.0-1.0
+    let curve: Box<dyn Fn(usize, f32) -> Option<f32> + '_> = match u32_at(vcgt, 8) {
+        // Tables of `entries` values for each channel
+        Some(0) => {
+            let channels = u16_at(vcgt, 12).ok_or_else(invalid)? as usize;
+            let entries = u16_at(vcgt, 14).ok_or_else(invalid)? as usize;
+            let size = u16_at(vcgt, 16).ok_or_else(invalid)? as usize;
+            if entries < 2 || !(channels == 1 || channels == 3) || !(size == 1 || size == 2) {
+                return Err(invalid());
+            }
+            let value = move |c: usize, i: usize| -> Option<f32> {
+                let at = 18 + (c.min(channels - 1) * entries + i) * size;
+                Some(match size {
+                    1 => *vcgt.get(at)? as f32 / 255.0,
+                    _ => u16_at(vcgt, at)? as f32 / 65535.0,
+                })
+            };
+            Box::new(move |c, x| {
+                let pos = x * (entries - 1) as f32;
+                let i = (pos as usize).min(entries - 2);
+                let (a, b) = (value(c, i)?, value(c, i + 1)?);
+                Some(a + (b - a) * (pos - i as f32))
+            })
+        }
+        // Gamma, minimum and maximum per channel as s15.16 fixed point
+        Some(1) => {
+            let mut params = [0.0; 9];
+            for (i, param) in params.iter_mut().enumerate() {
+                *param = u32_at(vcgt, 12 + i * 4).ok_or_else(invalid)? as i32 as f32 / 65536.0;
+            }
+            Box::new(move |c, x| {
+                let [gamma, min, max] = [params[c * 3], params[c * 3 + 1], params[c * 3 + 2]];
+                Some(min + (max - min) * x.powf(gamma))
+            })
+        }
+        _ => return Err(invalid()),
+    };
+
+    (0..LUT_SIZE)
+        .map(|i| {
+            let x = i as f32 / (LUT_SIZE - 1) as f32;
+            let mut entry = [255; 4];
+            for (c, value) in entry.iter_mut().take(3).enumerate() {
+                *value = (curve(c, x)?.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+            Some(entry)
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)
+}
+
+/// Runs every finished frame through calibration curves, for displays
+/// where the greeter can't load them into the video card itself
+pub struct Calibration {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    frame: glow::Texture,
+    lut: glow::Texture,
+    frame_location: Option<glow::UniformLocation>,
+    lut_location: Option<glow::UniformLocation>,
+}
+
+unsafe fn texture(gl: &glow::Context) -> Result<glow::Texture, String> {
+    let texture = gl.create_texture()?;
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    for parameter in [glow::TEXTURE_MIN_FILTER, glow::TEXTURE_MAG_FILTER] {
+        gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, glow::NEAREST as i32);
+    }
+    Ok(texture)
+}
+
+impl Calibration {
+    pub unsafe fn new(gl: &glow::Context, lut: &[[u8; 4]]) -> Result<Self, String> {
+        let program = shader::program(gl, FRAGMENT)?;
+        let frame = texture(gl)?;
+        let lut_texture = texture(gl)?;
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA8 as i32,
+            lut.len() as i32,
+            1,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            Some(lut.concat().as_slice()),
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        Ok(Calibration {
+            program,
+            vertex_array: gl.create_vertex_array()?,
+            frame,
+            lut: lut_texture,
+            frame_location: gl.get_uniform_location(program, "frame"),
+            lut_location: gl.get_uniform_location(program, "lut"),
+        })
+    }
+
+    /// Replaces the back buffer with itself run through the curves
+    pub unsafe fn paint(&self, gl: &glow::Context, width: u32, height: u32) {
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, Some(self.frame));
+        gl.copy_tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            0,
+        );
+        gl.active_texture(glow::TEXTURE1);
+        gl.bind_texture(glow::TEXTURE_2D, Some(self.lut));
+
+        gl.disable(glow::BLEND);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.use_program(Some(self.program));
+        gl.uniform_1_i32(self.frame_location.as_ref(), 0);
+        gl.uniform_1_i32(self.lut_location.as_ref(), 1);
+        gl.bind_vertex_array(Some(self.vertex_array));
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        gl.bind_vertex_array(None);
+        gl.use_program(None);
+
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+    }
+
+    pub unsafe fn destroy(&self, gl: &glow::Context) {
+        gl.delete_vertex_array(self.vertex_array);
+        gl.delete_program(self.program);
+        gl.delete_texture(self.frame);
+        gl.delete_texture(self.lut);
+    }
+}
@@ -0,0 +1,32 @@
+use std::os::{
+    linux::net::SocketAddrExt,
+    unix::net::{SocketAddr, UnixListener},
+};
+
+/// Held for as long as the greeter runs, the name is released when the
+/// process exits
+pub struct InstanceLock(#[allow(dead_code)] UnixListener);
+
+/// Takes the per-seat lock so a second greeter started by accident exits
+/// instead of fighting over DRM master and input devices. The lock is a
+/// socket name in the abstract namespace, which needs no directory the
+/// greeter user may not be able to create. Returns `Ok(None)` if the name
+/// can't be bound for another reason, as that shouldn't keep anyone from
+/// logging in.
+pub fn lock() -> Result<Option<InstanceLock>, String> {
+    let seat = crate::seat::name();
+    let name = format!("eguigreeter/{}", seat);
+    let listener =
+        SocketAddr::from_abstract_name(&name).and_then(|addr| UnixListener::bind_addr(&addr));
+    match listener {
+        Ok(listener) => Ok(Some(InstanceLock(listener))),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Err(format!(
+            "Another eguigreeter is already running on {}, exiting",
+            seat
+        )),
+        Err(e) => {
+            eprintln!("Failed to bind @{}: {}", name, e);
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::{io, os::unix::prelude::RawFd, process::Command};
+
+const KDGKBLED: libc::c_ulong = 0x4B64;
+const KDSKBLED: libc::c_ulong = 0x4B65;
+const KDSETLED: libc::c_ulong = 0x4B32;
+const K_SCROLLLOCK: libc::c_char = 0x01;
+const K_NUMLOCK: libc::c_char = 0x02;
+const K_CAPSLOCK: libc::c_char = 0x04;
+
+/// Lock state of the VT keyboard. Raw-mode stdin carries no modifiers, so
+/// this is the only way to know about Caps Lock on the DRM backend.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockState {
+    pub caps: bool,
+    pub num: bool,
+    pub scroll: bool,
+}
+
+/// Reads the lock flags of the VT keyboard on `fd`
+pub fn tty_locks(fd: RawFd) -> io::Result<LockState> {
+    let mut flags: libc::c_char = 0;
+    if unsafe { libc::ioctl(fd, KDGKBLED, &mut flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(LockState {
+        caps: flags & K_CAPSLOCK != 0,
+        num: flags & K_NUMLOCK != 0,
+        scroll: flags & K_SCROLLLOCK != 0,
+    })
+}
+
+/// Makes the LEDs of the VT keyboard on `fd` follow its lock flags, in case
+/// whatever ran on the VT before left them set by hand
+pub fn sync_tty_leds(fd: RawFd) -> io::Result<()> {
+    // Anything above 7 makes the LEDs follow the keyboard flags again
+    if unsafe { libc::ioctl(fd, KDSETLED, 0xFF as libc::c_ulong) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sets the Num Lock flag of the VT keyboard on `fd`, used when input is read
+/// from the TTY on the DRM backend
+pub fn set_tty_numlock(fd: RawFd, on: bool) -> io::Result<()> {
+    let mut flags: libc::c_char = 0;
+    unsafe {
+        if libc::ioctl(fd, KDGKBLED, &mut flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if on {
+            flags |= K_NUMLOCK;
+        } else {
+            flags &= !K_NUMLOCK;
+        }
+        if libc::ioctl(fd, KDSKBLED, flags as libc::c_ulong) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    sync_tty_leds(fd)
+}
+
+/// Sets Num Lock on X11 through `numlockx`; Wayland compositors own the
+/// keyboard state, so there is nothing to do there
+pub fn set_x11_numlock(on: bool) -> io::Result<()> {
+    Command::new("numlockx")
+        .arg(if on { "on" } else { "off" })
+        .status()
+        .map(|_| ())
+}
+
+/// Whether any input device looks like a keyboard, i.e. has letter keys.
+/// Power buttons and headset remotes report as keyboards too, so the kernel's
+/// `kbd` handler alone says nothing.
+pub fn present() -> bool {
+    const KEY_Q: usize = 16;
+    const KEY_A: usize = 30;
+    const KEY_Z: usize = 44;
+    let devices = match std::fs::read_to_string("/proc/bus/input/devices") {
+        Ok(devices) => devices,
+        // Better a keyboard too many than a greeter nobody can type into
+        Err(_) => return true,
+    };
+    devices.split("\n\n").any(|device| {
+        let keys: Vec<u64> = match device.lines().find_map(|line| line.strip_prefix("B: KEY=")) {
+            Some(keys) => keys
+                .split_whitespace()
+                .rev()
+                .filter_map(|word| u64::from_str_radix(word, 16).ok())
+                .collect(),
+            None => return false,
+        };
+        // Words are as wide as the kernel's longs, least significant last
+        let bits = usize::BITS as usize;
+        [KEY_Q, KEY_A, KEY_Z].iter().all(|&key| {
+            keys.get(key / bits)
+                .map_or(false, |word| word & (1 << (key % bits)) != 0)
+        })
+    })
+}
@@ -0,0 +1,105 @@
+//! The greeter's modules, split from the binary so the benchmarks can run
+//! parts of startup in-process
+
+// Only public for the binary and the benchmarks, not as an API
+#![allow(clippy::missing_safety_doc)]
+
+use std::path::PathBuf;
+
+pub mod animation;
+pub mod auth;
+pub mod backend;
+pub mod background;
+pub mod bidi;
+pub mod bluetooth;
+pub mod bounce;
+pub mod broadcast;
+pub mod cache;
+pub mod clock;
+pub mod config;
+pub mod crash;
+pub mod curfew;
+pub mod faillock;
+pub mod fonts;
+pub mod frames;
+pub mod gesture;
+#[cfg(feature = "gstreamer")]
+pub mod gst;
+pub mod handoff;
+pub mod hooks;
+pub mod icc;
+pub mod instance;
+pub mod keyboard;
+pub mod liveness;
+pub mod magnifier;
+pub mod metrics;
+pub mod modal;
+#[cfg(feature = "mpv")]
+pub mod mpv;
+pub mod osk;
+pub mod outbox;
+pub mod palette;
+pub mod pattern;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod plymouth;
+pub mod power;
+pub mod qr_login;
+pub mod replay;
+pub mod sas;
+pub mod screenshot;
+pub mod script;
+pub mod seat;
+pub mod secret;
+pub mod sessions;
+pub mod shader;
+pub mod speech;
+pub mod startup;
+pub mod state;
+pub mod sysinfo;
+pub mod textures;
+pub mod theme;
+pub mod trust;
+pub mod tty;
+pub mod user_data;
+pub mod users;
+pub mod vnc;
+pub mod wake;
+pub mod weather;
+
+#[cfg(not(any(feature = "mpv", feature = "gstreamer")))]
+compile_error!("either the `mpv` or the `gstreamer` feature has to be enabled");
+#[cfg(all(feature = "mpv", feature = "gstreamer"))]
+compile_error!(
+    "the `mpv` and `gstreamer` features can't be enabled together, \
+     build with `--no-default-features --features gstreamer`"
+);
+
+#[derive(Debug)]
+pub enum UserEvent {
+    Redraw,
+    OnBattery(bool),
+    Weather(String),
+    Broadcast(Option<String>),
+    QrApproved(qr_login::Approval),
+    ThemeChanged,
+    PrepareForSleep,
+    /// greetd hung up on the greeter
+    GreetdLost,
+    /// The machine woke up from suspend or hibernation
+    Resumed,
+    /// A device showed up or a network link came up, which may be someone
+    /// about to log in from elsewhere
+    Wake,
+    BackgroundLoaded(background::Loaded),
+    BackgroundRerolled(background::Rerolled),
+    /// The `on_start_session` hook exited or was killed
+    HookFinished,
+    Screenshot(PathBuf),
+    /// Repaints even if egui's output hasn't changed
+    Repaint,
+    /// Input from a VNC viewer
+    Remote(backend::event::WindowEvent<'static>),
+    /// Input or a greetd message from a recording
+    Replayed(replay::Event),
+}
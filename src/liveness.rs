@@ -0,0 +1,69 @@
+use std::{ffi::OsStr, os::unix::prelude::*, time::Duration};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// The greeter's connection to greetd, found among its open sockets by the
+/// address of the other end. greetd has no ping, and a second connection
+/// won't do as a probe: greetd cancels the session of a client that hangs up.
+fn greetd_fd() -> Option<RawFd> {
+    let sock = std::env::var_os("GREETD_SOCK")?;
+    std::fs::read_dir("/proc/self/fd")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<RawFd>().ok())
+        .find(|&fd| peer(fd).as_deref() == Some(sock.as_os_str()))
+}
+
+/// The path the Unix socket `fd` is connected to
+fn peer(fd: RawFd) -> Option<std::ffi::OsString> {
+    let mut addr = unsafe { std::mem::zeroed::<libc::sockaddr_un>() };
+    let mut len = std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let addr_ptr = (&mut addr as *mut libc::sockaddr_un).cast();
+    if unsafe { libc::getpeername(fd, addr_ptr, &mut len) } != 0
+        || addr.sun_family != libc::AF_UNIX as libc::sa_family_t
+    {
+        return None;
+    }
+    let path_len = (len as usize).checked_sub(std::mem::size_of::<libc::sa_family_t>())?;
+    let path: Vec<u8> = addr.sun_path[..path_len.min(addr.sun_path.len())]
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&c| c != 0)
+        .collect();
+    Some(OsStr::from_bytes(&path).to_owned())
+}
+
+/// Whether greetd still has its end of `fd` open. Only looks, anything
+/// waiting to be read is left for the greetd client.
+fn alive(fd: RawFd) -> bool {
+    let mut poll = libc::pollfd {
+        fd,
+        events: libc::POLLRDHUP,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut poll, 1, 0) } < 0 {
+        return true;
+    }
+    poll.revents & (libc::POLLRDHUP | libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) == 0
+}
+
+/// Checks on the connection to greetd every few seconds on a separate
+/// thread, sending [`UserEvent::GreetdLost`] once greetd hangs up. There's
+/// no getting it back: a restarted greetd starts a greeter of its own, which
+/// this one has to make way for.
+pub fn watch(proxy: EventLoopProxy<UserEvent>) {
+    let fd = match greetd_fd() {
+        Some(fd) => fd,
+        None => {
+            eprintln!("Failed to find the connection to greetd, not watching it");
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        while alive(fd) {
+            std::thread::sleep(INTERVAL);
+        }
+        let _ = proxy.send_event(UserEvent::GreetdLost);
+    });
+}
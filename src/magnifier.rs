@@ -0,0 +1,124 @@
+use glow::HasContext;
+
+/// Width of the frame around the lens, in physical pixels
+const BORDER: i32 = 3;
+
+/// A lens drawn over the finished frame, showing the area around a point
+/// magnified. The area is copied out of the back buffer into a texture and
+/// blitted back enlarged, so it works over any background.
+pub struct Magnifier {
+    texture: glow::Texture,
+    framebuffer: glow::Framebuffer,
+}
+
+impl Magnifier {
+    pub unsafe fn new(gl: &glow::Context) -> Result<Self, String> {
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        Ok(Magnifier {
+            texture,
+            framebuffer: gl.create_framebuffer()?,
+        })
+    }
+
+    /// Paints the lens centered on `center`, in physical pixels from the top
+    /// left. `size` is the lens' share of the shorter side of the screen.
+    pub unsafe fn paint(
+        &self,
+        gl: &glow::Context,
+        center: (f64, f64),
+        zoom: f32,
+        size: f32,
+        width: u32,
+        height: u32,
+    ) {
+        let (width, height) = (width as i32, height as i32);
+        let lens = ((width.min(height) as f32 * size) as i32).max(16);
+        let source = ((lens as f32 / zoom.max(1.0)) as i32).max(1);
+        // GL counts rows from the bottom
+        let (x, y) = (center.0 as i32, height - center.1 as i32);
+        let clamp = |v: i32, side: i32, max: i32| (v - side / 2).clamp(0, (max - side).max(0));
+        let (src_x, src_y) = (clamp(x, source, width), clamp(y, source, height));
+        let (dst_x, dst_y) = (clamp(x, lens, width), clamp(y, lens, height));
+
+        gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+        gl.copy_tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA,
+            src_x,
+            src_y,
+            source,
+            source,
+            0,
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+
+        let mut clear_color = [0.0; 4];
+        gl.get_parameter_f32_slice(glow::COLOR_CLEAR_VALUE, &mut clear_color);
+        gl.enable(glow::SCISSOR_TEST);
+        gl.scissor(
+            dst_x - BORDER,
+            dst_y - BORDER,
+            lens + BORDER * 2,
+            lens + BORDER * 2,
+        );
+        gl.clear_color(1.0, 1.0, 1.0, 1.0);
+        gl.clear(glow::COLOR_BUFFER_BIT);
+        gl.disable(glow::SCISSOR_TEST);
+        let [r, g, b, a] = clear_color;
+        gl.clear_color(r, g, b, a);
+
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::READ_FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(self.texture),
+            0,
+        );
+        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
+        gl.blit_framebuffer(
+            0,
+            0,
+            source,
+            source,
+            dst_x,
+            dst_y,
+            dst_x + lens,
+            dst_y + lens,
+            glow::COLOR_BUFFER_BIT,
+            glow::LINEAR,
+        );
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+    }
+
+    /// Turns the lens in `magnifier` on or off
+    pub unsafe fn toggle(magnifier: &mut Option<Self>, gl: &glow::Context) {
+        *magnifier = match magnifier.take() {
+            Some(magnifier) => {
+                magnifier.destroy(gl);
+                None
+            }
+            None => Magnifier::new(gl)
+                .map_err(|e| eprintln!("Failed to set up the magnifier: {}", e))
+                .ok(),
+        };
+    }
+
+    pub unsafe fn destroy(&self, gl: &glow::Context) {
+        gl.delete_framebuffer(self.framebuffer);
+        gl.delete_texture(self.texture);
+    }
+}
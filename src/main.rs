@@ -1,60 +1,58 @@
 use std::{
     borrow::Cow,
     cell::RefCell,
-    ffi::c_void,
     io::Read,
-    os::unix::prelude::FromRawFd,
+    os::unix::{prelude::FromRawFd, process::CommandExt},
     path::{Path, PathBuf},
     rc::Rc,
+    time::{Duration, Instant},
 };
 
 use calloop::{Interest, PostAction};
 use clap::Arg;
-use egui::{Align2, Color32, RichText, TextEdit};
+use egui::{Align2, Color32, RichText, Stroke, TextEdit};
 use freedesktop_desktop_entry::DesktopEntry;
 use greetd_client::{AuthMessageType, ErrorType, Greetd, GreetdSource, Response};
 
-use glutin::{
-    event::{DeviceId, ModifiersState, VirtualKeyCode},
+use eguigreeter::*;
+
+use backend::{
+    event::{DeviceId, ModifiersState, StartCause, VirtualKeyCode},
     event_loop::ControlFlow,
-    platform::{
-        run_return::EventLoopExtRunReturn,
-        unix::{EventLoopWindowTargetExtUnix, WindowExtUnix},
-    },
-    window::{Window, WindowId},
-    ContextWrapper, PossiblyCurrent,
-};
-use infer::MatcherType;
-use libmpv::{
-    render::{OpenGLInitParams, RenderContext, RenderParam, RenderParamApiType},
-    FileState, Mpv,
-};
-use rand::prelude::IteratorRandom;
-use time::{
-    format_description::modifier::{Hour, Minute},
-    UtcOffset,
+    window::WindowId,
+    EventLoopExtRunReturn, EventLoopWindowTargetExtUnix,
 };
+use time::UtcOffset;
 use tz::TimeZone;
 
-#[derive(PartialEq)]
-struct StrippedEntry<'a> {
-    name: Cow<'a, str>,
-    exec: &'a str,
-}
+use animation::Animation;
+use config::{AfterFinish, Config, NumLock, ShowKeyboard};
+use sessions::SessionKind;
 
-#[derive(Debug)]
-enum UserEvent {
-    Redraw,
-}
+#[cfg(feature = "gstreamer")]
+use gst::Video;
+#[cfg(feature = "mpv")]
+use mpv::Video;
 
-pub fn get_proc_address(
-    display: &Rc<ContextWrapper<PossiblyCurrent, Window>>,
-    name: &str,
-) -> *mut c_void {
-    display.get_proc_address(name) as *mut c_void
-}
+/// How long the fake password prompt for unknown users takes to fail,
+/// pam_unix's default fail delay
+const UNKNOWN_USER_DELAY: Duration = Duration::from_secs(2);
+const UI_SCALE_STEP: f32 = 0.1;
+/// How long a session has to stay selected before it's warmed up, so
+/// scrolling through the list doesn't warm up every one
+const PREWARM_DELAY: Duration = Duration::from_secs(1);
+/// Seconds spent waiting for greetd's socket at startup before giving up
+const GREETD_RETRIES: u32 = 10;
+/// How long the greeter says greetd is gone before it exits
+const GREETD_LOST_EXIT: Duration = Duration::from_secs(3);
 
 fn main() {
+    let mut args = std::env::args_os().skip(1);
+    if args.next().map_or(false, |arg| arg == handoff::FLAG) {
+        eprintln!("Failed to start the session: {}", handoff::run(args));
+        std::process::exit(1);
+    }
+    crash::install();
     let command = clap::Command::new("eguigreeter")
         .args(&[
             Arg::new("background")
@@ -72,24 +70,171 @@ fn main() {
                 .short('s')
                 .value_hint(clap::ValueHint::Other)
                 .help("Sets the default session for this login"),
+            Arg::new("config")
+                .long("config")
+                .short('c')
+                .value_hint(clap::ValueHint::FilePath)
+                .default_value(config::DEFAULT_PATH)
+                .help("Path to the greeter's config file"),
+            Arg::new("record")
+                .long("record")
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with("replay")
+                .help("Records input and greetd's messages to a file, with passwords masked"),
+            Arg::new("replay")
+                .long("replay")
+                .value_hint(clap::ValueHint::FilePath)
+                .help("Plays a recording back without connecting to greetd or anything else, running hooks or saving state"),
+            Arg::new("bench-startup")
+                .long("bench-startup")
+                .help("Prints how long startup and the frames after it take, then exits. Touches nothing outside the greeter and saves no state"),
         ])
         .get_matches();
-    let mut event_loop: glutin::event_loop::EventLoop<UserEvent> =
-        glutin::event_loop::EventLoopBuilder::with_user_event().build();
-    let display = unsafe {
-        Rc::new(
-            glutin::ContextBuilder::new()
-                .with_vsync(true)
-                .build_windowed(
-                    glutin::window::WindowBuilder::new().with_resizable(true),
-                    &event_loop,
-                )
-                .unwrap()
-                .make_current()
-                .unwrap(),
+    let bench = command.is_present("bench-startup");
+    let mut trace = startup::Trace::new(bench);
+    // A benchmark may run next to a real greeter
+    let _instance = (!bench).then(|| match instance::lock() {
+        Ok(lock) => lock,
+        Err(message) => crash::fail(&message),
+    });
+    let (mut config, config_error) = Config::load(command.value_of("config").unwrap());
+    // A replay only plays the greeter itself, without running anything or
+    // writing anything down
+    let replaying = command.value_of("replay").is_some();
+    if replaying {
+        config.script = None;
+    }
+    // Neither a replay nor a benchmark reaches out to anything, which would
+    // have them depend on more than the greeter
+    let offline = bench || replaying;
+    if offline {
+        config.hooks = config::Hooks::default();
+        config.session_crash.enabled = false;
+        config.vnc = None;
+        config.bluetooth_pairing = false;
+        config.qr_login = None;
+        config.metrics_socket = None;
+        config.screenshot.dbus = false;
+        config.weather.enabled = false;
+        config.numlock = NumLock::Keep;
+    }
+    if bench {
+        config.plymouth.handoff = false;
+    }
+    crash::note("config", command.value_of("config").unwrap());
+    crash::note("config error", config_error.as_deref().unwrap_or("none"));
+    crash::note("theme", config.theme.as_deref().unwrap_or("default"));
+    crash::note(
+        "background",
+        command
+            .value_of("background")
+            .or(config.background.path.as_deref())
+            .unwrap_or("none"),
+    );
+    crash::note(
+        "video backend",
+        if cfg!(feature = "gstreamer") {
+            "gstreamer"
+        } else {
+            "mpv"
+        },
+    );
+    startup::mark(&mut trace, "config");
+
+    // Slow work that doesn't need the display runs while it's being set up
+    let sessions_scan = std::thread::spawn(|| {
+        let started = Instant::now();
+        (sessions::read(), started.elapsed())
+    });
+    let seat_scan = std::thread::spawn(seat::sessions);
+    let greetd_connect = std::thread::spawn(move || {
+        // A replay brings greetd's side along, a benchmark does without
+        if offline {
+            return Ok(None);
+        }
+        // greetd may be a moment late with its socket, e.g. right after a restart
+        let mut attempts = 0;
+        loop {
+            match Greetd::new() {
+                Ok(greetd) => break Ok(Some(greetd)),
+                Err(e) if attempts < GREETD_RETRIES => {
+                    eprintln!("Failed to connect to greetd, retrying: {:?}", e);
+                    attempts += 1;
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+                Err(e) => break Err(format!("Failed to connect to greetd: {:?}", e)),
+            }
+        }
+    });
+    let bounce_check = {
+        let enabled = config.session_crash.enabled;
+        let within = Duration::from_secs_f32(config.session_crash.within.max(0.0));
+        let lines = config.session_crash.lines;
+        std::thread::spawn(move || enabled.then(|| bounce::check(within, lines)).flatten())
+    };
+    let clock_lookup = {
+        let zones: Vec<String> = config.clocks.iter().map(|c| c.timezone.clone()).collect();
+        let info_line = config.info_line;
+        std::thread::spawn(move || {
+            let offset = clock::offset(&TimeZone::local().unwrap()).unwrap();
+            let world: Vec<Option<UtcOffset>> =
+                zones.iter().map(|zone| clock::zone_offset(zone)).collect();
+            (offset, info_line.then(|| sysinfo::info_line(offset)), world)
+        })
+    };
+    let locale = config.locale();
+    let font_scan = {
+        let fallback = config.font_fallback.clone();
+        let locale = locale.clone();
+        std::thread::spawn(move || fonts::resolve(&fallback, locale.as_deref()))
+    };
+
+    if config.plymouth.handoff {
+        plymouth::deactivate();
+    }
+    if let Some(theme) = &config.cursor.theme {
+        std::env::set_var("XCURSOR_THEME", theme);
+    }
+    if let Some(size) = config.cursor.size {
+        std::env::set_var("XCURSOR_SIZE", size.to_string());
+    }
+    let cursor_hide_after = config.cursor.hide_after.map(Duration::from_secs_f32);
+    let mut cursor_moved_at = Instant::now();
+    let mut cursor_visible = true;
+    let blank_after =
+        (config.blank_after > 0.0).then(|| Duration::from_secs_f32(config.blank_after));
+    let mut input_at = Instant::now();
+    let mut blanked = false;
+    let text_fallback = || {
+        tty::run(
+            &config,
+            command.value_of("username"),
+            command.value_of("session"),
         )
     };
+    // winit panics when neither DRM nor a display server is usable
+    let mut event_loop: backend::event_loop::EventLoop<UserEvent> = match crash::expected(|| {
+        backend::event_loop::EventLoopBuilder::with_user_event().build()
+    }) {
+        Ok(event_loop) => event_loop,
+        Err(_) => return text_fallback(),
+    };
+    let backend_kind = backend::Kind::of(&event_loop);
+    crash::note("display", backend_kind.name());
+    let display = match backend::create_display(
+        &event_loop,
+        &config.gl.fallbacks(),
+        config.vsync,
+        config.video.hdr,
+    ) {
+        Ok(display) => Rc::new(display),
+        Err(e) => {
+            eprintln!("{}", e);
+            return text_fallback();
+        }
+    };
     let mut size = display.window().inner_size();
+    startup::mark(&mut trace, "display");
 
     let gl = unsafe {
         Rc::new(glow::Context::from_loader_function(|c| {
@@ -98,102 +243,280 @@ fn main() {
     };
 
     let mut egui_glow = egui_glow::EguiGlow::new(display.window(), gl.clone());
+    let mut greeter_state = state::State::load();
+    greeter_state.read_only = offline;
+    set_ui_scale(&mut egui_glow, display.window(), greeter_state.ui_scale);
+    let mut plymouth_pending = config.plymouth.handoff;
 
-    let mut vid = || -> Option<(Option<RenderContext>, Mpv)> {
-        let mut path = command.value_of("background")?.to_string();
-
-        if Path::new(&path).is_dir() {
-            path = std::fs::read_dir(path)
-                .ok()?
-                .choose(&mut rand::rngs::OsRng)?
-                .ok()?
-                .path()
-                .to_str()?
-                .to_string();
-        } else if !Path::new(&path).exists() {
-            return None;
-        }
+    unsafe {
+        use glow::HasContext as _;
+        let [r, g, b] = config.plymouth.color;
+        gl.clear_color(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+    }
 
-        let is_image = if let Some(mime) = infer::Infer::new().get_from_path(&path).ok()? {
-            mime.matcher_type() == MatcherType::Image
-        } else {
-            false
-        };
+    let background_arg = command
+        .value_of("background")
+        .or(config.background.path.as_deref())
+        .filter(|b| {
+            let path = Path::new(b.strip_prefix("shader:").unwrap_or(b));
+            trust::allowed(path, config.fleet.as_ref(), "background")
+        });
+    let shader = background_arg
+        .and_then(|b| b.strip_prefix("shader:"))
+        .and_then(|path| match unsafe { shader::Shader::new(&gl, path) } {
+            Ok(shader) => Some(shader),
+            Err(e) => {
+                eprintln!("Failed to load shader background {}: {}", path, e);
+                None
+            }
+        });
+
+    let mut background_caption = None;
+    let mut background_accent = None;
+    let mut background_dir = None;
+    let mut animation = None;
+    let mut vid: Option<Video> = None;
+    // What the video was made from and whether it's paused, to remake it
+    let mut vid_source: Option<(String, bool)> = None;
+    let mut on_battery = false;
+    let output = display.window().current_monitor().and_then(|m| m.name());
+    let calibration = config.icc.profile_for(output.as_deref()).and_then(|path| {
+        icc::curves(path)
+            .and_then(|curves| unsafe { icc::Calibration::new(&gl, &curves) })
+            .map_err(|e| eprintln!("Failed to apply ICC profile {}: {}", path.display(), e))
+            .ok()
+    });
+    // Loaded on a separate thread once the first frame is up
+    let mut pending_background = background_arg.filter(|b| !b.starts_with("shader:"));
+    let background_proxy = event_loop.create_proxy();
+    let mut watching_battery = false;
+    power::watch_sleep(event_loop.create_proxy());
+    if blank_after.is_some() {
+        wake::watch(event_loop.create_proxy());
+    }
+
+    let mut broadcast_message = None;
+    if !offline {
+        broadcast::watch(
+            config
+                .broadcast_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("/run/eguigreeter/message")),
+            event_loop.create_proxy(),
+        );
+    }
+
+    let qr_proxy = event_loop.create_proxy();
+    let hook_proxy = event_loop.create_proxy();
+    let mut qr_session = config
+        .qr_login
+        .as_ref()
+        .and_then(|qr| qr_login::QrSession::start(qr, qr_proxy.clone()));
+    let mut qr_secret: Option<String> = None;
 
-        let mut mpv = Mpv::with_initializer(|f| {
-            if is_image {
-                f.set_property("keep-open", true)?;
-            } else {
-                f.set_property("audio", false)?;
-                f.set_property("loop-file", true)?;
-                f.set_property("hwdec", "auto-safe")?;
+    let seat_sessions = seat_scan.join().unwrap();
+    let mut unlock_session: Option<String> = None;
+    // The power menu, its confirmations and crash reports
+    let mut modal: Option<modal::Modal<DialogAction>> = None;
+    // Only shown to the user whose session it was, once they log in again
+    let mut crash_report = bounce_check.join().unwrap();
+    // The report is read before the session starts
+    let mut reading_report = false;
+    let mut script = config.script.as_deref().and_then(script::Script::load);
+    let metrics = config.metrics_socket.clone().map(metrics::serve);
+    #[cfg(feature = "plugins")]
+    let mut plugins = if replaying {
+        plugin::Plugins::default()
+    } else {
+        plugin::Plugins::load(plugin::DEFAULT_DIR, config.fleet.as_ref())
+    };
+    let mut finish_splash: Option<&'static str> = None;
+    let sas_chord = config.secure_attention.as_ref().and_then(|sas| {
+        let chord = sas::Chord::parse(&sas.chord);
+        match &chord {
+            None => eprintln!("Invalid secure attention chord {}", sas.chord),
+            // It would hide the login form for good
+            Some(chord) if backend_kind == backend::Kind::Drm && !chord.typeable() => {
+                eprintln!(
+                    "Secure attention chord {} can't be typed on the TTY, ignoring it",
+                    sas.chord
+                );
+                return None;
             }
-            f.set_property("panscan", 1.0)
-        })
-        .ok()?;
-        if Path::new("/etc/mpv/mpv.conf").exists() {
-            mpv.load_config("/etc/mpv/mpv.conf").ok()?;
-        }
-        let mut params = vec![
-            RenderParam::ApiType(RenderParamApiType::OpenGl),
-            RenderParam::InitParams(OpenGLInitParams {
-                get_proc_address,
-                ctx: display.clone(),
-            }),
-        ];
-        if let Some(display) = event_loop.wayland_display() {
-            params.push(RenderParam::WaylandDisplay(display as _));
-        } else if let Some(display) = display.window().xlib_display() {
-            params.push(RenderParam::X11Display(display as _));
+            Some(_) => {}
         }
-        let mut render_context = RenderContext::new(unsafe { mpv.ctx.as_mut() }, params).ok()?;
-        mpv.event_context_mut().disable_deprecated_events().unwrap();
-        let event_proxy = event_loop.create_proxy();
-        render_context.set_update_callback(move || {
-            event_proxy.send_event(UserEvent::Redraw).unwrap();
-        });
-        mpv.playlist_load_files(&[(&path, FileState::AppendPlay, None)])
-            .unwrap();
+        chord
+    });
+    let mut attended = sas_chord.is_none();
+    let magnifier_chord = sas::Chord::parse(&config.magnifier.hotkey);
+    match &magnifier_chord {
+        None => eprintln!("Invalid magnifier hotkey {}", config.magnifier.hotkey),
+        Some(chord) if backend_kind == backend::Kind::Drm && !chord.typeable() => eprintln!(
+            "Magnifier hotkey {} can't be typed on the TTY",
+            config.magnifier.hotkey
+        ),
+        Some(_) => {}
+    }
+    let speech_chord = sas::Chord::parse(&config.speech.hotkey);
+    match &speech_chord {
+        None => eprintln!("Invalid speech hotkey {}", config.speech.hotkey),
+        Some(chord) if backend_kind == backend::Kind::Drm && !chord.typeable() => eprintln!(
+            "Speech hotkey {} can't be typed on the TTY",
+            config.speech.hotkey
+        ),
+        Some(_) => {}
+    }
+    let screenshot_chord = sas::Chord::parse(&config.screenshot.hotkey);
+    if screenshot_chord.is_none() {
+        eprintln!("Invalid screenshot hotkey {}", config.screenshot.hotkey);
+    }
+    if config.screenshot.dbus {
+        screenshot::serve(event_loop.create_proxy());
+    }
+    // Where to save the next frame
+    let mut screenshot_to: Option<PathBuf> = None;
+    let mut speech = speech::Speech::new(greeter_state.speech.unwrap_or(config.speech.enabled));
+    if config.speech.announce && speech_chord.is_some() {
+        speech::say(&format!(
+            "Login screen. Press {} to turn speech {}",
+            config.speech.hotkey.replace('+', " "),
+            if speech.enabled() { "off" } else { "on" }
+        ));
+    }
+    let mut magnifier: Option<magnifier::Magnifier> = None;
+    let mut frames = frames::Frames::default();
+    let frame_interval =
+        (config.max_fps > 0).then(|| Duration::from_secs_f64(1.0 / config.max_fps as f64));
+    let mut last_frame_at = Instant::now();
+    // A redraw put off to keep under `max_fps`
+    let mut capped_redraw_at: Option<Instant> = None;
+    // Follows the mouse or the text cursor, whichever moved last
+    let mut lens_at = (0.0, 0.0);
+    let mut text_cursor: Option<egui::Pos2> = None;
+    let mut sas_logo: Option<Option<egui::TextureHandle>> = None;
+    let mut modifiers = ModifiersState::empty();
+    let mut exit_at: Option<Instant> = None;
 
-        Some((Some(render_context), mpv))
-    }();
+    let mut weather_report = None;
+    if config.weather.enabled {
+        weather_report = weather::cached();
+        weather::watch(&config.weather, event_loop.create_proxy());
+    }
 
-    let mut stream = Greetd::new().unwrap();
+    let greetd = greetd_connect
+        .join()
+        .unwrap()
+        .unwrap_or_else(|message| crash::fail(&message));
+    let (outbox, greetd_source) = match greetd {
+        Some(mut greetd) => {
+            let source = greetd.event_source();
+            liveness::watch(event_loop.create_proxy());
+            (outbox::Outbox::spawn(greetd), Some(source))
+        }
+        None => (outbox::Outbox::discard(), None),
+    };
+    startup::mark(&mut trace, "greetd");
+    let mut auth = auth::Auth::new(outbox, config.username_policy.clone());
+    let mut recorder =
+        command
+            .value_of("record")
+            .and_then(|path| match replay::Recorder::create(path) {
+                Ok(recorder) => Some(recorder),
+                Err(e) => {
+                    eprintln!("Failed to record to {}: {}", path, e);
+                    None
+                }
+            });
+    let mut player = command.value_of("replay").and_then(|path| {
+        match replay::Player::open(path, event_loop.create_proxy()) {
+            Ok(player) => Some(player),
+            Err(e) => {
+                eprintln!("Failed to replay {}: {}", path, e);
+                None
+            }
+        }
+    });
+    let mut greetd_lost = false;
     let response_queue = Rc::new(RefCell::new(None));
 
     let mut focused = FocusedField::Username;
     let mut username = String::new();
+    let mut show_username_field = command.value_of("username").is_none();
+    let mut username_hint: Option<&'static str> = None;
+    // Whether the last input overflowed the field's limit, until it's shorter
+    let mut username_cut = false;
+    let mut password_cut = false;
 
     if let Some(defaults) = command.value_of("username") {
         username = defaults.to_string();
-        stream.create_session(&username).unwrap();
-        focused = FocusedField::Password;
+        secret::truncate(&mut username, config.input_limits.username);
+        match auth.create_session(&username) {
+            Ok(()) => {
+                hooks::run(config.hooks.on_login.as_deref(), &username, None, None);
+                focused = FocusedField::Password;
+            }
+            Err(hint) => {
+                username_hint = Some(hint);
+                show_username_field = true;
+            }
+        }
+    } else if let Some(last) = greeter_state
+        .last_username
+        .as_ref()
+        .filter(|_| config.remember_username)
+    {
+        username = last.clone();
     }
 
     crossterm::terminal::enable_raw_mode().unwrap();
 
-    let timezone = TimeZone::local().unwrap();
-    let offset = timezone.find_current_local_time_type().unwrap().ut_offset();
-    let current_time =
-        time::OffsetDateTime::now_utc().to_offset(UtcOffset::from_whole_seconds(offset).unwrap());
-    let clock = current_time
-        .format(
-            [
-                time::format_description::FormatItem::Component(
-                    time::format_description::Component::Hour({
-                        let mut h = Hour::default();
-                        h.is_12_hour_clock = true;
-                        h
-                    }),
-                ),
-                time::format_description::FormatItem::Literal(b":"),
-                time::format_description::FormatItem::Component(
-                    time::format_description::Component::Minute(Minute::default()),
-                ),
-            ]
-            .as_ref(),
+    let tty_input = backend_kind == backend::Kind::Drm;
+    let vnc = config.vnc.as_ref().and_then(|vnc| {
+        vnc::Server::start(
+            vnc.socket.clone(),
+            vnc.view_only,
+            tty_input,
+            event_loop.create_proxy(),
         )
-        .unwrap_or_else(|_| "??:??".to_string());
+    });
+    let mut locks = keyboard::LockState::default();
+    if tty_input && !offline {
+        if let Err(e) = keyboard::sync_tty_leds(0) {
+            eprintln!("Failed to reset the keyboard LEDs: {}", e);
+        }
+    }
+    if config.numlock != NumLock::Keep {
+        let on = config.numlock == NumLock::On;
+        let result = if tty_input {
+            keyboard::set_tty_numlock(0, on)
+        } else if backend_kind == backend::Kind::X11 {
+            keyboard::set_x11_numlock(on)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to set Num Lock: {}", e);
+        }
+    }
+
+    let (offset, info_line, world_offsets) = clock_lookup.join().unwrap();
+    let format_clocks = || {
+        let world: Vec<(&str, String)> = config
+            .clocks
+            .iter()
+            .zip(&world_offsets)
+            .map(|(c, offset)| {
+                let time = offset
+                    .map(|offset| clock::format(offset, config.clock_seconds))
+                    .unwrap_or_else(|| "??:??".to_string());
+                (c.label.as_str(), time)
+            })
+            .collect();
+        (clock::format(offset, config.clock_seconds), world)
+    };
+    let (mut clock, mut world_clocks) = format_clocks();
+    let mut clock_tick = clock::next_tick(config.clock_seconds);
+    startup::mark(&mut trace, "clock");
 
     if let Some(handle) = event_loop.drm_calloop_handle() {
         let stdin_source = calloop::generic::Generic::new(
@@ -205,18 +528,18 @@ fn main() {
         let stdin_dispatcher: calloop::Dispatcher<
             'static,
             calloop::generic::Generic<std::fs::File>,
-            Vec<glutin::event::Event<'static, ()>>,
+            Vec<backend::event::Event<'static, ()>>,
         > = calloop::Dispatcher::new(
             stdin_source,
-            move |_, stdin, shared_data: &mut Vec<glutin::event::Event<'static, ()>>| {
+            move |_, stdin, shared_data: &mut Vec<backend::event::Event<'static, ()>>| {
                 let mut b = [0x00];
                 if stdin.read_exact(&mut b).is_err() {
                     crossterm::terminal::disable_raw_mode().unwrap();
                     return Ok(PostAction::Remove);
                 }
-                shared_data.push(glutin::event::Event::WindowEvent {
+                shared_data.push(backend::event::Event::WindowEvent {
                     window_id: unsafe { WindowId::dummy() },
-                    event: glutin::event::WindowEvent::ReceivedCharacter(b[0] as char),
+                    event: backend::event::WindowEvent::ReceivedCharacter(b[0] as char),
                 });
                 Ok(PostAction::Continue)
             },
@@ -224,59 +547,222 @@ fn main() {
 
         handle.register_dispatcher(stdin_dispatcher).unwrap();
 
-        let rq = response_queue.clone();
+        if let Some(greetd_source) = greetd_source {
+            let rq = response_queue.clone();
 
-        let stream_dispatcher: calloop::Dispatcher<
-            'static,
-            GreetdSource,
-            Vec<glutin::event::Event<'static, ()>>,
-        > = calloop::Dispatcher::new(stream.event_source(), move |event, _, _| {
-            let mut rs = rq.borrow_mut();
-            if rs.is_some() {
-                panic!("Multiple events cannot be in the queue at once");
-            } else {
-                *rs = Some(event);
-            }
-        });
+            let stream_dispatcher: calloop::Dispatcher<
+                'static,
+                GreetdSource,
+                Vec<backend::event::Event<'static, ()>>,
+            > = calloop::Dispatcher::new(greetd_source, move |event, _, _| {
+                let mut rs = rq.borrow_mut();
+                if rs.is_some() {
+                    panic!("Multiple events cannot be in the queue at once");
+                } else {
+                    *rs = Some(event);
+                }
+            });
 
-        handle.register_dispatcher(stream_dispatcher).unwrap();
+            handle.register_dispatcher(stream_dispatcher).unwrap();
+        }
     }
 
-    let environments_raw: Vec<(String, PathBuf)> = freedesktop_desktop_entry::Iter::new(vec![
-        PathBuf::from("/usr/share/wayland-sessions"),
-        PathBuf::from("/usr/share/xsessions"),
-    ])
-    .filter_map(|path| Some((std::fs::read_to_string(&path).ok()?, path)))
-    .collect();
-    let environments_serialized: Vec<DesktopEntry> = environments_raw
-        .iter()
-        .filter_map(|(bytes, path)| DesktopEntry::decode(path, bytes).ok())
-        .collect();
-    let environments: Vec<StrippedEntry> = environments_serialized
+    // Owned, so the desktop files' contents are freed right away
+    let environments: Vec<sessions::StrippedEntry<'static>> = {
+        let (environments_raw, scanned_in) = sessions_scan.join().unwrap();
+        startup::background(&mut trace, "session scan", scanned_in);
+        let environments_serialized: Vec<DesktopEntry> = environments_raw
+            .iter()
+            .filter_map(|(bytes, path)| DesktopEntry::decode(path, bytes).ok())
+            .collect();
+        sessions::strip(
+            &environments_serialized,
+            &config.session_priority,
+            locale.as_deref(),
+        )
+        .into_iter()
+        .map(sessions::StrippedEntry::into_owned)
+        .collect()
+    };
+    let mut session_icons: Vec<_> = environments
         .iter()
-        .filter_map(|f| {
-            Some(StrippedEntry {
-                name: f.name(None)?,
-                exec: f.exec()?,
-            })
-        })
+        .map(|env| env.load_icon(&egui_glow.egui_ctx))
         .collect();
+    startup::mark(&mut trace, "sessions");
     let mut current_env_index = if let Some(session) = command.value_of("session") {
         environments
             .iter()
             .position(|f| f.name == Cow::Borrowed(session))
             .unwrap_or(0)
     } else {
-        0
+        greeter_state
+            .last_session
+            .as_deref()
+            .and_then(|session| environments.iter().position(|env| env.is(session)))
+            .unwrap_or(0)
     };
-    let mut current_env = &environments[current_env_index];
     let mut pending_focus = true;
     let mut auth_message = String::new();
     let mut auth_message_type: Option<AuthMessageType> = None;
     let mut password = String::new();
-    let mut window_title = Cow::Borrowed("Login");
-    event_loop.run_return(|event, _, control_flow| {
-        if let Some(i) = response_queue.take() {
+    let default_title: Cow<str> = match &config.branding.product_name {
+        Some(product) => Cow::Owned(format!("Log in to {}", product)),
+        None => Cow::Borrowed("Login"),
+    };
+    let mut window_title = default_title.clone();
+    let mut brand_logo: Option<Option<egui::TextureHandle>> = None;
+    let user_data = user_data::Providers::new(&config.user_data, event_loop.create_proxy());
+    let mut user_info = user_data::UserInfo::default();
+    let mut face: Option<(String, Option<egui::TextureHandle>)> = None;
+    let mut starting_session = false;
+    // The session command, held back until the `on_start_session` hook is done
+    let mut pending_start: Option<Vec<String>> = None;
+    let mut login_failed = false;
+    let mut reroll_background = false;
+    let mut logins_disabled = false;
+    let mut locked_until: Option<Instant> = None;
+    let fail_delay = Duration::from_secs_f32(config.fail_delay.max(0.0));
+    let mut failed_at: Option<Instant> = None;
+    let mut fake_prompt = false;
+    let escape_window = Duration::from_secs_f32(config.escape_restart.max(0.0));
+    let mut escape_at: Option<Instant> = None;
+    let mut restart_at: Option<Instant> = None;
+    let mut gestures = gesture::Tracker::default();
+    let mut show_password = false;
+    let mut password_rect: Option<egui::Rect> = None;
+    // Whether the secret being asked for is entered on the PIN pad
+    let mut pin_pad = false;
+    let mut pattern = pattern::Pattern::default();
+    let pad_proxy = event_loop.create_proxy();
+    // Session changes are read out when speech is on
+    let mut announced_env = current_env_index;
+    let mut selected_env: Option<usize> = None;
+    let mut prewarm_at: Option<Instant> = None;
+    let mut prewarmed: Vec<usize> = Vec::new();
+    let mut fake_failure_at: Option<Instant> = None;
+    // The made up prompt is up for failing, greetd knows nothing of it
+    let mut fake_failure = false;
+    let auth_timeout = Duration::from_secs(config.auth_timeout);
+    let mut auth_sent_at: Option<Instant> = None;
+    let font_fallbacks = font_scan.join().unwrap();
+    let load_theme = |name: &str| {
+        if theme::Theme::builtin(name).is_none()
+            && !trust::allowed(Path::new(name), config.fleet.as_ref(), "theme")
+        {
+            return None;
+        }
+        theme::Theme::load(name)
+    };
+    let mut theme = config
+        .theme
+        .as_deref()
+        .and_then(load_theme)
+        .unwrap_or_default();
+    theme.apply(&egui_glow.egui_ctx, &font_fallbacks);
+    let mut overlay = theme.overlay_color();
+    startup::mark(&mut trace, "theme");
+    if let Some(path) = config
+        .theme
+        .as_ref()
+        .filter(|t| theme::Theme::builtin(t).is_none())
+    {
+        theme::watch(path.clone(), event_loop.create_proxy());
+    }
+    let visuals_for = |theme: &theme::Theme, accent: Option<[u8; 3]>, state: &state::State| {
+        let mut visuals = theme.visuals();
+        if state.high_contrast {
+            visuals = theme::high_contrast(&visuals);
+        }
+        if let Some(color) = accent {
+            visuals = theme::tinted(&visuals, color);
+        }
+        visuals.text_cursor_width = config.login_window.caret_width;
+        if let Some([r, g, b]) = config.branding.accent {
+            visuals.selection.bg_fill = Color32::from_rgb(r, g, b);
+            visuals.hyperlink_color = Color32::from_rgb(r, g, b);
+        }
+        if let Some([r, g, b]) = config.login_window.selection_color {
+            visuals.selection.bg_fill = Color32::from_rgb(r, g, b);
+        }
+        state.palette.apply(&mut visuals);
+        visuals
+    };
+    let mut base_visuals = visuals_for(&theme, background_accent, &greeter_state);
+    egui_glow.egui_ctx.set_visuals(base_visuals.clone());
+    set_reduce_motion(&egui_glow.egui_ctx, greeter_state.reduce_motion);
+    // Kiosks with only a touchscreen get keys on screen and bigger targets
+    let keyboard_present = keyboard::present();
+    let show_osk =
+        greeter_state
+            .on_screen_keyboard
+            .unwrap_or(match config.on_screen_keyboard.show {
+                ShowKeyboard::Auto => !keyboard_present,
+                ShowKeyboard::Always => true,
+                ShowKeyboard::Never => false,
+            });
+    let osk_proxy = event_loop.create_proxy();
+    let mut osk = show_osk.then(|| {
+        osk::touch_style(&egui_glow.egui_ctx, config.on_screen_keyboard.touch_scale);
+        osk::Osk::new(osk_proxy.clone())
+    });
+    let pairing = (config.bluetooth_pairing && !keyboard_present)
+        .then(|| bluetooth::Pairing::start(event_loop.create_proxy()));
+    let mut fade_start: Option<f64> = None;
+    let mut faded_in = config.fade_in.duration <= 0.0 || greeter_state.reduce_motion;
+    let mut frame_bench = bench.then(startup::FrameBench::default);
+    event_loop.run_return(|event, target, control_flow| {
+        // Remote input goes where the local keyboard and mouse's would
+        let event = match event {
+            backend::event::Event::UserEvent(UserEvent::Remote(event)) => {
+                backend::event::Event::WindowEvent {
+                    window_id: display.window().id(),
+                    event,
+                }
+            }
+            // Replayed input too, and replayed messages as if greetd sent them
+            backend::event::Event::UserEvent(UserEvent::Replayed(event)) => {
+                match event.window_event() {
+                    Some(event) => backend::event::Event::WindowEvent {
+                        window_id: display.window().id(),
+                        event,
+                    },
+                    None => {
+                        *response_queue.borrow_mut() = event.response();
+                        backend::event::Event::UserEvent(UserEvent::Repaint)
+                    }
+                }
+            }
+            event => event,
+        };
+        let response = response_queue.take();
+        if let Some(recorder) = recorder.as_mut() {
+            if let Some(response) = &response {
+                recorder.response(response);
+            }
+            if let backend::event::Event::WindowEvent { event, .. } = &event {
+                let secret = focused == FocusedField::Password
+                    && matches!(auth_message_type, Some(AuthMessageType::Secret));
+                recorder.input(event, secret);
+            }
+        }
+        if response.is_some() {
+            auth_sent_at = None;
+        }
+        let response = response
+            .and_then(|response| auth.receive(response))
+            .or_else(|| std::mem::take(&mut fake_failure).then(auth::fake_failure));
+        if restart_at.is_some() && auth.settled() {
+            // greetd acknowledged the cancellation
+            eprintln!("Failed to restart the greeter: {}", restart());
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+        if starting_session && unlock_session.is_some() && auth.settled() {
+            // Nothing to start, the session was switched to
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+        if let Some(i) = response {
             match i {
                 Response::AuthMessage {
                     auth_message_type: at,
@@ -284,39 +770,173 @@ fn main() {
                 } => {
                     auth_message = am;
                     auth_message_type = Some(at);
+                    speech.say(&auth_message);
                     if let Some(AuthMessageType::Info) | Some(AuthMessageType::Error) =
                         auth_message_type
                     {
-                        stream.authentication_response(None).unwrap();
+                        if let Some(retry_after) = faillock::retry_after(&auth_message) {
+                            locked_until = Some(Instant::now() + retry_after);
+                        }
+                        auth.authentication_response(None);
+                    } else if let Some(secret) = qr_secret.take() {
+                        auth.authentication_response(Some(&secret));
                     }
                     display.window().request_redraw();
                 }
                 Response::Finish => {
+                    if config.session_crash.enabled {
+                        bounce::started(&username, &environments[current_env_index].name);
+                    }
+                    if config.remember_username {
+                        greeter_state.last_username = Some(username.clone());
+                    }
+                    greeter_state.last_session = Some(environments[current_env_index].id.to_string());
+                    greeter_state.save();
+                    // The form isn't coming back, let go of what only it needed
+                    session_icons.iter_mut().for_each(|icon| *icon = None);
+                    face = None;
+                    brand_logo = None;
+                    sas_logo = None;
+                    animation = None;
+                    vid = None;
+                    qr_secret = None;
+                    match config.after_finish {
+                        AfterFinish::Exit => *control_flow = ControlFlow::Exit,
+                        AfterFinish::Splash => {
+                            finish_splash = Some("Resuming session…");
+                            exit_at = Some(Instant::now() + Duration::from_secs(2));
+                        }
+                    }
+                    display.window().request_redraw();
+                }
+                Response::Success if starting_session => {
                     *control_flow = ControlFlow::Exit;
                 }
                 Response::Success => {
-                    stream
-                        .start_session(&["/etc/ly/wsetup.sh", current_env.exec])
-                        .unwrap();
+                    if let Some(script) = &mut script {
+                        script.on_auth_result(&username, true);
+                    }
+                    #[cfg(feature = "plugins")]
+                    plugins.event(plugin::PluginEvent::AuthSuccess);
+                    starting_session = true;
+                    auth_message_type = None;
+                    secret::scrub(&mut password);
+                    if let Some(id) = &unlock_session {
+                        // Nothing to start, exit once greetd acknowledges the cancellation
+                        seat::activate(id);
+                        auth.cancel_session();
+                    } else {
+                        let env = &environments[current_env_index];
+                        // Only if it was looked up for this user, QR logins skip the form
+                        let locale = user_info
+                            .locale
+                            .as_deref()
+                            .filter(|_| face.as_ref().map_or(false, |(u, _)| *u == username));
+                        let a11y_env = if config.accessibility_env {
+                            greeter_state.accessibility_env(speech.enabled(), osk.is_some())
+                        } else {
+                            Vec::new()
+                        };
+                        // Started once the hook is done
+                        pending_start = Some(env.command(&config, locale, &a11y_env));
+                        if let Some(report) = crash_report.take().filter(|r| r.username == username)
+                        {
+                            modal = Some(crash_modal(report, greeter_state.palette.warning()));
+                            reading_report = true;
+                        } else {
+                            hooks::run(
+                                config.hooks.on_start_session.as_deref(),
+                                &username,
+                                Some(&env.name),
+                                Some(hook_proxy.clone()),
+                            );
+                        }
+                    }
+                    display.window().request_redraw();
+                }
+                Response::Error { .. }
+                    if config.hide_unknown_users
+                        && !starting_session
+                        && !fake_prompt
+                        && focused == FocusedField::Password
+                        && auth_message_type.is_none() =>
+                {
+                    // greetd refused the username before asking for anything,
+                    // pretend it asked for a password like it would for a real user
+                    fake_prompt = true;
+                    auth_message = "Password:".to_string();
+                    auth_message_type = Some(AuthMessageType::Secret);
+                    pending_focus = true;
+                    display.window().request_redraw();
                 }
                 Response::Error {
                     error_type,
                     description,
                 } => {
+                    let was_starting = std::mem::replace(&mut starting_session, false);
                     match error_type {
+                        ErrorType::Error if was_starting => {
+                            window_title =
+                                Cow::Owned(format!("Failed to start session: {}", description));
+                            if let Err(hint) = auth.create_session(&username) {
+                                username_hint = Some(hint);
+                            }
+                            focused = FocusedField::Password;
+                            pending_focus = true;
+                        }
                         ErrorType::Error => window_title = Cow::Owned(description),
                         ErrorType::AuthError => {
+                            if let Some(script) = &mut script {
+                                script.on_auth_result(&username, false);
+                            }
+                            if let Some(metrics) = &metrics {
+                                metrics.auth_failure();
+                            }
+                            #[cfg(feature = "plugins")]
+                            plugins.event(plugin::PluginEvent::AuthFailure);
+                            hooks::run(
+                                config.hooks.on_auth_failure.as_deref(),
+                                &username,
+                                Some(&environments[current_env_index].name),
+                                None,
+                            );
                             window_title = Cow::Borrowed("Login failed");
+                            login_failed = true;
+                            show_password = false;
+                            failed_at = Some(Instant::now()).filter(|_| !fail_delay.is_zero());
                             focused = FocusedField::Username;
                             pending_focus = true;
                             auth_message_type = None;
-                            username.clear();
-                            password.clear();
-
+                            secret::scrub(&mut password);
+                            if config.clear_username_on_failure {
+                                username.clear();
+                            }
                             if let Some(defaults) = command.value_of("username") {
-                                username = defaults.to_string();
-                                stream.create_session(&username).unwrap();
-                                focused = FocusedField::Password;
+                                if username.is_empty() {
+                                    username = defaults.to_string();
+                                }
+                            }
+
+                            if !username.is_empty() {
+                                match auth.create_session(&username) {
+                                    Ok(()) => {
+                                        hooks::run(
+                                            config.hooks.on_login.as_deref(),
+                                            &username,
+                                            Some(&environments[current_env_index].name),
+                                            None,
+                                        );
+                                        focused = FocusedField::Password;
+                                    }
+                                    // Refused accounts fail like unknown ones, over and over
+                                    Err(users::REFUSED) if config.hide_unknown_users => {
+                                        fake_prompt = true;
+                                        auth_message = "Password:".to_string();
+                                        auth_message_type = Some(AuthMessageType::Secret);
+                                        focused = FocusedField::Password;
+                                    }
+                                    Err(hint) => username_hint = Some(hint),
+                                }
                             }
                         }
                     }
@@ -324,35 +944,604 @@ fn main() {
                 }
             }
         }
+        if reading_report && modal.is_none() {
+            reading_report = false;
+            hooks::run(
+                config.hooks.on_start_session.as_deref(),
+                &username,
+                Some(&environments[current_env_index].name),
+                Some(hook_proxy.clone()),
+            );
+        }
         match event {
-            glutin::event::Event::LoopDestroyed => {
+            backend::event::Event::LoopDestroyed => {
                 crossterm::terminal::disable_raw_mode().unwrap();
                 egui_glow.destroy();
-                if let Some(v) = &mut vid {
-                    v.0.take();
+                if let Some(calibration) = &calibration {
+                    unsafe { calibration.destroy(&gl) };
+                }
+                if let Some(shader) = &shader {
+                    unsafe { shader.destroy(&gl) };
+                }
+                if let Some(magnifier) = &magnifier {
+                    unsafe { magnifier.destroy(&gl) };
                 }
                 vid.take();
             }
-            glutin::event::Event::RedrawRequested(_) => {
-                let needs_repaint = egui_glow.run(display.window(), |ctx| {
+            backend::event::Event::RedrawRequested(_) => {
+                let frame_started = Instant::now();
+                if let Some(at) = frame_interval
+                    .map(|interval| last_frame_at + interval)
+                    .filter(|at| *at > frame_started)
+                {
+                    capped_redraw_at = Some(at);
+                    if *control_flow != ControlFlow::Exit {
+                        *control_flow = ControlFlow::WaitUntil(at);
+                    }
+                    return;
+                }
+                last_frame_at = frame_started;
+                capped_redraw_at = None;
+                if blanked {
+                    unsafe {
+                        use glow::HasContext as _;
+                        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                        gl.clear(glow::COLOR_BUFFER_BIT);
+                        let [r, g, b] = config.plymouth.color;
+                        gl.clear_color(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+                    }
+                    display.swap_buffers().unwrap();
+                    // Timeouts and the clock still run with nothing shown
+                    if *control_flow != ControlFlow::Exit {
+                        let auth_deadline = auth_sent_at.map(|sent_at| sent_at + auth_timeout);
+                        *control_flow = match [
+                            Some(clock_tick),
+                            auth_deadline,
+                            exit_at,
+                            fake_failure_at,
+                            restart_at,
+                            locked_until,
+                            prewarm_at,
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .min()
+                        {
+                            Some(deadline) => ControlFlow::WaitUntil(deadline),
+                            None => ControlFlow::Wait,
+                        };
+                    }
+                    return;
+                }
+                if tty_input {
+                    locks = keyboard::tty_locks(0).unwrap_or_default();
+                }
+                if frame_started >= clock_tick {
+                    let (now, world_now) = format_clocks();
+                    clock = now;
+                    world_clocks = world_now;
+                    clock_tick = clock::next_tick(config.clock_seconds);
+                }
+                let curfew = config
+                    .curfew
+                    .as_ref()
+                    .filter(|curfew| curfew::active(curfew, offset));
+                if failed_at
+                    .map(|at| at.elapsed() >= fail_delay)
+                    .unwrap_or(false)
+                {
+                    failed_at = None;
+                    pending_focus = true;
+                }
+                logins_disabled = curfew.map(|c| c.disable_login).unwrap_or(false)
+                    || locked_until.is_some()
+                    || failed_at.is_some()
+                    || greetd_lost;
+
+                // Set from the accessibility menu, egui can't be rescaled mid-frame
+                let mut rescale_to: Option<f32> = None;
+                speech.title(&window_title);
+                if announced_env != current_env_index {
+                    announced_env = current_env_index;
+                    speech.say(&format!(
+                        "Session: {}, {} of {}",
+                        environments[current_env_index].name,
+                        current_env_index + 1,
+                        environments.len()
+                    ));
+                }
+                if config.hooks.on_session_selected.is_some()
+                    && selected_env != Some(current_env_index)
+                {
+                    selected_env = Some(current_env_index);
+                    prewarm_at = (!prewarmed.contains(&current_env_index))
+                        .then(|| Instant::now() + PREWARM_DELAY);
+                }
+                let ui_started = Instant::now();
+                let needs_repaint = frames.run(&mut egui_glow, display.window(), |ctx| {
+                    if let Some(vid) = &mut vid {
+                        vid.show(ctx);
+                    }
+                    if let Some(animation) = &mut animation {
+                        animation.show(ctx);
+                    }
+                    if config.background.dim > 0.0 {
+                        ctx.layer_painter(egui::LayerId::background()).rect_filled(
+                            ctx.input().screen_rect(),
+                            0.0,
+                            Color32::BLACK.linear_multiply(config.background.dim.min(1.0)),
+                        );
+                    }
+                    if let Some(splash) = finish_splash {
+                        egui::Area::new("finish_splash")
+                            .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+                            .show(ctx, |ui| {
+                                ui.label(RichText::new(splash).size(32.0).color(Color32::WHITE));
+                            });
+                        return;
+                    }
+                    if !attended {
+                        let sas = config.secure_attention.as_ref().unwrap();
+                        let logo = sas_logo.get_or_insert_with(|| {
+                            sas.logo.as_ref().and_then(|path| textures::load(ctx, path))
+                        });
+                        egui::Area::new("secure_attention")
+                            .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+                            .show(ctx, |ui| {
+                                ui.vertical_centered(|ui| {
+                                    if let Some(logo) = logo {
+                                        ui.image(&*logo, logo.size_vec2());
+                                    }
+                                    ui.label(RichText::new(&sas.message).size(24.0).color(overlay));
+                                });
+                            });
+                        return;
+                    }
+                    let failure = ctx.animate_bool_with_time(
+                        egui::Id::new("login_failed"),
+                        login_failed,
+                        0.5,
+                    );
+                    let shake = if login_failed && !greeter_state.reduce_motion {
+                        (failure * std::f32::consts::PI * 6.0).sin() * 12.0 * (1.0 - failure)
+                    } else {
+                        0.0
+                    };
+                    if failure >= 1.0 {
+                        login_failed = false;
+                    }
+
+                    let now = ctx.input().time;
+                    let fade = if faded_in {
+                        1.0
+                    } else {
+                        (((now - *fade_start.get_or_insert(now)) as f32) / config.fade_in.duration)
+                            .min(1.0)
+                    };
+                    if fade < 1.0 {
+                        ctx.set_visuals(theme::faded(&base_visuals, fade));
+                        if config.fade_in.background {
+                            ctx.layer_painter(egui::LayerId::background()).rect_filled(
+                                ctx.input().screen_rect(),
+                                0.0,
+                                Color32::BLACK.linear_multiply(1.0 - fade),
+                            );
+                        }
+                        ctx.request_repaint();
+                    } else if !faded_in {
+                        faded_in = true;
+                        ctx.set_visuals(base_visuals.clone());
+                    }
+
+                    #[cfg(feature = "plugins")]
+                    if !plugins.is_empty() {
+                        egui::Area::new("plugins")
+                            .anchor(Align2::LEFT_TOP, (8.0, 40.0))
+                            .show(ctx, |ui| plugins.show(ui, overlay.linear_multiply(fade)));
+                    }
+                    if let Some(script) = &mut script {
+                        script.on_frame(now);
+                        let lines = script.widget();
+                        if !lines.is_empty() {
+                            egui::Area::new("script_widget")
+                                .anchor(Align2::CENTER_BOTTOM, (0.0, -8.0))
+                                .show(ctx, |ui| {
+                                    ui.vertical_centered(|ui| {
+                                        for line in lines {
+                                            ui.label(
+                                                RichText::new(line)
+                                                    .color(overlay.linear_multiply(fade)),
+                                            );
+                                        }
+                                    });
+                                });
+                        }
+                    }
+
                     egui::Window::new("")
                         .title_bar(false)
                         .auto_sized()
                         .collapsible(false)
                         .anchor(Align2::RIGHT_TOP, (-5.0, 5.0))
                         .show(ctx, |ui| {
-                            ui.add(egui::Label::new(
-                                RichText::new(&clock).size(48.0).color(Color32::WHITE),
-                            ));
+                            // Clickable so screen readers can ask for the time
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(&clock)
+                                        .size(theme.fonts.clock_size)
+                                        .color(overlay.linear_multiply(fade)),
+                                )
+                                .sense(egui::Sense::click()),
+                            )
+                            .widget_info(|| {
+                                egui::WidgetInfo::labeled(
+                                    egui::WidgetType::Label,
+                                    format!("Time {}", clock),
+                                )
+                            });
+                            for (label, time) in &world_clocks {
+                                ui.label(
+                                    RichText::new(format!("{} {}", label, time))
+                                        .size(18.0)
+                                        .color(overlay.linear_multiply(fade)),
+                                );
+                            }
+                            if let Some(report) = &weather_report {
+                                ui.label(
+                                    RichText::new(report)
+                                        .size(18.0)
+                                        .color(overlay.linear_multiply(fade)),
+                                );
+                            }
+                        });
+                    if let Some(info_line) = &info_line {
+                        egui::Area::new("info_line")
+                            .anchor(Align2::RIGHT_BOTTOM, (-8.0, -8.0))
+                            .show(ctx, |ui| {
+                                ui.label(
+                                    RichText::new(info_line)
+                                        .small()
+                                        .color(overlay.linear_multiply(fade)),
+                                );
+                            });
+                    }
+                    if background_dir.is_some() {
+                        egui::Area::new("background_caption")
+                            .anchor(Align2::LEFT_BOTTOM, (8.0, -8.0))
+                            .show(ctx, |ui| {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("New background").clicked() {
+                                        reroll_background = true;
+                                    }
+                                    if let Some(caption) = &background_caption {
+                                        ui.label(
+                                            RichText::new(caption)
+                                                .small()
+                                                .color(overlay.linear_multiply(fade)),
+                                        );
+                                    }
+                                });
+                            });
+                    }
+                    egui::Area::new("power")
+                        .anchor(Align2::LEFT_TOP, (8.0, 8.0))
+                        .show(ctx, |ui| {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Power").clicked() {
+                                    let mut actions: Vec<_> =
+                                        [power::PowerAction::PowerOff, power::PowerAction::Reboot]
+                                            .into_iter()
+                                            .map(|action| {
+                                                (
+                                                    action.label().to_string(),
+                                                    DialogAction::Power(action),
+                                                )
+                                            })
+                                            .collect();
+                                    actions.push(("Cancel".to_string(), DialogAction::Close));
+                                    modal = Some(modal::Modal::new("Power", Vec::new(), actions));
+                                }
+                                ui.menu_button("Accessibility", |ui| {
+                                    for palette in palette::Palette::ALL {
+                                        if ui
+                                            .radio(
+                                                greeter_state.palette == palette,
+                                                palette.label(),
+                                            )
+                                            .clicked()
+                                        {
+                                            greeter_state.palette = palette;
+                                            greeter_state.save();
+                                            base_visuals =
+                                                visuals_for(&theme, background_accent, &greeter_state);
+                                            ctx.set_visuals(base_visuals.clone());
+                                        }
+                                    }
+                                    ui.separator();
+                                    if ui
+                                        .checkbox(&mut greeter_state.high_contrast, "High contrast")
+                                        .changed()
+                                    {
+                                        greeter_state.save();
+                                        base_visuals =
+                                            visuals_for(&theme, background_accent, &greeter_state);
+                                        ctx.set_visuals(base_visuals.clone());
+                                    }
+                                    if ui
+                                        .checkbox(&mut greeter_state.reduce_motion, "Reduce motion")
+                                        .changed()
+                                    {
+                                        greeter_state.save();
+                                        set_reduce_motion(ctx, greeter_state.reduce_motion);
+                                    }
+                                    let mut reading = speech.enabled();
+                                    if ui.checkbox(&mut reading, "Read aloud").changed() {
+                                        speech.toggle();
+                                        greeter_state.speech = Some(speech.enabled());
+                                        greeter_state.save();
+                                    }
+                                    let mut keys = osk.is_some();
+                                    if ui.checkbox(&mut keys, "On-screen keyboard").changed() {
+                                        let scale = config.on_screen_keyboard.touch_scale;
+                                        if keys {
+                                            osk::touch_style(ctx, scale);
+                                            osk = Some(osk::Osk::new(osk_proxy.clone()));
+                                        } else {
+                                            osk::touch_style(ctx, 1.0 / scale);
+                                            osk = None;
+                                        }
+                                        greeter_state.on_screen_keyboard = Some(keys);
+                                        greeter_state.save();
+                                    }
+                                    let mut lens = magnifier.is_some();
+                                    if ui.checkbox(&mut lens, "Magnifier").changed() {
+                                        unsafe {
+                                            magnifier::Magnifier::toggle(&mut magnifier, &gl)
+                                        };
+                                    }
+                                    ui.horizontal(|ui| {
+                                        ui.label("Text size");
+                                        if ui.small_button("−").clicked() {
+                                            rescale_to =
+                                                Some(greeter_state.ui_scale - UI_SCALE_STEP);
+                                        }
+                                        if ui.small_button("+").clicked() {
+                                            rescale_to =
+                                                Some(greeter_state.ui_scale + UI_SCALE_STEP);
+                                        }
+                                    });
+                                });
+                            });
                         });
-                    egui::Window::new(window_title.as_ref())
+                    let remote = vnc.as_ref().and_then(vnc::Server::status);
+                    if curfew.is_some()
+                        || broadcast_message.is_some()
+                        || config_error.is_some()
+                        || greetd_lost
+                        || remote.is_some()
+                    {
+                        egui::Area::new("banners")
+                            .anchor(Align2::CENTER_TOP, (0.0, 16.0))
+                            .show(ctx, |ui| {
+                                ui.vertical_centered(|ui| {
+                                    if let Some(error) = &config_error {
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "Invalid config, using the defaults\n{}",
+                                                error
+                                            ))
+                                            .color(
+                                                greeter_state.palette.error().linear_multiply(fade),
+                                            ),
+                                        );
+                                    }
+                                    if greetd_lost {
+                                        ui.label(
+                                            RichText::new("greetd is unavailable, the greeter is exiting")
+                                                .size(20.0)
+                                                .color(
+                                                    greeter_state
+                                                        .palette
+                                                        .error()
+                                                        .linear_multiply(fade),
+                                                ),
+                                        );
+                                    }
+                                    if let Some(curfew) = curfew {
+                                        ui.label(
+                                            RichText::new(bidi::visual(&curfew.message))
+                                                .size(20.0)
+                                                .color(
+                                                    greeter_state
+                                                        .palette
+                                                        .warning()
+                                                        .linear_multiply(fade),
+                                                ),
+                                        );
+                                    }
+                                    if let Some(message) = &broadcast_message {
+                                        ui.label(
+                                            RichText::new(bidi::visual(message))
+                                                .size(20.0)
+                                                .color(overlay.linear_multiply(fade)),
+                                        );
+                                    }
+                                    if let Some(remote) = remote {
+                                        ui.label(
+                                            RichText::new(remote).size(20.0).color(
+                                                greeter_state
+                                                    .palette
+                                                    .warning()
+                                                    .linear_multiply(fade),
+                                            ),
+                                        );
+                                    }
+                                });
+                            });
+                    }
+                    if let Some(footer) = &config.branding.footer {
+                        egui::Area::new("branding_footer")
+                            .anchor(Align2::LEFT_BOTTOM, (8.0, -8.0))
+                            .show(ctx, |ui| {
+                                ui.label(
+                                    RichText::new(footer)
+                                        .small()
+                                        .color(overlay.linear_multiply(fade)),
+                                );
+                            });
+                    }
+                    egui::Window::new(bidi::visual(&window_title))
+                        .enabled(!logins_disabled)
                         .auto_sized()
+                        .min_width(config.login_window.width)
+                        .resize(|r| r.max_size([config.login_window.width, f32::INFINITY]))
                         .collapsible(false)
-                        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+                        .anchor(Align2::CENTER_CENTER, (shake, 0.0))
                         .show(ctx, |ui| {
+                            let stale = focused == FocusedField::Password
+                                && face.as_ref().map_or(true, |(u, _)| u != &username);
+                            if let Some(info) =
+                                stale.then(|| user_data.lookup(&username)).flatten()
+                            {
+                                user_info = info;
+                                face = Some((
+                                    username.clone(),
+                                    user_info
+                                        .avatar
+                                        .as_ref()
+                                        .and_then(|path| textures::load(ctx, path)),
+                                ));
+                                // The session picked on the command line wins
+                                if let Some(index) = user_info
+                                    .session
+                                    .as_deref()
+                                    .filter(|_| command.value_of("session").is_none())
+                                    .and_then(|session| {
+                                        environments.iter().position(|env| env.is(session))
+                                    })
+                                {
+                                    current_env_index = index;
+                                }
+                            }
+                            let logo = brand_logo.get_or_insert_with(|| {
+                                config
+                                    .branding
+                                    .logo
+                                    .as_ref()
+                                    .and_then(|path| textures::load(ctx, path))
+                            });
+                            if let Some(logo) = logo {
+                                let size = logo.size_vec2();
+                                let scale = (64.0 / size.y).min(1.0);
+                                ui.vertical_centered(|ui| ui.image(&*logo, size * scale));
+                            }
+                            // Only real users have either, they'd give away which ones exist
+                            if let Some((_, Some(face))) =
+                                face.as_ref().filter(|_| !config.hide_unknown_users)
+                            {
+                                ui.vertical_centered(|ui| ui.image(face, [64.0, 64.0]));
+                            }
+                            if let Some(full_name) = user_info.full_name.as_deref().filter(|_| {
+                                focused == FocusedField::Password && !config.hide_unknown_users
+                            }) {
+                                ui.vertical_centered(|ui| ui.label(bidi::visual(full_name)));
+                            }
+
+                            if starting_session {
+                                if unlock_session.is_some() {
+                                    ui.label(format!("Switching to {}…", username));
+                                } else {
+                                    ui.label(format!(
+                                        "Starting {}…",
+                                        environments[current_env_index].name
+                                    ));
+                                }
+                                return;
+                            }
+
+                            if show_username_field && !seat_sessions.is_empty() {
+                                ui.label("Already logged in:");
+                                for session in &seat_sessions {
+                                    ui.horizontal(|ui| {
+                                        ui.label(session.describe());
+                                        if ui.button("Unlock").clicked() {
+                                            if let Err(hint) = auth.create_session(&session.user)
+                                            {
+                                                username_hint = Some(hint);
+                                                return;
+                                            }
+                                            username = session.user.clone();
+                                            unlock_session = Some(session.id.clone());
+                                            fake_prompt = false;
+                                            show_username_field = false;
+                                            hooks::run(
+                                                config.hooks.on_login.as_deref(),
+                                                &username,
+                                                Some(&environments[current_env_index].name),
+                                                None,
+                                            );
+                                            focused = FocusedField::Password;
+                                            pending_focus = true;
+                                        }
+                                    });
+                                }
+                                ui.separator();
+                            }
+
+                            if let Some(failed_at) = failed_at {
+                                let progress =
+                                    failed_at.elapsed().as_secs_f32() / fail_delay.as_secs_f32();
+                                ui.add(
+                                    egui::ProgressBar::new(progress.min(1.0))
+                                        .desired_width(config.login_window.width),
+                                );
+                                ctx.request_repaint();
+                            }
+                            if let Some(locked_until) = locked_until {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Account locked, try again in {}",
+                                        faillock::countdown(
+                                            locked_until.saturating_duration_since(Instant::now())
+                                        )
+                                    ))
+                                    .color(greeter_state.palette.warning()),
+                                );
+                            }
+
                             ui.horizontal(|ui| {
+                                if !show_username_field {
+                                    if unlock_session.is_some() {
+                                        ui.label(format!("Unlocking {}", username));
+                                    } else {
+                                        ui.label(format!("Logging in as {}", username));
+                                    }
+                                    let other_user = if unlock_session.is_some() {
+                                        "log in as different user…"
+                                    } else {
+                                        "other user…"
+                                    };
+                                    if ui.link(other_user).clicked() {
+                                        auth.cancel_session();
+                                        fake_prompt = false;
+                                        unlock_session = None;
+                                        show_username_field = true;
+                                        username.clear();
+                                        secret::scrub(&mut password);
+                                        auth_message_type = None;
+                                        focused = FocusedField::Username;
+                                        pending_focus = true;
+                                    }
+                                    return;
+                                }
                                 ui.label("Username: ");
-                                let resp = ui.text_edit_singleline(&mut username);
+                                let resp = ui.add(
+                                    TextEdit::singleline(&mut username)
+                                        .desired_width(config.login_window.field_width),
+                                );
+                                let max = config.input_limits.username;
+                                username_cut = secret::truncate(&mut username, max)
+                                    || (username_cut && username.chars().count() >= max);
                                 if pending_focus {
                                     if let FocusedField::Username = focused {
                                         ui.memory().request_focus(resp.id);
@@ -360,22 +1549,58 @@ fn main() {
                                     }
                                 }
                             });
+                            if let Some(hint) = username_hint {
+                                ui.label(
+                                    RichText::new(hint)
+                                        .small()
+                                        .color(greeter_state.palette.warning()),
+                                );
+                            }
+                            if username_cut && show_username_field {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Cut to {} characters",
+                                        config.input_limits.username
+                                    ))
+                                    .small()
+                                    .color(greeter_state.palette.warning()),
+                                );
+                            }
 
+                            let mut password_id = None;
                             ui.horizontal(|ui| {
                                 if auth_message_type.is_some() {
-                                    ui.label(&auth_message);
+                                    ui.label(bidi::visual(&auth_message));
                                 } else {
                                     return;
                                 }
+                                if failure > 0.0 {
+                                    let stroke = Stroke::new(
+                                        1.5,
+                                        greeter_state.palette.error().linear_multiply(failure),
+                                    );
+                                    let visuals = ui.visuals_mut();
+                                    visuals.selection.stroke = stroke;
+                                    visuals.widgets.inactive.bg_stroke = stroke;
+                                    visuals.widgets.hovered.bg_stroke = stroke;
+                                }
                                 let resp = match auth_message_type {
-                                    Some(AuthMessageType::Visible) => {
-                                        ui.add(TextEdit::singleline(&mut password))
-                                    }
-                                    Some(AuthMessageType::Secret) => {
-                                        ui.add(TextEdit::singleline(&mut password).password(true))
-                                    }
+                                    Some(AuthMessageType::Visible) => ui.add(
+                                        TextEdit::singleline(&mut password)
+                                            .desired_width(config.login_window.field_width),
+                                    ),
+                                    Some(AuthMessageType::Secret) => ui.add(
+                                        TextEdit::singleline(&mut password)
+                                            .password(!show_password)
+                                            .desired_width(config.login_window.field_width),
+                                    ),
                                     _ => return,
                                 };
+                                let max = config.input_limits.password;
+                                password_cut = secret::truncate(&mut password, max)
+                                    || (password_cut && password.chars().count() >= max);
+                                password_rect = Some(resp.rect);
+                                password_id = Some(resp.id);
                                 if pending_focus {
                                     if let FocusedField::Password = focused {
                                         ui.memory().request_focus(resp.id);
@@ -384,59 +1609,766 @@ fn main() {
                                 }
                             });
 
-                            ui.label(format!("Session: < {} >", current_env.name));
+                            let secret_prompt =
+                                matches!(auth_message_type, Some(AuthMessageType::Secret));
+                            let pattern_unlock = secret_prompt
+                                && user_info.pattern_unlock.unwrap_or(config.pattern_unlock);
+                            pin_pad = secret_prompt
+                                && !pattern_unlock
+                                && user_info.pin_pad.unwrap_or(config.pin_pad);
+                            if pattern_unlock {
+                                let drawn = ui
+                                    .vertical_centered(|ui| pattern.show(ui, 240.0, &mut password))
+                                    .inner;
+                                if drawn {
+                                    // Submitted the way Enter would
+                                    let _ = pad_proxy.send_event(UserEvent::Remote(
+                                        backend::event::WindowEvent::ReceivedCharacter('\r'),
+                                    ));
+                                }
+                            }
+                            if pin_pad {
+                                ui.vertical_centered(|ui| {
+                                    egui::Grid::new("pin_pad")
+                                        .spacing([8.0, 8.0])
+                                        .show(ui, |ui| {
+                                            for row in ["123", "456", "789", "\x7F0\r"] {
+                                                for key in row.chars() {
+                                                    let label = match key {
+                                                        '\x7F' => "Back".to_string(),
+                                                        '\r' => "OK".to_string(),
+                                                        digit => digit.to_string(),
+                                                    };
+                                                    let pressed = ui
+                                                        .add_sized(
+                                                            [64.0, 64.0],
+                                                            egui::Button::new(
+                                                                RichText::new(label).size(24.0),
+                                                            ),
+                                                        )
+                                                        .clicked();
+                                                    if pressed {
+                                                        // Typed like a keyboard would, into the
+                                                        // field the tap took focus from
+                                                        let _ = pad_proxy.send_event(
+                                                            UserEvent::Remote(
+                                                                backend::event::WindowEvent::ReceivedCharacter(key),
+                                                            ),
+                                                        );
+                                                        if let Some(id) = password_id {
+                                                            ui.memory().request_focus(id);
+                                                        }
+                                                    }
+                                                }
+                                                ui.end_row();
+                                            }
+                                        });
+                                });
+                            }
+                            if password_cut && auth_message_type.is_some() {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Cut to {} characters",
+                                        config.input_limits.password
+                                    ))
+                                    .small()
+                                    .color(greeter_state.palette.warning()),
+                                );
+                            }
+
+                            if let Some(url) = config
+                                .branding
+                                .support_url
+                                .as_ref()
+                                .filter(|_| window_title != default_title)
+                            {
+                                ui.label(RichText::new(format!("Need help? {}", url)).small());
+                            }
+
+                            if locks.caps
+                                && matches!(auth_message_type, Some(AuthMessageType::Secret))
+                            {
+                                ui.label(
+                                    RichText::new("Caps Lock is on")
+                                        .small()
+                                        .color(greeter_state.palette.warning()),
+                                );
+                            }
+
+                            egui::ComboBox::from_label("Session")
+                                .selected_text(bidi::visual(&environments[current_env_index].name))
+                                .show_ui(ui, |ui| {
+                                    let mut kind: Option<SessionKind> = None;
+                                    for (i, env) in environments.iter().enumerate() {
+                                        if kind != Some(env.kind) {
+                                            kind = Some(env.kind);
+                                            ui.label(RichText::new(env.kind.label()).strong());
+                                        }
+                                        ui.horizontal(|ui| {
+                                            if let Some(icon) = &session_icons[i] {
+                                                ui.image(icon, [16.0, 16.0]);
+                                            }
+                                            let resp = ui
+                                                .add_enabled_ui(env.installed, |ui| {
+                                                    ui.selectable_value(
+                                                        &mut current_env_index,
+                                                        i,
+                                                        bidi::visual(&env.name),
+                                                    )
+                                                })
+                                                .inner;
+                                            if !env.installed {
+                                                resp.on_disabled_hover_text(env.missing_hint());
+                                            } else if let Some(comment) = &env.comment {
+                                                resp.on_hover_text(comment.as_ref());
+                                            }
+                                        });
+                                    }
+                                });
+
+                            if let Some(qr_session) = &qr_session {
+                                ui.separator();
+                                ui.vertical_centered(|ui| {
+                                    ui.label("Or scan to log in");
+                                    qr_session.show(ui, 160.0);
+                                });
+                            }
                         });
+                    if let Some(pairing) = &pairing {
+                        let status = pairing.status();
+                        let connected = status.keyboards.iter().any(|k| k.connected);
+                        if status.available && !connected {
+                            egui::Window::new("Bluetooth keyboard")
+                                .collapsible(false)
+                                .resizable(false)
+                                .anchor(Align2::LEFT_BOTTOM, (8.0, -8.0))
+                                .show(ctx, |ui| {
+                                    if let Some(passkey) = &status.passkey {
+                                        ui.label("Type this on the keyboard, then Enter:");
+                                        ui.label(RichText::new(passkey).size(32.0).monospace());
+                                    }
+                                    for keyboard in &status.keyboards {
+                                        ui.horizontal(|ui| {
+                                            ui.label(bidi::visual(&keyboard.name));
+                                            let action =
+                                                if keyboard.paired { "Connect" } else { "Pair" };
+                                            if ui.button(action).clicked() {
+                                                pairing.pair(keyboard);
+                                            }
+                                        });
+                                    }
+                                    if status.keyboards.is_empty() {
+                                        ui.horizontal(|ui| {
+                                            ui.spinner();
+                                            ui.label("Looking for keyboards…");
+                                        });
+                                    }
+                                    if let Some(message) = &status.message {
+                                        ui.label(RichText::new(message).small());
+                                    }
+                                });
+                        }
+                    }
+                    // Dialogs have nothing to type into and keep focus themselves
+                    if let Some(osk) = osk
+                        .as_mut()
+                        .filter(|_| !starting_session && modal.is_none())
+                    {
+                        osk.show(ctx);
+                    }
+                    if let Some(outcome) = modal.as_mut().and_then(|m| m.show(ctx)) {
+                        modal = match outcome {
+                            modal::Outcome::Chosen(action) => picked(
+                                action,
+                                &seat_sessions,
+                                greeter_state.palette.warning(),
+                                replaying,
+                            ),
+                            modal::Outcome::Dismissed => None,
+                        };
+                    }
+
+                    speech.widget_events(&ctx.output().events);
+
+                    let caret = ctx.output().text_cursor_pos;
+                    if caret != text_cursor {
+                        text_cursor = caret;
+                        if let Some(caret) = caret {
+                            let ppp = ctx.pixels_per_point() as f64;
+                            lens_at = (caret.x as f64 * ppp, caret.y as f64 * ppp);
+                        }
+                    }
                 });
+                let ui_took = ui_started.elapsed();
+                if let Some(ui_scale) = rescale_to {
+                    rescale(
+                        &mut greeter_state,
+                        &mut egui_glow,
+                        display.window(),
+                        ui_scale,
+                    );
+                    greeter_state.save();
+                }
+
+                if reroll_background {
+                    reroll_background = false;
+                    if let Some(dir) = background_dir {
+                        if vid.is_some() {
+                            // The player stays, it's only handed another file
+                            background::reroll(
+                                dir.to_string(),
+                                config.background.caption,
+                                config.background.accent_from_image,
+                                background_proxy.clone(),
+                            );
+                        } else {
+                            // Animations are decoded up front, like at startup
+                            background::load(
+                                dir.to_string(),
+                                config.background.caption,
+                                config.background.accent_from_image,
+                                config.fleet.clone(),
+                                background_proxy.clone(),
+                            );
+                        }
+                    }
+                }
 
-                *control_flow = if needs_repaint {
+                // egui's repaints come through as redraw requests, so the loop
+                // only has to wake up on its own for the deadlines below
+                if needs_repaint {
                     display.window().request_redraw();
-                    ControlFlow::Poll
-                } else if *control_flow != ControlFlow::Exit {
-                    ControlFlow::Wait
+                }
+                *control_flow = if *control_flow != ControlFlow::Exit {
+                    let blank_deadline = blank_after.map(|after| input_at + after);
+                    let cursor_deadline = cursor_hide_after
+                        .filter(|_| cursor_visible)
+                        .map(|hide_after| cursor_moved_at + hide_after);
+                    let animation_deadline = animation
+                        .as_ref()
+                        .filter(|a| !a.paused)
+                        .map(Animation::next_frame_at);
+                    let auth_deadline = auth_sent_at.map(|sent_at| sent_at + auth_timeout);
+                    // Lock keys produce no input on the TTY, so poll for Caps Lock
+                    // while a password is being typed
+                    let caps_deadline = (tty_input
+                        && matches!(auth_message_type, Some(AuthMessageType::Secret)))
+                    .then(|| Instant::now() + Duration::from_millis(500));
+                    #[cfg(feature = "plugins")]
+                    let plugin_deadline = plugins.next_deadline();
+                    #[cfg(not(feature = "plugins"))]
+                    let plugin_deadline: Option<Instant> = None;
+                    // Tick on the second boundaries of the lockout countdown
+                    let lockout_deadline = locked_until.map(|until| {
+                        let left = until.saturating_duration_since(Instant::now());
+                        until - Duration::from_secs(left.as_secs())
+                    });
+                    match cursor_deadline
+                        .into_iter()
+                        .chain(Some(clock_tick))
+                        .chain(animation_deadline)
+                        .chain(auth_deadline)
+                        .chain(exit_at)
+                        .chain(plugin_deadline)
+                        .chain(fake_failure_at)
+                        .chain(restart_at)
+                        .chain(caps_deadline)
+                        .chain(lockout_deadline)
+                        .chain(prewarm_at)
+                        .chain(blank_deadline)
+                        .min()
+                    {
+                        Some(deadline) => ControlFlow::WaitUntil(deadline),
+                        None => ControlFlow::Wait,
+                    }
                 } else {
                     ControlFlow::Exit
                 };
 
-                {
+                // A benchmark measures every frame in full
+                if frame_bench.is_some() {
+                    frames.invalidate();
+                }
+                // Over a static background an unchanged frame is left on screen
+                // as it is, rather than cleared, redrawn and swapped again
+                let idle = vid.is_none()
+                    && shader.is_none()
+                    && magnifier.is_none()
+                    && frames.unchanged(&egui_glow, display.window());
+                if !idle {
                     unsafe {
                         use glow::HasContext as _;
                         gl.clear(glow::COLOR_BUFFER_BIT);
                     }
 
-                    if let Some(vi) = &vid {
-                        if let Some(render_context) = &vi.0 {
-                            render_context
-                                .render::<ContextWrapper<PossiblyCurrent, Window>>(
-                                    0,
-                                    size.width as _,
-                                    size.height as _,
-                                    true,
-                                )
-                                .expect("Failed to draw on glutin window");
-                        }
+                    if let Some(vid) = &vid {
+                        vid.render(size.width, size.height);
                     }
 
-                    egui_glow.paint(display.window());
+                    if let Some(shader) = &shader {
+                        unsafe { shader.paint(&gl, size.width, size.height) };
+                        display.window().request_redraw();
+                    }
+
+                    frames.paint(&mut egui_glow, display.window());
+                    if let Some(magnifier) = &magnifier {
+                        unsafe {
+                            magnifier.paint(
+                                &gl,
+                                lens_at,
+                                config.magnifier.zoom,
+                                config.magnifier.size,
+                                size.width,
+                                size.height,
+                            )
+                        };
+                    }
+
+                    if let Some(calibration) = &calibration {
+                        unsafe { calibration.paint(&gl, size.width, size.height) };
+                    }
+                    if let Some(path) = screenshot_to.take() {
+                        unsafe { screenshot::capture(&gl, size.width, size.height, path) };
+                    }
+                    if let Some(vnc) = &vnc {
+                        unsafe { vnc.painted(&gl, size.width, size.height) };
+                    }
 
                     display.swap_buffers().unwrap();
+                    if let Some(metrics) = &metrics {
+                        metrics.frame(frame_started.elapsed());
+                    }
+
+                    if let Some(trace) = trace.take() {
+                        trace.finish();
+                    } else if let Some(bench) = &mut frame_bench {
+                        if bench.frame(ui_took, frame_started.elapsed()) {
+                            frame_bench.take().unwrap().finish();
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    if frame_bench.is_some() {
+                        display.window().request_redraw();
+                    }
+                    if plymouth_pending {
+                        plymouth_pending = false;
+                        plymouth::quit(config.plymouth.retain_splash);
+                    }
+                    if let Some(background) = pending_background.take() {
+                        background::load(
+                            background.to_string(),
+                            config.background.caption,
+                            config.background.accent_from_image,
+                            config.fleet.clone(),
+                            background_proxy.clone(),
+                        );
+                    }
+                }
+
+                // Recordings are timed by frames, not by the clock
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.frame();
+                }
+                if player.as_mut().map_or(false, replay::Player::frame) {
+                    display.window().request_redraw();
+                }
+            }
+            backend::event::Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                if exit_at.map(|at| Instant::now() >= at).unwrap_or(false) {
+                    *control_flow = ControlFlow::Exit;
+                }
+                if !blanked && blank_after.map_or(false, |after| input_at.elapsed() >= after) {
+                    blanked = true;
+                    if let Some(vid) = &vid {
+                        vid.set_paused(true);
+                    }
+                    display.window().request_redraw();
+                }
+                if let Some(hide_after) = cursor_hide_after {
+                    if cursor_visible && cursor_moved_at.elapsed() >= hide_after {
+                        cursor_visible = false;
+                        display.window().set_cursor_visible(false);
+                    }
+                }
+                if animation.as_ref().map(|a| !a.paused).unwrap_or(false) {
+                    display.window().request_redraw();
+                }
+                if Instant::now() >= clock_tick {
+                    display.window().request_redraw();
+                }
+                if prewarm_at.map(|at| Instant::now() >= at).unwrap_or(false) {
+                    prewarm_at = None;
+                    prewarmed.push(current_env_index);
+                    hooks::run(
+                        config.hooks.on_session_selected.as_deref(),
+                        &username,
+                        Some(&environments[current_env_index].name),
+                        None,
+                    );
+                }
+                if capped_redraw_at
+                    .map(|at| Instant::now() >= at)
+                    .unwrap_or(false)
+                {
+                    capped_redraw_at = None;
+                    display.window().request_redraw();
+                }
+                if restart_at.map(|at| Instant::now() >= at).unwrap_or(false) {
+                    eprintln!("Failed to restart the greeter: {}", restart());
+                    *control_flow = ControlFlow::Exit;
+                }
+                if tty_input && keyboard::tty_locks(0).unwrap_or_default() != locks {
+                    display.window().request_redraw();
+                }
+                if fake_failure_at
+                    .map(|at| Instant::now() >= at)
+                    .unwrap_or(false)
+                {
+                    fake_failure_at = None;
+                    fake_prompt = false;
+                    // Fails exactly like a wrong password would, past the
+                    // recorder and the conversation with greetd
+                    fake_failure = true;
+                    display.window().request_redraw();
+                }
+                #[cfg(feature = "plugins")]
+                if plugins.tick() {
+                    display.window().request_redraw();
+                }
+                if let Some(until) = locked_until {
+                    if Instant::now() >= until {
+                        locked_until = None;
+                        pending_focus = true;
+                    }
+                    display.window().request_redraw();
+                }
+                if auth_sent_at
+                    .map(|sent_at| sent_at.elapsed() >= auth_timeout)
+                    .unwrap_or(false)
+                {
+                    // greetd stopped answering, anything more sent on this
+                    // connection would queue up behind the hung request.
+                    // Start over on a new one.
+                    eprintln!("greetd didn't answer within {:?}", auth_timeout);
+                    auth_sent_at = None;
+                    auth.abandon();
+                    secret::scrub(&mut password);
+                    window_title = Cow::Borrowed("Authentication timed out, restarting");
+                    restart_at = Some(Instant::now());
+                    display.window().request_redraw();
                 }
             }
-            glutin::event::Event::UserEvent(_) => {
+            backend::event::Event::UserEvent(UserEvent::OnBattery(battery)) => {
+                on_battery = battery;
+                if let Some(vid) = &vid {
+                    vid.set_paused(on_battery);
+                }
+                if let Some(animation) = &mut animation {
+                    animation.paused = on_battery;
+                }
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::Weather(report)) => {
+                weather_report = Some(report);
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::Broadcast(message)) => {
+                broadcast_message = message;
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::QrApproved(approval)) => {
+                // Each token approves one login, used or not
+                qr_session = config
+                    .qr_login
+                    .as_ref()
+                    .and_then(|qr| qr_login::QrSession::start(qr, qr_proxy.clone()));
+                display.window().request_redraw();
+                if logins_disabled {
+                    eprintln!("Ignoring a QR login while logins are disabled");
+                    return;
+                }
+                if let Err(hint) = auth.create_session(&approval.username) {
+                    username_hint = Some(hint);
+                    display.window().request_redraw();
+                    return;
+                }
+                username = approval.username;
+                unlock_session = None;
+                fake_prompt = false;
+                qr_secret = Some(approval.secret);
+                hooks::run(
+                    config.hooks.on_login.as_deref(),
+                    &username,
+                    Some(&environments[current_env_index].name),
+                    None,
+                );
+                focused = FocusedField::Password;
+                display.window().request_redraw();
+            }
+            // Don't leave a half-typed password around when the user walks away
+            backend::event::Event::UserEvent(UserEvent::PrepareForSleep)
+            | backend::event::Event::Suspended
+            | backend::event::Event::WindowEvent {
+                event: backend::event::WindowEvent::Focused(false),
+                ..
+            } => {
+                secret::scrub(&mut password);
+                egui_glow.on_event(&backend::event::WindowEvent::Focused(false));
                 display.window().request_redraw();
             }
-            glutin::event::Event::WindowEvent { event, .. } => {
-                use glutin::event::WindowEvent;
+            backend::event::Event::UserEvent(UserEvent::GreetdLost) => {
+                // Let go of the VT and DRM master for the greeter a restarted
+                // greetd starts
+                eprintln!("greetd hung up, exiting");
+                greetd_lost = true;
+                exit_at = Some(Instant::now() + GREETD_LOST_EXIT);
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::Wake) => {
+                input_at = Instant::now();
+                if blanked {
+                    blanked = false;
+                    if let Some(vid) = &vid {
+                        vid.set_paused(on_battery);
+                    }
+                    frames.invalidate();
+                    display.window().request_redraw();
+                }
+            }
+            backend::event::Event::UserEvent(UserEvent::Resumed) => {
+                // Drivers don't always bring mpv's render context through a
+                // suspend, so start over with a new one
+                if let (Some(_), Some((path, is_image))) = (&vid, &vid_source) {
+                    vid = None;
+                    vid = Video::new(
+                        path,
+                        *is_image,
+                        &config,
+                        &display,
+                        target,
+                        &background_proxy,
+                    );
+                    if let Some(vid) = &vid {
+                        vid.set_paused(on_battery);
+                    }
+                }
+                // Whatever woke the machine, local or remote, someone's likely
+                // about to log in
+                input_at = Instant::now();
+                if blanked {
+                    blanked = false;
+                    if let Some(vid) = &vid {
+                        vid.set_paused(on_battery);
+                    }
+                }
+                frames.invalidate();
+                // Monotonic time stood still while asleep, so the tick that
+                // was due is off by however long that was
+                let (now, world_now) = format_clocks();
+                clock = now;
+                world_clocks = world_now;
+                clock_tick = clock::next_tick(config.clock_seconds);
+                // Keys and fingers down as the lid closed never come back up
+                modifiers = ModifiersState::empty();
+                egui_glow.on_event(&backend::event::WindowEvent::ModifiersChanged(modifiers));
+                egui_glow.on_event(&backend::event::WindowEvent::CursorLeft {
+                    device_id: unsafe { DeviceId::dummy() },
+                });
+                gestures = gesture::Tracker::default();
+                escape_at = None;
+                if tty_input {
+                    locks = keyboard::tty_locks(0).unwrap_or_default();
+                }
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::BackgroundLoaded(loaded)) => {
+                if loaded.random {
+                    background_caption = loaded.caption;
+                    // The video backend picks rerolls itself, unchecked
+                    background_dir = background_arg.filter(|_| config.fleet.is_none());
+                }
+                // A reroll may go from one kind of background to the other
+                match loaded.media {
+                    background::Media::Animation(anim) => {
+                        vid = None;
+                        vid_source = None;
+                        animation = Some(anim);
+                    }
+                    background::Media::Video { is_image } => {
+                        animation = None;
+                        vid_source = Some((loaded.path.clone(), is_image));
+                        vid = Video::new(
+                            &loaded.path,
+                            is_image,
+                            &config,
+                            &display,
+                            target,
+                            &background_proxy,
+                        );
+                    }
+                }
+                if config.background.pause_on_battery
+                    && !watching_battery
+                    && (vid.is_some() || animation.is_some())
+                {
+                    watching_battery = true;
+                    power::watch_battery(background_proxy.clone());
+                }
+                // Rerolled on battery
+                if on_battery {
+                    if let Some(vid) = &vid {
+                        vid.set_paused(true);
+                    }
+                    if let Some(animation) = &mut animation {
+                        animation.paused = true;
+                    }
+                }
+                if loaded.accent.is_some() {
+                    background_accent = loaded.accent;
+                    base_visuals = visuals_for(&theme, background_accent, &greeter_state);
+                    if faded_in {
+                        egui_glow.egui_ctx.set_visuals(base_visuals.clone());
+                    }
+                }
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::BackgroundRerolled(rerolled)) => {
+                if let Some(vid) = &mut vid {
+                    vid.play(&rerolled.path, rerolled.is_image);
+                    vid_source = Some((rerolled.path, rerolled.is_image));
+                    background_caption = rerolled.caption;
+                    if rerolled.accent.is_some() {
+                        background_accent = rerolled.accent;
+                        base_visuals = visuals_for(&theme, background_accent, &greeter_state);
+                        if faded_in {
+                            egui_glow.egui_ctx.set_visuals(base_visuals.clone());
+                        }
+                    }
+                    display.window().request_redraw();
+                }
+            }
+            backend::event::Event::UserEvent(UserEvent::HookFinished) => {
+                if let Some(command) = pending_start.take() {
+                    auth.start_session(command);
+                }
+            }
+            backend::event::Event::UserEvent(UserEvent::ThemeChanged) => {
+                if let Some(reloaded) = config.theme.as_deref().and_then(load_theme) {
+                    theme = reloaded;
+                    theme.apply(&egui_glow.egui_ctx, &font_fallbacks);
+                    overlay = theme.overlay_color();
+                    base_visuals = visuals_for(&theme, background_accent, &greeter_state);
+                    if faded_in {
+                        egui_glow.egui_ctx.set_visuals(base_visuals.clone());
+                    }
+                    display.window().request_redraw();
+                }
+            }
+            backend::event::Event::UserEvent(UserEvent::Screenshot(path)) => {
+                screenshot_to = Some(path);
+                frames.invalidate();
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::Repaint) => {
+                frames.invalidate();
+                display.window().request_redraw();
+            }
+            backend::event::Event::UserEvent(UserEvent::Redraw) => {
+                if let Some(vid) = &mut vid {
+                    vid.poll_events();
+                }
+                display.window().request_redraw();
+            }
+            backend::event::Event::WindowEvent { event, .. } => {
+                use backend::event::WindowEvent;
+                if matches!(
+                    event,
+                    WindowEvent::KeyboardInput { .. }
+                        | WindowEvent::ReceivedCharacter(_)
+                        | WindowEvent::MouseInput { .. }
+                        | WindowEvent::MouseWheel { .. }
+                        | WindowEvent::CursorMoved { .. }
+                        | WindowEvent::Touch(_)
+                ) {
+                    input_at = Instant::now();
+                    // The input that wakes the screen goes no further
+                    if blanked {
+                        blanked = false;
+                        if let Some(vid) = &vid {
+                            vid.set_paused(on_battery);
+                        }
+                        frames.invalidate();
+                        display.window().request_redraw();
+                        return;
+                    }
+                }
+                if let WindowEvent::CursorMoved { position, .. } = event {
+                    lens_at = (position.x, position.y);
+                    cursor_moved_at = Instant::now();
+                    if !cursor_visible {
+                        cursor_visible = true;
+                        display.window().set_cursor_visible(true);
+                    }
+                }
                 if matches!(event, WindowEvent::CloseRequested | WindowEvent::Destroyed) {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    *control_flow = backend::event_loop::ControlFlow::Exit;
 
                     egui_glow.on_event(&event);
                 }
 
-                if let glutin::event::WindowEvent::Resized(physical_size) = &event {
+                if let WindowEvent::ModifiersChanged(state) = event {
+                    modifiers = state;
+                }
+                if let WindowEvent::Touch(touch) = &event {
+                    match gestures.touch(touch, egui_glow.egui_ctx.pixels_per_point()) {
+                        // Long-press the password field to peek at what was typed
+                        Some(gesture::Gesture::LongPress(pos))
+                            if password_rect.map(|r| r.contains(pos)).unwrap_or(false) =>
+                        {
+                            show_password = !show_password;
+                        }
+                        Some(gesture::Gesture::LongPress(_)) => {}
+                        Some(gesture::Gesture::SwipeLeft) => {
+                            current_env_index = (current_env_index + 1) % environments.len();
+                        }
+                        Some(gesture::Gesture::SwipeRight) => {
+                            current_env_index = current_env_index
+                                .checked_sub(1)
+                                .unwrap_or(environments.len() - 1);
+                        }
+                        Some(gesture::Gesture::Pinch(ratio)) => {
+                            let ui_scale = greeter_state.ui_scale * ratio;
+                            rescale(
+                                &mut greeter_state,
+                                &mut egui_glow,
+                                display.window(),
+                                ui_scale,
+                            );
+                        }
+                        // Saved once the fingers are lifted, not at every step
+                        Some(gesture::Gesture::PinchEnded) => greeter_state.save(),
+                        None => {}
+                    }
+                }
+                if !attended
+                    && matches!(
+                        event,
+                        WindowEvent::KeyboardInput { .. }
+                            | WindowEvent::ReceivedCharacter(_)
+                            | WindowEvent::MouseInput { .. }
+                    )
+                {
+                    // Swallow all input until the chord is pressed
+                    attended = sas_chord
+                        .as_ref()
+                        .unwrap()
+                        .pressed(&event, modifiers, tty_input);
+                    pending_focus = true;
+                } else if let backend::event::WindowEvent::Resized(physical_size) = &event {
                     size = *physical_size;
                     display.resize(*physical_size);
                     egui_glow.on_event(&event);
-                } else if let glutin::event::WindowEvent::ScaleFactorChanged {
+                } else if let backend::event::WindowEvent::ScaleFactorChanged {
                     new_inner_size,
                     ..
                 } = &event
@@ -444,74 +2376,235 @@ fn main() {
                     size = **new_inner_size;
                     display.resize(**new_inner_size);
                     egui_glow.on_event(&event);
-                } else if let glutin::event::WindowEvent::ReceivedCharacter(c) = event {
+                    set_ui_scale(&mut egui_glow, display.window(), greeter_state.ui_scale);
+                } else if let (
+                    true,
+                    WindowEvent::KeyboardInput {
+                        input:
+                            backend::event::KeyboardInput {
+                                state: backend::event::ElementState::Pressed,
+                                virtual_keycode:
+                                    Some(
+                                        key @ (VirtualKeyCode::Equals
+                                        | VirtualKeyCode::Plus
+                                        | VirtualKeyCode::NumpadAdd
+                                        | VirtualKeyCode::Minus
+                                        | VirtualKeyCode::NumpadSubtract
+                                        | VirtualKeyCode::Key0
+                                        | VirtualKeyCode::Numpad0),
+                                    ),
+                                ..
+                            },
+                        ..
+                    },
+                ) = (modifiers.ctrl(), &event)
+                {
+                    let ui_scale = match key {
+                        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                            greeter_state.ui_scale - UI_SCALE_STEP
+                        }
+                        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => 1.0,
+                        _ => greeter_state.ui_scale + UI_SCALE_STEP,
+                    };
+                    rescale(
+                        &mut greeter_state,
+                        &mut egui_glow,
+                        display.window(),
+                        ui_scale,
+                    );
+                    greeter_state.save();
+                } else if magnifier_chord
+                    .as_ref()
+                    .map(|chord| chord.pressed(&event, modifiers, tty_input))
+                    .unwrap_or(false)
+                {
+                    unsafe { magnifier::Magnifier::toggle(&mut magnifier, &gl) };
+                    // The lens is still in the painted frame
+                    frames.invalidate();
+                } else if screenshot_chord
+                    .as_ref()
+                    .map(|chord| chord.pressed(&event, modifiers, tty_input))
+                    .unwrap_or(false)
+                {
+                    screenshot_to = Some(screenshot::path());
+                    frames.invalidate();
+                } else if speech_chord
+                    .as_ref()
+                    .map(|chord| chord.pressed(&event, modifiers, tty_input))
+                    .unwrap_or(false)
+                {
+                    speech.toggle();
+                    greeter_state.speech = Some(speech.enabled());
+                    greeter_state.save();
+                } else if let (Some(open), true) = (
+                    &mut modal,
+                    matches!(
+                        event,
+                        WindowEvent::ReceivedCharacter(_) | WindowEvent::KeyboardInput { .. }
+                    ),
+                ) {
+                    if let Some(outcome) = modal_key(&event, modifiers).and_then(|key| open.key(key)) {
+                        modal = match outcome {
+                            modal::Outcome::Chosen(action) => picked(
+                                action,
+                                &seat_sessions,
+                                greeter_state.palette.warning(),
+                                replaying,
+                            ),
+                            modal::Outcome::Dismissed => None,
+                        };
+                    }
+                    display.window().request_redraw();
+                } else if let (Some(keys), true) = (
+                    osk.as_mut()
+                        .filter(|keys| !starting_session && !keys.focused()),
+                    matches!(
+                        event,
+                        WindowEvent::KeyboardInput {
+                            input: backend::event::KeyboardInput {
+                                state: backend::event::ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Down),
+                                ..
+                            },
+                            ..
+                        }
+                    ),
+                ) {
+                    // Down from the form moves into the on-screen keyboard
+                    keys.enter();
+                    display.window().request_redraw();
+                } else if let Some(keys) = osk.as_mut().filter(|keys| keys.navigating(&event)) {
+                    if modal_key(&event, modifiers)
+                        .map(|key| keys.key(key))
+                        .unwrap_or(false)
+                    {
+                        pending_focus = true;
+                    }
+                    display.window().request_redraw();
+                } else if let backend::event::WindowEvent::ReceivedCharacter(c) = event {
+                    // Arrow keys on the TTY start with an escape too, but it's
+                    // never directly followed by another one
+                    if c != '\x1b' {
+                        escape_at = None;
+                    }
                     match c {
+                        '\x1b' if !escape_window.is_zero() => {
+                            if escape_at
+                                .map(|at| at.elapsed() <= escape_window)
+                                .unwrap_or(false)
+                            {
+                                // Reset everything, waiting a moment for greetd to
+                                // drop the session first
+                                escape_at = None;
+                                restart_at = Some(Instant::now() + Duration::from_secs(2));
+                                auth.cancel_session();
+                            } else {
+                                escape_at = Some(Instant::now());
+                            }
+                        }
+                        '\r' if logins_disabled => {}
                         '\r' => match focused {
                             FocusedField::Password => {
                                 if username.is_empty() {
                                     focused = FocusedField::Username;
-                                } else {
-                                    stream.authentication_response(Some(&password)).unwrap();
+                                } else if fake_prompt {
+                                    fake_failure_at = Some(Instant::now() + UNKNOWN_USER_DELAY);
+                                } else if !environments[current_env_index].installed {
+                                    window_title =
+                                        Cow::Owned(environments[current_env_index].missing_hint());
+                                } else if auth.authentication_response(Some(&password)) {
+                                    if let Some(metrics) = &metrics {
+                                        metrics.auth_attempt();
+                                    }
+                                    auth_sent_at = Some(Instant::now());
                                 }
                                 pending_focus = true;
                             }
                             FocusedField::Username => {
-                                stream.create_session(&username).unwrap();
-                                focused = FocusedField::Password;
+                                fake_prompt = false;
+                                match users::normalize(&username, &config.username_policy) {
+                                    Ok((normalized, hint)) => {
+                                        username = normalized;
+                                        username_hint = hint;
+                                        match auth.create_session(&username) {
+                                            Ok(()) => {
+                                                hooks::run(
+                                                    config.hooks.on_login.as_deref(),
+                                                    &username,
+                                                    Some(&environments[current_env_index].name),
+                                                    None,
+                                                );
+                                                focused = FocusedField::Password;
+                                            }
+                                            Err(hint) => username_hint = Some(hint),
+                                        }
+                                    }
+                                    // Refusing the account straight away would
+                                    // tell it exists, it gets the unknown
+                                    // users' made up prompt instead
+                                    Err(users::REFUSED) if config.hide_unknown_users => {
+                                        fake_prompt = true;
+                                        auth_message = "Password:".to_string();
+                                        auth_message_type = Some(AuthMessageType::Secret);
+                                        focused = FocusedField::Password;
+                                    }
+                                    Err(hint) => username_hint = Some(hint),
+                                }
                                 pending_focus = true;
                             }
                         },
+                        // Ctrl+R
+                        '\x12' => {
+                            reroll_background = true;
+                        }
                         '\t' => {
                             match focused {
                                 FocusedField::Username => focused = FocusedField::Password,
-                                FocusedField::Password => focused = FocusedField::Username,
+                                FocusedField::Password if show_username_field => {
+                                    focused = FocusedField::Username
+                                }
+                                FocusedField::Password => {}
                             }
                             pending_focus = true;
                         }
                         '>' => {
-                            current_env_index += 1;
-                            if let Some(env) = environments.get(current_env_index) {
-                                current_env = env
-                            } else {
-                                current_env_index = 0;
-                                current_env = &environments[current_env_index];
-                            }
+                            current_env_index = (current_env_index + 1) % environments.len();
                         }
                         '<' => {
-                            current_env_index -= 1;
-                            if let Some(env) = environments.get(current_env_index) {
-                                current_env = env
-                            } else {
-                                current_env_index = environments.len() - 1;
-                                current_env = &environments[current_env_index];
-                            }
+                            current_env_index = current_env_index
+                                .checked_sub(1)
+                                .unwrap_or(environments.len() - 1);
                         }
                         '\x7F' => {
                             #[allow(deprecated)]
-                            egui_glow.on_event(&glutin::event::WindowEvent::KeyboardInput {
+                            egui_glow.on_event(&backend::event::WindowEvent::KeyboardInput {
                                 device_id: unsafe { DeviceId::dummy() },
-                                input: glutin::event::KeyboardInput {
+                                input: backend::event::KeyboardInput {
                                     scancode: b'\x7F' as u32,
-                                    state: glutin::event::ElementState::Pressed,
+                                    state: backend::event::ElementState::Pressed,
                                     virtual_keycode: Some(VirtualKeyCode::Back),
                                     modifiers: ModifiersState::empty(),
                                 },
                                 is_synthetic: false,
                             });
                             #[allow(deprecated)]
-                            egui_glow.on_event(&glutin::event::WindowEvent::KeyboardInput {
+                            egui_glow.on_event(&backend::event::WindowEvent::KeyboardInput {
                                 device_id: unsafe { DeviceId::dummy() },
-                                input: glutin::event::KeyboardInput {
+                                input: backend::event::KeyboardInput {
                                     scancode: b'\x7F' as u32,
-                                    state: glutin::event::ElementState::Released,
+                                    state: backend::event::ElementState::Released,
                                     virtual_keycode: Some(VirtualKeyCode::Back),
                                     modifiers: ModifiersState::empty(),
                                 },
                                 is_synthetic: false,
                             });
                         }
+                        // Only digits go into a PIN
+                        c if pin_pad
+                            && focused == FocusedField::Password
+                            && !c.is_ascii_digit() => {}
                         c => {
-                            egui_glow.on_event(&glutin::event::WindowEvent::ReceivedCharacter(c));
+                            egui_glow.on_event(&backend::event::WindowEvent::ReceivedCharacter(c));
                         }
                     }
                 } else {
@@ -524,8 +2617,147 @@ fn main() {
     });
 }
 
+/// What the buttons of the open dialog do
+#[derive(Clone, Copy)]
+enum DialogAction {
+    /// Picked from the power menu, asked again if sessions would be lost
+    Power(power::PowerAction),
+    /// Confirmed, or nothing to lose
+    PowerConfirmed(power::PowerAction),
+    Close,
+}
+
+/// Does what was picked in a dialog, returning the dialog to show next
+fn picked(
+    action: DialogAction,
+    seat_sessions: &[seat::SeatSession],
+    warning: Color32,
+    replaying: bool,
+) -> Option<modal::Modal<DialogAction>> {
+    match action {
+        DialogAction::Power(action) if !seat_sessions.is_empty() => {
+            let mut body =
+                vec![RichText::new("Unsaved work in these sessions will be lost:").color(warning)];
+            body.extend(seat_sessions.iter().map(|s| RichText::new(s.describe())));
+            Some(modal::Modal::new(
+                action.label(),
+                body,
+                vec![
+                    (
+                        action.label().to_string(),
+                        DialogAction::PowerConfirmed(action),
+                    ),
+                    ("Cancel".to_string(), DialogAction::Close),
+                ],
+            ))
+        }
+        DialogAction::Power(action) | DialogAction::PowerConfirmed(action) => {
+            if !replaying {
+                action.run();
+            }
+            None
+        }
+        DialogAction::Close => None,
+    }
+}
+
+/// Tells `bounce`'s user how their last session ended
+fn crash_modal(bounce: bounce::Bounce, warning: Color32) -> modal::Modal<DialogAction> {
+    let mut body = vec![RichText::new(format!(
+        "{} for {} {}.",
+        bounce.session, bounce.username, bounce.status
+    ))
+    .color(warning)];
+    if bounce.log.is_empty() {
+        body.push(RichText::new(
+            "Nothing was logged, or the greeter isn't allowed to read the journal.",
+        ));
+    } else {
+        body.push(RichText::new("Last messages:"));
+        body.extend(
+            bounce
+                .log
+                .iter()
+                .map(|line| RichText::new(line).monospace()),
+        );
+    }
+    modal::Modal::new(
+        "Your session crashed",
+        body,
+        vec![("OK".to_string(), DialogAction::Close)],
+    )
+}
+
+/// The dialog or on-screen keyboard key `event` stands for, from either
+/// backend
+fn modal_key(event: &backend::event::WindowEvent, modifiers: ModifiersState) -> Option<modal::Key> {
+    use backend::event::{ElementState, KeyboardInput, WindowEvent};
+    match event {
+        WindowEvent::ReceivedCharacter('\t') if modifiers.shift() => Some(modal::Key::Previous),
+        WindowEvent::ReceivedCharacter('\t') => Some(modal::Key::Next),
+        WindowEvent::ReceivedCharacter('\r') => Some(modal::Key::Activate),
+        WindowEvent::ReceivedCharacter('\x1b') => Some(modal::Key::Dismiss),
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+            ..
+        } => match key {
+            VirtualKeyCode::Right | VirtualKeyCode::Down => Some(modal::Key::Next),
+            VirtualKeyCode::Left | VirtualKeyCode::Up => Some(modal::Key::Previous),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum FocusedField {
     Username,
     Password,
 }
+
+/// Scales the UI by `ui_scale` on top of the display's own scale factor
+fn set_ui_scale(
+    egui_glow: &mut egui_glow::EguiGlow,
+    window: &backend::window::Window,
+    ui_scale: f32,
+) {
+    egui_glow
+        .egui_winit
+        .set_pixels_per_point(window.scale_factor() as f32 * ui_scale);
+}
+
+/// Applies a new UI scale chosen at the greeter, to be saved for next time
+fn rescale(
+    greeter_state: &mut state::State,
+    egui_glow: &mut egui_glow::EguiGlow,
+    window: &backend::window::Window,
+    ui_scale: f32,
+) {
+    greeter_state.ui_scale = ui_scale.clamp(0.5, 3.0);
+    set_ui_scale(egui_glow, window, greeter_state.ui_scale);
+}
+
+/// Turns egui's own animations off or back on
+fn set_reduce_motion(ctx: &egui::Context, reduce_motion: bool) {
+    let mut style = (*ctx.style()).clone();
+    style.animation_time = if reduce_motion {
+        0.0
+    } else {
+        egui::Style::default().animation_time
+    };
+    ctx.set_style(style);
+}
+
+/// Starts over from scratch by re-executing the greeter, as if greetd had
+/// launched it again. Only returns if that failed.
+fn restart() -> std::io::Error {
+    crossterm::terminal::disable_raw_mode().unwrap();
+    std::process::Command::new(std::env::current_exe().unwrap())
+        .args(std::env::args_os().skip(1))
+        .exec()
+}
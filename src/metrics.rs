@@ -0,0 +1,128 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io::{Read, Write},
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How many recent frame times the percentiles are computed over
+const FRAME_WINDOW: usize = 1024;
+
+pub struct Metrics {
+    started: Instant,
+    auth_attempts: AtomicU64,
+    auth_failures: AtomicU64,
+    frame_times: Mutex<VecDeque<Duration>>,
+}
+
+impl Metrics {
+    pub fn auth_attempt(&self) {
+        self.auth_attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn auth_failure(&self) {
+        self.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame(&self, time: Duration) {
+        let mut frame_times = self.frame_times.lock().unwrap();
+        if frame_times.len() == FRAME_WINDOW {
+            frame_times.pop_front();
+        }
+        frame_times.push_back(time);
+    }
+
+    /// The metrics in Prometheus' text format
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+            writeln!(out, "# HELP eguigreeter_{} {}", name, help).unwrap();
+            writeln!(out, "# TYPE eguigreeter_{} {}", name, kind).unwrap();
+            for (labels, value) in samples {
+                writeln!(out, "eguigreeter_{}{} {}", name, labels, value).unwrap();
+            }
+        };
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Seconds since the greeter started",
+            &[("", self.started.elapsed().as_secs_f64())],
+        );
+        metric(
+            "auth_attempts_total",
+            "counter",
+            "Passwords submitted to greetd",
+            &[("", self.auth_attempts.load(Ordering::Relaxed) as f64)],
+        );
+        metric(
+            "auth_failures_total",
+            "counter",
+            "Authentication errors reported by greetd",
+            &[("", self.auth_failures.load(Ordering::Relaxed) as f64)],
+        );
+
+        let mut frame_times: Vec<Duration> =
+            self.frame_times.lock().unwrap().iter().copied().collect();
+        frame_times.sort();
+        let quantile = |q: f64| {
+            frame_times
+                .get(((frame_times.len() - 1) as f64 * q) as usize)
+                .map(Duration::as_secs_f64)
+                .unwrap_or(0.0)
+        };
+        if !frame_times.is_empty() {
+            metric(
+                "frame_time_seconds",
+                "summary",
+                "Time spent drawing recent frames",
+                &[
+                    ("{quantile=\"0.5\"}", quantile(0.5)),
+                    ("{quantile=\"0.9\"}", quantile(0.9)),
+                    ("{quantile=\"0.99\"}", quantile(0.99)),
+                ],
+            );
+        }
+        out
+    }
+}
+
+/// Serves the metrics over HTTP on a Unix socket at `path`, for scraping
+/// through a proxy or `curl --unix-socket`
+pub fn serve(path: PathBuf) -> Arc<Metrics> {
+    let metrics = Arc::new(Metrics {
+        started: Instant::now(),
+        auth_attempts: AtomicU64::new(0),
+        auth_failures: AtomicU64::new(0),
+        frame_times: Mutex::new(VecDeque::with_capacity(FRAME_WINDOW)),
+    });
+    let _ = std::fs::remove_file(&path);
+    match UnixListener::bind(&path) {
+        Ok(listener) => {
+            let metrics = metrics.clone();
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    // Only one kind of request is served, so the request
+                    // itself doesn't matter
+                    let mut request = [0; 1024];
+                    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+                    let _ = stream.read(&mut request);
+                    let body = metrics.render();
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                }
+            });
+        }
+        Err(e) => eprintln!("Failed to listen on {}: {}", path.display(), e),
+    }
+    metrics
+}
@@ -0,0 +1,84 @@
+use egui::{Align2, Color32, Context, Order, RichText, Sense};
+
+pub enum Outcome<A> {
+    Chosen(A),
+    Dismissed,
+}
+
+/// Keys a modal reacts to, whatever backend they came from
+pub enum Key {
+    Next,
+    Previous,
+    Activate,
+    Dismiss,
+}
+
+/// A dialog drawn above everything else. While one is open it takes all
+/// keyboard input: Tab and the arrow keys move between its buttons, Enter
+/// picks one and Escape dismisses it. Clicks outside of it are swallowed.
+pub struct Modal<A> {
+    title: String,
+    body: Vec<RichText>,
+    actions: Vec<(String, A)>,
+    focused: usize,
+}
+
+impl<A: Copy> Modal<A> {
+    pub fn new(title: impl Into<String>, body: Vec<RichText>, actions: Vec<(String, A)>) -> Self {
+        Modal {
+            title: title.into(),
+            body,
+            actions,
+            focused: 0,
+        }
+    }
+
+    pub fn key(&mut self, key: Key) -> Option<Outcome<A>> {
+        let len = self.actions.len();
+        match key {
+            Key::Next => self.focused = (self.focused + 1) % len,
+            Key::Previous => self.focused = (self.focused + len - 1) % len,
+            Key::Activate => return Some(Outcome::Chosen(self.actions[self.focused].1)),
+            Key::Dismiss => return Some(Outcome::Dismissed),
+        }
+        None
+    }
+
+    pub fn show(&mut self, ctx: &Context) -> Option<Outcome<A>> {
+        let screen = ctx.input().screen_rect();
+        egui::Area::new("modal_blocker")
+            .order(Order::Foreground)
+            .fixed_pos(screen.min)
+            .show(ctx, |ui| {
+                ui.painter()
+                    .rect_filled(screen, 0.0, Color32::from_black_alpha(160));
+                ui.allocate_rect(screen, Sense::click());
+            });
+
+        let mut outcome = None;
+        egui::Area::new("modal")
+            .order(Order::Tooltip)
+            .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Frame::window(ui.style()).show(ui, |ui| {
+                    ui.heading(&self.title);
+                    for line in &self.body {
+                        ui.label(line.clone());
+                    }
+                    ui.horizontal(|ui| {
+                        for (i, (label, action)) in self.actions.iter().enumerate() {
+                            let resp = ui.button(label);
+                            // Keeps focus from wandering off to widgets behind
+                            if i == self.focused && !resp.has_focus() {
+                                resp.request_focus();
+                            }
+                            if resp.clicked() {
+                                outcome = Some(Outcome::Chosen(*action));
+                            }
+                        }
+                    });
+                });
+            });
+        outcome
+    }
+}
@@ -0,0 +1,192 @@
+use std::{ffi::c_void, path::Path, rc::Rc, sync::Mutex};
+
+use libmpv::{
+    events::Event,
+    render::{OpenGLInitParams, RenderContext, RenderParam, RenderParamApiType},
+    FileState, Mpv,
+};
+
+use crate::{
+    backend::{
+        self,
+        event_loop::{EventLoopProxy, EventLoopWindowTarget},
+        Display, NativeDisplay,
+    },
+    background,
+    config::Config,
+    UserEvent,
+};
+
+pub fn get_proc_address(display: &Rc<Display>, name: &str) -> *mut c_void {
+    display.get_proc_address(name) as *mut c_void
+}
+
+/// Background video or picture played by libmpv straight into the window's
+/// GL context
+pub struct Video {
+    // The render context has to be dropped before mpv itself
+    render_context: Option<RenderContext>,
+    mpv: Mpv,
+    hwdec: String,
+    /// Whether the file playing is a still picture, which isn't decoded in
+    /// hardware anyway
+    is_image: bool,
+    /// Played instead when a stream ends before it ever loaded
+    fallback: Option<String>,
+}
+
+impl Video {
+    pub fn new(
+        path: &str,
+        is_image: bool,
+        config: &Config,
+        display: &Rc<Display>,
+        target: &EventLoopWindowTarget<UserEvent>,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) -> Option<Self> {
+        // Without a 10-bit surface mpv tone maps HDR content down to SDR
+        let hdr = config.video.hdr && display.get_pixel_format().color_bits >= 30;
+
+        let mut mpv = Mpv::with_initializer(|f| {
+            if is_image {
+                f.set_property("keep-open", true)?;
+            } else {
+                f.set_property("audio", false)?;
+                f.set_property("loop-file", true)?;
+                f.set_property("hwdec", config.video.hwdec.as_str())?;
+            }
+            if hdr {
+                f.set_property("target-colorspace-hint", true)?;
+                f.set_property("dither-depth", "auto")?;
+            }
+            f.set_property(
+                "saturation",
+                ((config.background.saturation - 1.0) * 100.0).clamp(-100.0, 100.0) as i64,
+            )?;
+            if background::is_stream(path) {
+                f.set_property("network-timeout", config.background.stream_timeout as i64)?;
+            }
+            f.set_property("panscan", 1.0)
+        })
+        .ok()?;
+        if Path::new("/etc/mpv/mpv.conf").exists() {
+            mpv.load_config("/etc/mpv/mpv.conf").ok()?;
+        }
+        let mut params = vec![
+            RenderParam::ApiType(RenderParamApiType::OpenGl),
+            RenderParam::InitParams(OpenGLInitParams {
+                get_proc_address,
+                ctx: display.clone(),
+            }),
+        ];
+        match backend::native_display(target, display.window()) {
+            Some(NativeDisplay::Wayland(display)) => {
+                params.push(RenderParam::WaylandDisplay(display as _))
+            }
+            Some(NativeDisplay::X11(display)) => params.push(RenderParam::X11Display(display as _)),
+            None => {}
+        }
+        let mut render_context = RenderContext::new(unsafe { mpv.ctx.as_mut() }, params).ok()?;
+        mpv.event_context_mut().disable_deprecated_events().unwrap();
+        let wakeup_proxy = Mutex::new(proxy.clone());
+        mpv.event_context_mut().set_wakeup_callback(move || {
+            let _ = wakeup_proxy.lock().unwrap().send_event(UserEvent::Redraw);
+        });
+        let event_proxy = proxy.clone();
+        render_context.set_update_callback(move || {
+            event_proxy.send_event(UserEvent::Redraw).unwrap();
+        });
+        mpv.playlist_load_files(&[(path, FileState::AppendPlay, None)])
+            .unwrap();
+
+        Some(Video {
+            render_context: Some(render_context),
+            mpv,
+            hwdec: config.video.hwdec.clone(),
+            is_image,
+            fallback: config
+                .background
+                .fallback
+                .clone()
+                .filter(|_| background::is_stream(path)),
+        })
+    }
+
+    pub fn render(&self, width: u32, height: u32) {
+        if let Some(render_context) = &self.render_context {
+            render_context
+                .render::<Display>(0, width as _, height as _, true)
+                .expect("Failed to draw on the window");
+        }
+    }
+
+    /// Handles pending mpv events. When hardware decoding was asked for but
+    /// couldn't be initialized for a video, it's played again in software.
+    pub fn poll_events(&mut self) {
+        while let Some(event) = self.mpv.event_context_mut().wait_event(0.0) {
+            if let Ok(Event::EndFile(_)) = event {
+                if let Some(fallback) = self.fallback.take() {
+                    eprintln!("Background stream unreachable, showing {}", fallback);
+                    let _ = self.mpv.set_property("keep-open", true);
+                    let _ = self.mpv.set_property("loop-file", "no");
+                    let _ = self
+                        .mpv
+                        .playlist_load_files(&[(&fallback, FileState::Replace, None)]);
+                }
+            }
+            if let Ok(Event::FileLoaded) = event {
+                // Streams that loaded once don't fall back anymore
+                self.fallback = None;
+            }
+            // The decoder is set up by the time the video output is
+            if let Ok(Event::VideoReconfig) = event {
+                if self.is_image || self.hwdec == "no" {
+                    continue;
+                }
+                let current = self
+                    .mpv
+                    .get_property::<String>("hwdec-current")
+                    .unwrap_or_default();
+                if current.is_empty() || current == "no" {
+                    eprintln!(
+                        "Hardware decoding ({}) failed to initialize, retrying in software",
+                        self.hwdec
+                    );
+                    self.hwdec = "no".to_string();
+                    let _ = self.mpv.set_property("hwdec", "no");
+                    if let Ok(path) = self.mpv.get_property::<String>("path") {
+                        let _ = self
+                            .mpv
+                            .playlist_load_files(&[(&path, FileState::Replace, None)]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Holds the first frame while paused, e.g. while running on battery
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            let _ = self.mpv.command("seek", &["0", "absolute"]);
+        }
+        let _ = self.mpv.set_property("pause", paused);
+    }
+
+    /// mpv draws in [`Video::render`], so there is nothing to add to the UI
+    pub fn show(&mut self, _ctx: &egui::Context) {}
+
+    /// Replaces the playing background with `path`
+    pub fn play(&mut self, path: &str, is_image: bool) {
+        self.is_image = is_image;
+        if !is_image {
+            let _ = self.mpv.set_property("hwdec", self.hwdec.as_str());
+        }
+        let _ = self.mpv.set_property("keep-open", is_image);
+        let _ = self
+            .mpv
+            .set_property("loop-file", if is_image { "no" } else { "inf" });
+        let _ = self
+            .mpv
+            .playlist_load_files(&[(path, FileState::Replace, None)]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+
+use egui::{Align2, Context, Id, RichText, Vec2};
+
+use crate::{
+    backend::{event::WindowEvent, event_loop::EventLoopProxy},
+    modal::Key,
+    UserEvent,
+};
+
+// `<` and `>` are left out, typing them switches sessions
+const LETTERS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+const SYMBOLS: [&str; 3] = ["1234567890", "@#$%&*-+=_", "!?.,:;/'"];
+
+/// Keys drawn on screen for when there's no keyboard to type with. Presses
+/// go through the event loop as the characters a keyboard would send.
+///
+/// It can be entered from the keyboard too, then it keeps keyboard focus
+/// like a [`crate::modal::Modal`]: Tab and the arrow keys move between keys,
+/// Enter presses one and Escape goes back to the form.
+pub struct Osk {
+    proxy: EventLoopProxy<UserEvent>,
+    shift: bool,
+    symbols: bool,
+    /// The field being typed into, which loses focus whenever a key is tapped
+    target: Option<Id>,
+    /// The key with keyboard focus, if it was entered from the keyboard
+    focused: Option<usize>,
+    /// The keys drawn last frame
+    keys: Vec<Id>,
+    /// Enter was hit on the focused key
+    press: bool,
+    /// Characters sent while focused that haven't come back yet
+    sent: VecDeque<char>,
+}
+
+impl Osk {
+    pub fn new(proxy: EventLoopProxy<UserEvent>) -> Self {
+        Osk {
+            proxy,
+            shift: false,
+            symbols: false,
+            target: None,
+            focused: None,
+            keys: Vec::new(),
+            press: false,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Moves keyboard focus onto the first key
+    pub fn enter(&mut self) {
+        self.focused = Some(0);
+        self.sent.clear();
+    }
+
+    pub fn focused(&self) -> bool {
+        self.focused.is_some()
+    }
+
+    /// Handles a navigation key, returning whether focus went back to the form
+    pub fn key(&mut self, key: Key) -> bool {
+        let (focused, len) = match self.focused {
+            Some(focused) => (focused, self.keys.len().max(1)),
+            None => return false,
+        };
+        match key {
+            Key::Next => self.focused = Some((focused + 1) % len),
+            Key::Previous => self.focused = Some((focused + len - 1) % len),
+            Key::Activate => self.press = true,
+            Key::Dismiss => {
+                self.focused = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether `event` is keyboard input for the keyboard itself rather than
+    /// the form. Characters it sent itself are let through.
+    pub fn navigating(&mut self, event: &WindowEvent) -> bool {
+        if self.focused.is_none() {
+            return false;
+        }
+        match event {
+            WindowEvent::ReceivedCharacter(c) if self.sent.front() == Some(c) => {
+                self.sent.pop_front();
+                false
+            }
+            WindowEvent::ReceivedCharacter(_) | WindowEvent::KeyboardInput { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Draws the keyboard along the bottom of the screen. Has to come after
+    /// the fields it types into.
+    pub fn show(&mut self, ctx: &Context) {
+        // Reaching for the pointer leaves keyboard navigation
+        if ctx.input().pointer.any_pressed() {
+            self.focused = None;
+        }
+        if let Some(focus) = ctx.memory().focus() {
+            if !self.keys.contains(&focus) {
+                self.target = Some(focus);
+            }
+        }
+        let focused = self.focused;
+        let press = std::mem::take(&mut self.press);
+        let keys = &mut self.keys;
+        keys.clear();
+        let mut typed = None;
+        egui::Area::new("on_screen_keyboard")
+            .order(egui::Order::Foreground)
+            .anchor(Align2::CENTER_BOTTOM, (0.0, -8.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let size = Vec2::splat(ui.spacing().interact_size.y * 1.5);
+                    let step = size.x + ui.spacing().item_spacing.x;
+                    let mut key = |ui: &mut egui::Ui, label: &str, width: f32| {
+                        let resp = ui.add_sized(
+                            [width, size.y],
+                            egui::Button::new(RichText::new(label).size(size.y * 0.5)),
+                        );
+                        let current = focused == Some(keys.len());
+                        keys.push(resp.id);
+                        // Keeps focus from wandering off to the form behind
+                        if current && !resp.has_focus() {
+                            resp.request_focus();
+                        }
+                        resp.clicked() || (current && press)
+                    };
+                    let rows = if self.symbols { SYMBOLS } else { LETTERS };
+                    for (i, row) in rows.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let indent = (10 - row.len()) as f32 * step / 2.0;
+                            if i == 2 {
+                                let shift = if self.shift { "SHIFT" } else { "Shift" };
+                                if key(ui, shift, indent - ui.spacing().item_spacing.x) {
+                                    self.shift = !self.shift;
+                                }
+                            } else {
+                                ui.add_space(indent);
+                            }
+                            for c in row.chars() {
+                                let c = if self.shift {
+                                    c.to_ascii_uppercase()
+                                } else {
+                                    c
+                                };
+                                if key(ui, &c.to_string(), size.x) {
+                                    typed = Some(c);
+                                }
+                            }
+                            if i == 2 && key(ui, "Back", indent - ui.spacing().item_spacing.x) {
+                                typed = Some('\x7F');
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        let mode = if self.symbols { "abc" } else { "123" };
+                        if key(ui, mode, step * 1.5 - ui.spacing().item_spacing.x) {
+                            self.symbols = !self.symbols;
+                        }
+                        if key(ui, "Tab", step * 1.5 - ui.spacing().item_spacing.x) {
+                            typed = Some('\t');
+                        }
+                        if key(ui, " ", step * 5.0 - ui.spacing().item_spacing.x) {
+                            typed = Some(' ');
+                        }
+                        if key(ui, "Enter", step * 2.0 - ui.spacing().item_spacing.x) {
+                            typed = Some('\r');
+                        }
+                    });
+                });
+            });
+
+        if let Some(c) = typed {
+            if c.is_alphabetic() {
+                self.shift = false;
+            }
+            if self.focused.is_some() {
+                self.sent.push_back(c);
+            }
+            if let Some(target) = self.target {
+                ctx.memory().request_focus(target);
+            }
+            let _ = self
+                .proxy
+                .send_event(UserEvent::Remote(WindowEvent::ReceivedCharacter(c)));
+        }
+    }
+}
+
+/// Makes buttons and fields big enough to hit with a finger
+pub fn touch_style(ctx: &Context, scale: f32) {
+    let mut style = (*ctx.style()).clone();
+    style.spacing.interact_size *= scale;
+    style.spacing.button_padding *= scale;
+    style.spacing.item_spacing *= scale;
+    ctx.set_style(style);
+}
@@ -0,0 +1,68 @@
+use std::sync::mpsc::{self, Sender};
+
+use greetd_client::Greetd;
+
+/// Something to ask of greetd, each answered with exactly one message
+pub enum Request {
+    CreateSession(String),
+    AuthenticationResponse(Option<String>),
+    StartSession(Vec<String>),
+    CancelSession,
+}
+
+/// Queues requests for greetd and writes them from a separate thread, so a
+/// slow or wedged greetd never stalls rendering or input. Responses still
+/// arrive through the calloop source.
+pub struct Outbox(Sender<Request>);
+
+impl Outbox {
+    pub fn spawn(mut greetd: Greetd) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for request in rx {
+                let result = match request {
+                    Request::CreateSession(username) => greetd.create_session(&username),
+                    Request::AuthenticationResponse(response) => {
+                        greetd.authentication_response(response.as_deref())
+                    }
+                    Request::StartSession(cmd) => {
+                        greetd.start_session(&cmd.iter().map(String::as_str).collect::<Vec<_>>())
+                    }
+                    Request::CancelSession => greetd.cancel_session(),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to write to greetd: {:?}", e);
+                }
+            }
+        });
+        Outbox(tx)
+    }
+
+    /// Takes requests without sending them anywhere, for when greetd's side
+    /// is being replayed or there's none, in a benchmark
+    pub fn discard() -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for request in rx {
+                let name = match request {
+                    Request::CreateSession(_) => "create_session",
+                    Request::AuthenticationResponse(_) => "post_auth_message_response",
+                    Request::StartSession(_) => "start_session",
+                    Request::CancelSession => "cancel_session",
+                };
+                eprintln!(
+                    "Not sending {} to greetd, there's no connection to it",
+                    name
+                );
+            }
+        });
+        Outbox(tx)
+    }
+}
+
+impl crate::auth::Sink for Outbox {
+    fn send(&self, request: Request) {
+        // The writer thread only exits if it panicked
+        self.0.send(request).unwrap();
+    }
+}
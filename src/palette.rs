@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use egui::{Color32, Stroke, Visuals};
+use serde::{Deserialize, Serialize};
+
+/// The dominant color of a picture, pywal-style: pixels are bucketed by
+/// color, greys count for little, and the fullest bucket's average wins.
+/// Returns `None` for anything the `image` crate can't open, like videos.
+pub fn dominant(path: &str) -> Option<[u8; 3]> {
+    let thumbnail = image::open(path).ok()?.thumbnail(64, 64).to_rgb8();
+    let mut buckets: HashMap<[u8; 3], (f32, [u32; 3], u32)> = HashMap::new();
+    for pixel in thumbnail.pixels() {
+        let [r, g, b] = pixel.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let saturation = if max == 0 {
+            0.0
+        } else {
+            (max - min) as f32 / max as f32
+        };
+        let bucket = buckets.entry([r >> 5, g >> 5, b >> 5]).or_default();
+        bucket.0 += 0.1 + saturation;
+        bucket.1[0] += r as u32;
+        bucket.1[1] += g as u32;
+        bucket.1[2] += b as u32;
+        bucket.2 += 1;
+    }
+    let (_, (_, [r, g, b], n)) = buckets
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))?;
+    Some([(r / n) as u8, (g / n) as u8, (b / n) as u8])
+}
+
+/// Colors that carry meaning, with variants that stay apart for the common
+/// kinds of color blindness, based on the Okabe-Ito palette
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Standard,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::Tritanopia,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Palette::Standard => "Standard colors",
+            Palette::Deuteranopia => "Deuteranopia (green weak)",
+            Palette::Protanopia => "Protanopia (red weak)",
+            Palette::Tritanopia => "Tritanopia (blue weak)",
+        }
+    }
+
+    pub fn error(self) -> Color32 {
+        match self {
+            Palette::Standard => Color32::LIGHT_RED,
+            // Vermillion, which doesn't read as green or brown
+            Palette::Deuteranopia | Palette::Protanopia => Color32::from_rgb(213, 94, 0),
+            Palette::Tritanopia => Color32::from_rgb(220, 50, 47),
+        }
+    }
+
+    pub fn warning(self) -> Color32 {
+        match self {
+            Palette::Standard => Color32::YELLOW,
+            Palette::Deuteranopia | Palette::Protanopia => Color32::from_rgb(240, 228, 66),
+            // Yellow looks pink or white without blue cones
+            Palette::Tritanopia => Color32::from_rgb(204, 121, 167),
+        }
+    }
+
+    /// Makes the focus outline stand out in a color that can't be confused
+    /// with [`Palette::error`]
+    pub fn apply(self, visuals: &mut Visuals) {
+        let focus = match self {
+            Palette::Standard => return,
+            Palette::Deuteranopia | Palette::Protanopia => Color32::from_rgb(86, 180, 233),
+            Palette::Tritanopia => Color32::from_rgb(0, 158, 115),
+        };
+        visuals.selection.stroke = Stroke::new(2.5, focus);
+    }
+}
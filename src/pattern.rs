@@ -0,0 +1,73 @@
+use egui::{vec2, Pos2, Sense, Stroke, Ui, Vec2};
+
+/// Patterns shorter than this are ignored rather than submitted
+const MIN_NODES: usize = 4;
+
+/// A 3×3 grid to draw an unlock pattern on. The pattern is written out as
+/// the numbers of the dots it went through, `1` to `9` row by row, which is
+/// what the PAM module has to check against.
+#[derive(Default)]
+pub struct Pattern {
+    nodes: Vec<u8>,
+}
+
+impl Pattern {
+    /// Draws the grid, `size` points wide. Once a pattern is finished it's
+    /// written into `secret`, replacing what was there, and `true` returned.
+    pub fn show(&mut self, ui: &mut Ui, size: f32, secret: &mut String) -> bool {
+        let (rect, resp) = ui.allocate_exact_size(Vec2::splat(size), Sense::drag());
+        let cell = size / 3.0;
+        let center =
+            |node: u8| rect.min + vec2((node % 3) as f32 + 0.5, (node / 3) as f32 + 0.5) * cell;
+
+        let pointer = resp.interact_pointer_pos().filter(|_| resp.dragged());
+        if let Some(pos) = pointer {
+            if let Some(node) = (0..9).find(|&node| center(node).distance(pos) < cell * 0.3) {
+                if !self.nodes.contains(&node) {
+                    // Going straight over a dot takes it along, as on phones
+                    if let Some(&last) = self.nodes.last() {
+                        let (row, col) = (last / 3 + node / 3, last % 3 + node % 3);
+                        let middle = row / 2 * 3 + col / 2;
+                        if row % 2 == 0 && col % 2 == 0 && !self.nodes.contains(&middle) {
+                            self.nodes.push(middle);
+                        }
+                    }
+                    self.nodes.push(node);
+                }
+            }
+        }
+
+        let visuals = ui.visuals();
+        let idle = visuals.widgets.inactive.fg_stroke.color;
+        let active = visuals.selection.bg_fill;
+        let painter = ui.painter_at(rect);
+        let mut points: Vec<Pos2> = self.nodes.iter().map(|&node| center(node)).collect();
+        points.extend(pointer);
+        if points.len() > 1 {
+            painter.add(egui::Shape::line(points, Stroke::new(cell * 0.08, active)));
+        }
+        for node in 0..9 {
+            let color = if self.nodes.contains(&node) {
+                active
+            } else {
+                idle
+            };
+            painter.circle_filled(center(node), cell * 0.1, color);
+        }
+        if !self.nodes.is_empty() {
+            ui.ctx().request_repaint();
+        }
+
+        if !resp.drag_released() || self.nodes.is_empty() {
+            return false;
+        }
+        let long_enough = self.nodes.len() >= MIN_NODES;
+        if long_enough {
+            crate::secret::scrub(secret);
+            secret.extend(self.nodes.iter().map(|&node| (b'1' + node) as char));
+        }
+        self.nodes.fill(0);
+        self.nodes.clear();
+        long_enough
+    }
+}
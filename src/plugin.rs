@@ -0,0 +1,275 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use egui::{RichText, Ui};
+use wasmtime::{
+    Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use crate::{config::Fleet, trust};
+
+pub const DEFAULT_DIR: &str = "/usr/lib/eguigreeter/plugins";
+
+/// Fuel handed to a plugin for every call, so a runaway plugin traps instead
+/// of freezing the greeter
+const FUEL: u64 = 10_000_000;
+/// Bytes of linear memory a plugin may grow to, so it can't take the
+/// greeter's memory the way fuel keeps it from taking its time
+const MEMORY: usize = 16 << 20;
+
+/// Sent to a plugin's `on_event`
+#[derive(Clone, Copy)]
+#[repr(i32)]
+pub enum PluginEvent {
+    AuthSuccess = 0,
+    AuthFailure = 1,
+}
+
+enum Widget {
+    Label(String),
+    Heading(String),
+    Separator,
+}
+
+struct State {
+    widgets: Vec<Widget>,
+    timer: Option<Duration>,
+    limits: StoreLimits,
+}
+
+struct Plugin {
+    name: String,
+    store: Store<State>,
+    draw: TypedFunc<(), ()>,
+    on_timer: Option<TypedFunc<(), ()>>,
+    on_event: Option<TypedFunc<i32, ()>>,
+    widgets: Vec<Widget>,
+    next_tick: Option<Instant>,
+}
+
+/// Widgets compiled to WebAssembly. A plugin exports `memory` and `draw()`,
+/// and optionally `init()`, `on_timer()` and `on_event(kind)`. From the
+/// `eguigreeter` module it can import:
+///
+/// - `label(ptr, len)` and `heading(ptr, len)` to draw UTF-8 text from its
+///   memory, and `separator()`, only while in `draw()`
+/// - `set_timer(ms)` to have `on_timer()` called every `ms` milliseconds,
+///   0 stops it
+/// - `log(ptr, len)` to print to the greeter's stderr
+///
+/// `draw()` is called again after every other call into the plugin.
+#[derive(Default)]
+pub struct Plugins(Vec<Plugin>);
+
+fn read_str(caller: &mut Caller<'_, State>, ptr: u32, len: u32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let bytes = memory
+        .data(&*caller)
+        .get(ptr as usize..ptr as usize + len as usize)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+impl Plugin {
+    fn load(engine: &Engine, linker: &Linker<State>, path: &Path) -> wasmtime::Result<Self> {
+        let module = Module::from_file(engine, path)?;
+        let state = State {
+            widgets: Vec::new(),
+            timer: None,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY)
+                .memories(1)
+                .table_elements(10_000)
+                .tables(4)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.add_fuel(FUEL)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        if let Ok(init) = instance.get_typed_func::<(), (), _>(&mut store, "init") {
+            init.call(&mut store, ())?;
+        }
+        let mut plugin = Plugin {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            draw: instance.get_typed_func(&mut store, "draw")?,
+            on_timer: instance.get_typed_func(&mut store, "on_timer").ok(),
+            on_event: instance.get_typed_func(&mut store, "on_event").ok(),
+            store,
+            widgets: Vec::new(),
+            next_tick: None,
+        };
+        plugin.redraw()?;
+        Ok(plugin)
+    }
+
+    fn redraw(&mut self) -> wasmtime::Result<()> {
+        self.store.add_fuel(FUEL)?;
+        self.store.data_mut().widgets.clear();
+        self.draw.call(&mut self.store, ())?;
+        self.widgets = std::mem::take(&mut self.store.data_mut().widgets);
+        // Timers are only picked up after a call, so reschedule here
+        self.next_tick = match (self.store.data().timer, self.next_tick) {
+            (Some(timer), None) => Some(Instant::now() + timer),
+            (Some(_), next_tick) => next_tick,
+            (None, _) => None,
+        };
+        Ok(())
+    }
+
+    fn call<P: wasmtime::WasmParams>(
+        &mut self,
+        func: Option<TypedFunc<P, ()>>,
+        params: P,
+    ) -> wasmtime::Result<()> {
+        if let Some(func) = func {
+            self.store.add_fuel(FUEL)?;
+            func.call(&mut self.store, params)?;
+            self.redraw()?;
+        }
+        Ok(())
+    }
+}
+
+impl Plugins {
+    /// Loads every `*.wasm` in `dir` in lexical order. In fleet mode only
+    /// those `dir` vouches for, see [`trust::allowed_in`].
+    pub fn load(dir: impl AsRef<Path>, fleet: Option<&Fleet>) -> Self {
+        let dir = dir.as_ref();
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.extension().map(|ext| ext == "wasm").unwrap_or(false))
+            .filter(|p| fleet.map_or(true, |fleet| trust::allowed_in(p, dir, fleet, "plugin")))
+            .collect();
+        if paths.is_empty() {
+            return Plugins(Vec::new());
+        }
+        paths.sort();
+
+        let engine = Engine::new(Config::new().consume_fuel(true)).unwrap();
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap(
+                "eguigreeter",
+                "label",
+                |mut caller: Caller<'_, State>, ptr: u32, len: u32| {
+                    if let Some(text) = read_str(&mut caller, ptr, len) {
+                        caller.data_mut().widgets.push(Widget::Label(text));
+                    }
+                },
+            )
+            .unwrap()
+            .func_wrap(
+                "eguigreeter",
+                "heading",
+                |mut caller: Caller<'_, State>, ptr: u32, len: u32| {
+                    if let Some(text) = read_str(&mut caller, ptr, len) {
+                        caller.data_mut().widgets.push(Widget::Heading(text));
+                    }
+                },
+            )
+            .unwrap()
+            .func_wrap(
+                "eguigreeter",
+                "separator",
+                |mut caller: Caller<'_, State>| {
+                    caller.data_mut().widgets.push(Widget::Separator);
+                },
+            )
+            .unwrap()
+            .func_wrap(
+                "eguigreeter",
+                "set_timer",
+                |mut caller: Caller<'_, State>, ms: u32| {
+                    caller.data_mut().timer =
+                        (ms > 0).then(|| Duration::from_millis(ms.max(100) as u64));
+                },
+            )
+            .unwrap()
+            .func_wrap(
+                "eguigreeter",
+                "log",
+                |mut caller: Caller<'_, State>, ptr: u32, len: u32| {
+                    if let Some(text) = read_str(&mut caller, ptr, len) {
+                        eprintln!("plugin: {}", text);
+                    }
+                },
+            )
+            .unwrap();
+
+        Plugins(
+            paths
+                .iter()
+                .filter_map(|path| {
+                    Plugin::load(&engine, &linker, path)
+                        .map_err(|e| eprintln!("Failed to load plugin {}: {:#}", path.display(), e))
+                        .ok()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Drops plugins whose call failed, so a broken plugin can't keep
+    /// erroring every frame
+    fn retain_working(&mut self, mut f: impl FnMut(&mut Plugin) -> wasmtime::Result<()>) {
+        self.0.retain_mut(|plugin| match f(plugin) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Plugin {} failed and was unloaded: {:#}", plugin.name, e);
+                false
+            }
+        });
+    }
+
+    pub fn event(&mut self, event: PluginEvent) {
+        self.retain_working(|plugin| plugin.call(plugin.on_event.clone(), event as i32));
+    }
+
+    /// Calls `on_timer()` of plugins whose timer is due, returning whether
+    /// any of them did
+    pub fn tick(&mut self) -> bool {
+        let now = Instant::now();
+        let mut ticked = false;
+        self.retain_working(
+            |plugin| match (plugin.next_tick, plugin.store.data().timer) {
+                (Some(next_tick), Some(timer)) if next_tick <= now => {
+                    ticked = true;
+                    plugin.next_tick = Some(now + timer);
+                    plugin.call(plugin.on_timer.clone(), ())
+                }
+                _ => Ok(()),
+            },
+        );
+        ticked
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.0.iter().filter_map(|plugin| plugin.next_tick).min()
+    }
+
+    pub fn show(&self, ui: &mut Ui, color: egui::Color32) {
+        for plugin in &self.0 {
+            for widget in &plugin.widgets {
+                match widget {
+                    Widget::Label(text) => {
+                        ui.label(RichText::new(text).color(color));
+                    }
+                    Widget::Heading(text) => {
+                        ui.label(RichText::new(text).heading().color(color));
+                    }
+                    Widget::Separator => {
+                        ui.separator();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::process::{Command, Stdio};
+
+fn plymouth(args: &[&str]) {
+    let status = Command::new("plymouth")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if let Err(e) = status {
+        eprintln!("Failed to run plymouth {}: {}", args.join(" "), e);
+    }
+}
+
+/// Makes plymouth give up the display without clearing it, so the splash
+/// stays on screen while the greeter initializes
+pub fn deactivate() {
+    plymouth(&["deactivate"]);
+}
+
+/// Tells plymouth to exit once the greeter's first frame is on screen
+pub fn quit(retain_splash: bool) {
+    if retain_splash {
+        plymouth(&["quit", "--retain-splash"]);
+    } else {
+        plymouth(&["quit"]);
+    }
+}
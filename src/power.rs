@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use zbus::blocking::{Connection, Proxy};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+/// Polls UPower's `OnBattery` and sends [`UserEvent::OnBattery`] whenever it
+/// changes
+pub fn watch_battery(proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let upower = match Connection::system().and_then(|conn| {
+            Proxy::new(
+                &conn,
+                "org.freedesktop.UPower",
+                "/org/freedesktop/UPower",
+                "org.freedesktop.UPower",
+            )
+        }) {
+            Ok(upower) => upower,
+            Err(e) => {
+                eprintln!("Failed to connect to UPower: {}", e);
+                return;
+            }
+        };
+        let mut last = None;
+        loop {
+            if let Ok(on_battery) = upower.get_property::<bool>("OnBattery") {
+                if last != Some(on_battery) {
+                    last = Some(on_battery);
+                    if proxy.send_event(UserEvent::OnBattery(on_battery)).is_err() {
+                        return;
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_secs(15));
+        }
+    });
+}
+
+/// Sends [`UserEvent::PrepareForSleep`] when logind announces the machine is
+/// about to suspend or hibernate, and [`UserEvent::Resumed`] once it's back
+pub fn watch_sleep(proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let signals = Connection::system().and_then(|conn| {
+            Proxy::new(
+                &conn,
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+            )?
+            .receive_signal("PrepareForSleep")
+        });
+        let signals = match signals {
+            Ok(signals) => signals,
+            Err(e) => {
+                eprintln!("Failed to watch for suspend: {}", e);
+                return;
+            }
+        };
+        for signal in signals {
+            let event = match signal.body::<bool>() {
+                Ok(true) => UserEvent::PrepareForSleep,
+                Ok(false) => UserEvent::Resumed,
+                Err(_) => continue,
+            };
+            if proxy.send_event(event).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    PowerOff,
+    Reboot,
+}
+
+impl PowerAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerAction::PowerOff => "Shut down",
+            PowerAction::Reboot => "Reboot",
+        }
+    }
+
+    /// Asks logind to power off or reboot the machine
+    pub fn run(&self) {
+        let method = match self {
+            PowerAction::PowerOff => "PowerOff",
+            PowerAction::Reboot => "Reboot",
+        };
+        let result = Connection::system().and_then(|conn| {
+            Proxy::new(
+                &conn,
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+            )?
+            .call::<_, _, ()>(method, &(false,))
+        });
+        if let Err(e) = result {
+            eprintln!("Failed to {}: {}", self.label().to_lowercase(), e);
+        }
+    }
+}
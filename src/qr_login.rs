@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use egui::{vec2, Color32, Rect, Sense, Ui};
+use qrcode::{Color, QrCode};
+use rand::RngCore;
+use serde::Deserialize;
+
+use crate::{backend::event_loop::EventLoopProxy, config::QrLogin, UserEvent};
+
+/// What the endpoint answers once the companion app approved the token
+#[derive(Deserialize, Debug)]
+pub struct Approval {
+    pub username: String,
+    pub secret: String,
+}
+
+pub struct QrSession {
+    code: QrCode,
+}
+
+impl QrSession {
+    /// Creates a one-time token, encodes `<endpoint>/approve?token=…` as a QR
+    /// code and polls `<endpoint>/status?token=…` until the login is approved,
+    /// then sends [`UserEvent::QrApproved`]. Endpoints that aren't https are
+    /// refused, as the token and the secret travel through them.
+    pub fn start(config: &QrLogin, proxy: EventLoopProxy<UserEvent>) -> Option<Self> {
+        if !config.endpoint.starts_with("https://") {
+            eprintln!(
+                "Not showing a QR login code, {} isn't an https URL",
+                config.endpoint
+            );
+            return None;
+        }
+        let mut token = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut token);
+        let token: String = token.iter().map(|b| format!("{:02x}", b)).collect();
+        let endpoint = config.endpoint.trim_end_matches('/');
+        let code = QrCode::new(format!("{}/approve?token={}", endpoint, token)).ok()?;
+
+        let status = format!("{}/status?token={}", endpoint, token);
+        let interval = Duration::from_secs(config.poll_interval.max(1));
+        std::thread::spawn(move || {
+            let agent = ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build();
+            loop {
+                std::thread::sleep(interval);
+                let response = match agent.get(&status).call() {
+                    Ok(response) if response.status() == 200 => response,
+                    _ => continue,
+                };
+                let approval = response
+                    .into_string()
+                    .ok()
+                    .and_then(|body| serde_json::from_str::<Approval>(&body).ok());
+                if let Some(approval) = approval {
+                    let _ = proxy.send_event(UserEvent::QrApproved(approval));
+                    return;
+                }
+            }
+        });
+
+        Some(QrSession { code })
+    }
+
+    pub fn show(&self, ui: &mut Ui, size: f32) {
+        let width = self.code.width();
+        // Leave a quiet zone of two modules around the code
+        let module = size / (width + 4) as f32;
+        let (rect, _) = ui.allocate_exact_size(vec2(size, size), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::WHITE);
+        for y in 0..width {
+            for x in 0..width {
+                if self.code[(x, y)] == Color::Dark {
+                    let min = rect.min + vec2((x + 2) as f32, (y + 2) as f32) * module;
+                    painter.rect_filled(
+                        Rect::from_min_size(min, vec2(module, module)),
+                        0.0,
+                        Color32::BLACK,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    os::unix::fs::OpenOptionsExt,
+};
+
+use greetd_client::{AuthMessageType, ErrorType, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{
+        dpi::PhysicalPosition,
+        event::{
+            DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode,
+            WindowEvent,
+        },
+        event_loop::EventLoopProxy,
+    },
+    UserEvent,
+};
+
+/// Keys without a character of their own that the greeter reacts to, by the
+/// names they're recorded under
+const KEYS: [(&str, VirtualKeyCode); 12] = [
+    ("back", VirtualKeyCode::Back),
+    ("delete", VirtualKeyCode::Delete),
+    ("return", VirtualKeyCode::Return),
+    ("tab", VirtualKeyCode::Tab),
+    ("escape", VirtualKeyCode::Escape),
+    ("left", VirtualKeyCode::Left),
+    ("right", VirtualKeyCode::Right),
+    ("up", VirtualKeyCode::Up),
+    ("down", VirtualKeyCode::Down),
+    ("home", VirtualKeyCode::Home),
+    ("end", VirtualKeyCode::End),
+    ("print", VirtualKeyCode::Snapshot),
+];
+
+/// Something that happened to the greeter from outside: input, or a
+/// message from greetd
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    Char {
+        char: char,
+    },
+    Key {
+        key: String,
+        pressed: bool,
+    },
+    Modifiers {
+        bits: u32,
+    },
+    Cursor {
+        x: f64,
+        y: f64,
+    },
+    Button {
+        /// 0 is the left button, 1 the right one, 2 the middle one
+        button: u8,
+        pressed: bool,
+    },
+    AuthMessage {
+        /// `visible`, `secret`, `info` or `error`
+        message_type: String,
+        message: String,
+    },
+    Success,
+    Error {
+        /// Whether authentication failed, rather than anything else
+        auth: bool,
+        description: String,
+    },
+    Finish,
+}
+
+impl Event {
+    /// `event` as recorded, if it's input the greeter reacts to. Characters
+    /// typed into a secret are recorded as `*`.
+    fn from_window_event(event: &WindowEvent, secret: bool) -> Option<Event> {
+        Some(match event {
+            WindowEvent::ReceivedCharacter(c) if secret && !c.is_control() => {
+                Event::Char { char: '*' }
+            }
+            WindowEvent::ReceivedCharacter(c) => Event::Char { char: *c },
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(code),
+                        state,
+                        ..
+                    },
+                ..
+            } => Event::Key {
+                key: KEYS.iter().find(|(_, key)| key == code)?.0.to_string(),
+                pressed: *state == ElementState::Pressed,
+            },
+            WindowEvent::ModifiersChanged(state) => Event::Modifiers { bits: state.bits() },
+            WindowEvent::CursorMoved { position, .. } => Event::Cursor {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::MouseInput { state, button, .. } => Event::Button {
+                button: match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Right => 1,
+                    MouseButton::Middle => 2,
+                    MouseButton::Other(_) => return None,
+                },
+                pressed: *state == ElementState::Pressed,
+            },
+            _ => return None,
+        })
+    }
+
+    fn from_response(response: &Response) -> Event {
+        match response {
+            Response::AuthMessage {
+                auth_message_type,
+                auth_message,
+            } => Event::AuthMessage {
+                message_type: match auth_message_type {
+                    AuthMessageType::Visible => "visible",
+                    AuthMessageType::Secret => "secret",
+                    AuthMessageType::Info => "info",
+                    AuthMessageType::Error => "error",
+                }
+                .to_string(),
+                message: auth_message.clone(),
+            },
+            Response::Success => Event::Success,
+            Response::Error {
+                error_type,
+                description,
+            } => Event::Error {
+                auth: matches!(error_type, ErrorType::AuthError),
+                description: description.clone(),
+            },
+            Response::Finish => Event::Finish,
+        }
+    }
+
+    /// The input this stands for, as the window would have received it
+    #[allow(deprecated)]
+    pub fn window_event(&self) -> Option<WindowEvent<'static>> {
+        let device_id = unsafe { DeviceId::dummy() };
+        let state = |pressed: bool| {
+            if pressed {
+                ElementState::Pressed
+            } else {
+                ElementState::Released
+            }
+        };
+        Some(match self {
+            Event::Char { char } => WindowEvent::ReceivedCharacter(*char),
+            Event::Key { key, pressed } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: state(*pressed),
+                    virtual_keycode: KEYS
+                        .iter()
+                        .find(|(name, _)| name == key)
+                        .map(|(_, code)| *code),
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+            Event::Modifiers { bits } => {
+                WindowEvent::ModifiersChanged(ModifiersState::from_bits_truncate(*bits))
+            }
+            Event::Cursor { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(*x, *y),
+                modifiers: ModifiersState::empty(),
+            },
+            Event::Button { button, pressed } => WindowEvent::MouseInput {
+                device_id,
+                state: state(*pressed),
+                button: match button {
+                    1 => MouseButton::Right,
+                    2 => MouseButton::Middle,
+                    _ => MouseButton::Left,
+                },
+                modifiers: ModifiersState::empty(),
+            },
+            _ => return None,
+        })
+    }
+
+    /// The greetd message this stands for
+    pub fn response(&self) -> Option<Response> {
+        Some(match self {
+            Event::AuthMessage {
+                message_type,
+                message,
+            } => Response::AuthMessage {
+                auth_message_type: match message_type.as_str() {
+                    "visible" => AuthMessageType::Visible,
+                    "info" => AuthMessageType::Info,
+                    "error" => AuthMessageType::Error,
+                    _ => AuthMessageType::Secret,
+                },
+                auth_message: message.clone(),
+            },
+            Event::Success => Response::Success,
+            Event::Error { auth, description } => Response::Error {
+                error_type: if *auth {
+                    ErrorType::AuthError
+                } else {
+                    ErrorType::Error
+                },
+                description: description.clone(),
+            },
+            Event::Finish => Response::Finish,
+            _ => return None,
+        })
+    }
+}
+
+/// A line of a recording
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Frames the greeter had drawn when it happened
+    frame: u64,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Writes input and greetd's messages to a file as they happen, one JSON
+/// object per line, for [`Player`] to play back
+pub struct Recorder {
+    file: File,
+    frame: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        Ok(Recorder { file, frame: 0 })
+    }
+
+    fn write(&mut self, event: Event) {
+        let entry = Entry {
+            frame: self.frame,
+            event,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(self.file, "{}", line));
+        if let Err(e) = result {
+            eprintln!("Failed to record {:?}: {}", entry.event, e);
+        }
+    }
+
+    /// Records `event` if it's input the greeter reacts to. With `secret`,
+    /// what's typed is masked.
+    pub fn input(&mut self, event: &WindowEvent, secret: bool) {
+        if let Some(event) = Event::from_window_event(event, secret) {
+            self.write(event);
+        }
+    }
+
+    pub fn response(&mut self, response: &Response) {
+        self.write(Event::from_response(response));
+    }
+
+    /// Counts a frame drawn, what's recorded is timed by
+    pub fn frame(&mut self) {
+        self.frame += 1;
+    }
+}
+
+/// Plays a recording back, sending each event as [`UserEvent::Replayed`]
+/// once the greeter has drawn as many frames as when it was recorded, so
+/// the replay doesn't depend on how fast the greeter runs
+pub struct Player {
+    entries: VecDeque<Entry>,
+    frame: u64,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl Player {
+    pub fn open(path: &str, proxy: EventLoopProxy<UserEvent>) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let entries = BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|(i, line)| {
+                let line = line.map_err(|e| e.to_string())?;
+                serde_json::from_str::<Entry>(&line).map_err(|e| format!("line {}: {}", i + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let mut player = Player {
+            entries,
+            frame: 0,
+            proxy,
+        };
+        player.send();
+        Ok(player)
+    }
+
+    fn send(&mut self) {
+        while self
+            .entries
+            .front()
+            .map_or(false, |entry| entry.frame <= self.frame)
+        {
+            let entry = self.entries.pop_front().unwrap();
+            let _ = self.proxy.send_event(UserEvent::Replayed(entry.event));
+            if self.entries.is_empty() {
+                eprintln!("Replay finished");
+            }
+        }
+    }
+
+    /// Counts a frame drawn and sends what was recorded by then. Returns
+    /// whether there's more to play, which needs more frames drawn.
+    pub fn frame(&mut self) -> bool {
+        self.frame += 1;
+        self.send();
+        !self.entries.is_empty()
+    }
+}
@@ -0,0 +1,151 @@
+use crate::backend::event::{
+    ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent,
+};
+
+/// A key chord like `ctrl+alt+delete`
+pub struct Chord {
+    modifiers: ModifiersState,
+    key: VirtualKeyCode,
+}
+
+pub const LETTERS: [VirtualKeyCode; 26] = {
+    use VirtualKeyCode::*;
+    [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ]
+};
+
+fn key(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const FUNCTION_KEYS: [VirtualKeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    Some(match name {
+        "delete" | "del" => Delete,
+        "enter" | "return" => Return,
+        "space" => Space,
+        "escape" | "esc" => Escape,
+        "backspace" => Back,
+        "tab" => Tab,
+        "insert" => Insert,
+        "home" => Home,
+        "end" => End,
+        "print" | "prtsc" => Snapshot,
+        _ if name.len() == 1 && name.as_bytes()[0].is_ascii_lowercase() => {
+            LETTERS[(name.as_bytes()[0] - b'a') as usize]
+        }
+        _ => *FUNCTION_KEYS.get(
+            name.strip_prefix('f')?
+                .parse::<usize>()
+                .ok()?
+                .checked_sub(1)?,
+        )?,
+    })
+}
+
+impl Chord {
+    pub fn parse(chord: &str) -> Option<Self> {
+        let mut modifiers = ModifiersState::empty();
+        let mut parts: Vec<String> = chord.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let key = key(&parts.pop()?)?;
+        for part in parts {
+            modifiers |= match part.as_str() {
+                "ctrl" | "control" => ModifiersState::CTRL,
+                "alt" => ModifiersState::ALT,
+                "shift" => ModifiersState::SHIFT,
+                "super" | "logo" => ModifiersState::LOGO,
+                _ => return None,
+            };
+        }
+        Some(Chord { modifiers, key })
+    }
+
+    pub fn matches(&self, modifiers: ModifiersState, key: VirtualKeyCode) -> bool {
+        self.modifiers == modifiers && self.key == key
+    }
+
+    /// Whether the chord can be told apart in input read from the TTY, where
+    /// only `ctrl+<letter>` chords can
+    pub fn typeable(&self) -> bool {
+        self.modifiers == ModifiersState::CTRL && LETTERS.contains(&self.key)
+    }
+
+    /// Whether `c` is what a terminal sends for the chord, for input read
+    /// from the TTY. Only `ctrl+<letter>` chords can be told apart there.
+    pub fn matches_char(&self, c: char) -> bool {
+        self.modifiers == ModifiersState::CTRL
+            && LETTERS
+                .iter()
+                .position(|letter| *letter == self.key)
+                .map(|i| c as u32 == i as u32 + 1)
+                .unwrap_or(false)
+    }
+
+    /// Whether `event` is the chord being pressed. Characters only count for
+    /// input read from the TTY, as windowing systems send the key as well.
+    pub fn pressed(&self, event: &WindowEvent, modifiers: ModifiersState, tty: bool) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => self.matches(modifiers, *key),
+            WindowEvent::ReceivedCharacter(c) if tty => self.matches_char(*c),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_chords() {
+        let chord = Chord::parse("ctrl+alt+delete").unwrap();
+        assert!(chord.matches(
+            ModifiersState::CTRL | ModifiersState::ALT,
+            VirtualKeyCode::Delete
+        ));
+        assert!(!chord.matches(ModifiersState::CTRL, VirtualKeyCode::Delete));
+
+        let chord = Chord::parse(" Super + F12 ").unwrap();
+        assert!(chord.matches(ModifiersState::LOGO, VirtualKeyCode::F12));
+
+        assert!(Chord::parse("hyper+a").is_none());
+        assert!(Chord::parse("ctrl+").is_none());
+        assert!(Chord::parse("").is_none());
+    }
+
+    #[test]
+    fn names_keys() {
+        assert_eq!(key("del"), Some(VirtualKeyCode::Delete));
+        assert_eq!(key("prtsc"), Some(VirtualKeyCode::Snapshot));
+        assert_eq!(key("q"), Some(VirtualKeyCode::Q));
+        assert_eq!(key("f1"), Some(VirtualKeyCode::F1));
+        assert_eq!(key("f12"), Some(VirtualKeyCode::F12));
+        assert_eq!(key("f0"), None);
+        assert_eq!(key("f13"), None);
+        assert_eq!(key("Q"), None);
+        assert_eq!(key("hello"), None);
+    }
+
+    #[test]
+    fn only_ctrl_letters_are_typeable() {
+        let chord = Chord::parse("ctrl+e").unwrap();
+        assert!(chord.typeable());
+        assert!(chord.matches_char('\x05'));
+        assert!(!chord.matches_char('e'));
+        // Only on the TTY, windowing systems send the key too
+        let event = WindowEvent::ReceivedCharacter('\x05');
+        assert!(chord.pressed(&event, ModifiersState::empty(), true));
+        assert!(!chord.pressed(&event, ModifiersState::empty(), false));
+
+        let chord = Chord::parse("ctrl+alt+e").unwrap();
+        assert!(!chord.typeable());
+        assert!(!chord.matches_char('\x05'));
+        assert!(!Chord::parse("ctrl+f1").unwrap().typeable());
+    }
+}
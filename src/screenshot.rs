@@ -0,0 +1,90 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glow::HasContext;
+
+use crate::{backend::event_loop::EventLoopProxy, state::STATE_DIR, UserEvent};
+
+/// A path for a new screenshot, named after when it's taken
+pub fn path() -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis())
+        .unwrap_or_default();
+    Path::new(STATE_DIR)
+        .join("screenshots")
+        .join(format!("{}.png", millis))
+}
+
+/// Reads the finished frame out of the back buffer, the PNG is written to
+/// `path` on a separate thread
+pub unsafe fn capture(gl: &glow::Context, width: u32, height: u32, path: PathBuf) {
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    gl.read_pixels(
+        0,
+        0,
+        width as i32,
+        height as i32,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        glow::PixelPackData::Slice(&mut pixels),
+    );
+    std::thread::spawn(move || {
+        // GL counts rows from the bottom
+        let row = width as usize * 4;
+        let flipped: Vec<u8> = pixels.rchunks_exact(row).flatten().copied().collect();
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                image::save_buffer(&path, &flipped, width, height, image::ColorType::Rgba8)
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => eprintln!("Saved a screenshot to {}", path.display()),
+            Err(e) => eprintln!("Failed to save a screenshot to {}: {}", path.display(), e),
+        }
+    });
+}
+
+struct Service {
+    proxy: Mutex<EventLoopProxy<UserEvent>>,
+}
+
+#[zbus::dbus_interface(name = "org.eguigreeter.Greeter")]
+impl Service {
+    /// Saves the next frame, returning where it will be
+    fn screenshot(&self) -> String {
+        let path = path();
+        let _ = self
+            .proxy
+            .lock()
+            .unwrap()
+            .send_event(UserEvent::Screenshot(path.clone()));
+        path.display().to_string()
+    }
+}
+
+/// Offers `Screenshot` on the system bus as `org.eguigreeter.Greeter`. The
+/// bus needs a policy letting the greeter's user own that name.
+pub fn serve(proxy: EventLoopProxy<UserEvent>) {
+    let result = zbus::blocking::ConnectionBuilder::system()
+        .and_then(|builder| builder.name("org.eguigreeter.Greeter"))
+        .and_then(|builder| {
+            builder.serve_at(
+                "/org/eguigreeter/Greeter",
+                Service {
+                    proxy: Mutex::new(proxy),
+                },
+            )
+        })
+        .and_then(|builder| builder.build());
+    match result {
+        // Requests are handled on zbus' own thread for as long as the
+        // connection is around, which is until the greeter exits
+        Ok(conn) => std::mem::forget(conn),
+        Err(e) => eprintln!("Failed to serve org.eguigreeter.Greeter: {}", e),
+    }
+}
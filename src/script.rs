@@ -0,0 +1,133 @@
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST};
+
+/// How long `sh()` waits for its command before killing it
+const SH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A user script with optional `on_frame(time)`, `on_auth_result(username,
+/// success)` and `widget()` functions. `widget()` returns a string or an
+/// array of strings shown as lines on screen. Top-level statements run once
+/// at load, and variables they define persist between calls.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    widget: Vec<String>,
+    widget_at: Option<Instant>,
+}
+
+/// Runs `cmd` with `sh -c` and returns its trimmed output, for scripts that
+/// want to show things like `fortune -s`. Commands still running after
+/// [`SH_TIMEOUT`] are killed and return nothing.
+fn sh(cmd: &str) -> String {
+    let mut child = match Command::new("sh")
+        .args(["-c", cmd])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return String::new(),
+    };
+    // Read on the side, so a command that never closes its output can't
+    // stall the caller
+    let mut stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = stdout.read_to_end(&mut out);
+        let _ = sender.send(out);
+    });
+    let out = receiver.recv_timeout(SH_TIMEOUT);
+    if out.is_err() {
+        eprintln!("Script command `{}` took too long, killing it", cmd);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    out.map(|out| String::from_utf8_lossy(&out).trim().to_string())
+        .unwrap_or_default()
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Option<Self> {
+        let mut engine = Engine::new();
+        // A runaway script would hang the login screen for good
+        engine
+            .set_max_operations(1_000_000)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(64 * 1024)
+            .set_max_array_size(10_000)
+            .set_max_map_size(10_000);
+        engine.register_fn("sh", sh);
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| eprintln!("Failed to load script {}: {}", path.display(), e))
+            .ok()?;
+        let mut scope = Scope::new();
+        if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+            eprintln!("Script {} failed: {}", path.display(), e);
+        }
+        Some(Script {
+            engine,
+            ast,
+            scope,
+            widget: Vec::new(),
+            widget_at: None,
+        })
+    }
+
+    fn call(&mut self, name: &str, args: impl FuncArgs) -> Option<Dynamic> {
+        if !self.ast.iter_functions().any(|f| f.name == name) {
+            return None;
+        }
+        self.engine
+            .call_fn_with_options(
+                CallFnOptions::new().eval_ast(false).rewind_scope(false),
+                &mut self.scope,
+                &self.ast,
+                name,
+                args,
+            )
+            .map_err(|e| eprintln!("Script function {} failed: {}", name, e))
+            .ok()
+    }
+
+    pub fn on_frame(&mut self, time: f64) {
+        self.call("on_frame", (time,));
+    }
+
+    pub fn on_auth_result(&mut self, username: &str, success: bool) {
+        self.call("on_auth_result", (username.to_string(), success));
+    }
+
+    /// The lines returned by `widget()`, re-evaluated at most once a second
+    pub fn widget(&mut self) -> &[String] {
+        if self
+            .widget_at
+            .map(|at| at.elapsed() >= Duration::from_secs(1))
+            .unwrap_or(true)
+        {
+            self.widget_at = Some(Instant::now());
+            self.widget = match self.call("widget", ()) {
+                Some(value) if value.is_array() => value
+                    .into_array()
+                    .unwrap()
+                    .into_iter()
+                    .map(|line| line.to_string())
+                    .collect(),
+                Some(value) if value.is_unit() => Vec::new(),
+                Some(value) => value.to_string().lines().map(str::to_string).collect(),
+                None => Vec::new(),
+            };
+        }
+        &self.widget
+    }
+}
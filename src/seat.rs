@@ -0,0 +1,98 @@
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::OwnedObjectPath,
+};
+
+/// A user session already running on this seat
+pub struct SeatSession {
+    pub id: String,
+    pub user: String,
+    /// Terminal the session runs on, e.g. `tty2`, empty if logind doesn't know
+    pub tty: String,
+}
+
+impl SeatSession {
+    /// e.g. "alice is logged in on tty2"
+    pub fn describe(&self) -> String {
+        if self.tty.is_empty() {
+            format!("{} is logged in", self.user)
+        } else {
+            format!("{} is logged in on {}", self.user, self.tty)
+        }
+    }
+}
+
+/// The seat the greeter runs on, `seat0` unless logind says otherwise
+pub fn name() -> String {
+    std::env::var("XDG_SEAT")
+        .ok()
+        // Seat names end up in paths
+        .filter(|seat| {
+            !seat.is_empty()
+                && seat
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .unwrap_or_else(|| "seat0".to_string())
+}
+
+fn manager(conn: &Connection) -> zbus::Result<Proxy> {
+    Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+}
+
+/// Lists the user sessions logind knows about on the greeter's seat, which is
+/// how we tell a running session asked greetd to show the greeter again
+pub fn sessions() -> Vec<SeatSession> {
+    let seat = name();
+    let result = Connection::system().and_then(|conn| {
+        let listed: Vec<(String, u32, String, String, OwnedObjectPath)> =
+            manager(&conn)?.call("ListSessions", &())?;
+        Ok(listed
+            .into_iter()
+            .filter(|(_, _, _, session_seat, _)| *session_seat == seat)
+            .filter_map(|(id, _, user, _, path)| {
+                let session = Proxy::new(
+                    &conn,
+                    "org.freedesktop.login1",
+                    path.as_str(),
+                    "org.freedesktop.login1.Session",
+                )
+                .ok()?;
+                if session.get_property::<String>("Class").ok()? != "user" {
+                    return None;
+                }
+                let tty = session
+                    .get_property::<String>("TTY")
+                    .ok()
+                    .filter(|tty| !tty.is_empty())
+                    .or_else(|| {
+                        let vt = session.get_property::<u32>("VTNr").ok()?;
+                        (vt > 0).then(|| format!("tty{}", vt))
+                    })
+                    .unwrap_or_default();
+                Some(SeatSession { id, user, tty })
+            })
+            .collect())
+    });
+    result.unwrap_or_else(|e| {
+        eprintln!("Failed to list sessions from logind: {}", e);
+        Vec::new()
+    })
+}
+
+/// Unlocks the session and switches the seat over to it
+pub fn activate(id: &str) {
+    let result = Connection::system().and_then(|conn| {
+        let manager = manager(&conn)?;
+        manager.call::<_, _, ()>("UnlockSession", &(id,))?;
+        manager.call::<_, _, ()>("ActivateSession", &(id,))
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to switch to session {}: {}", id, e);
+    }
+}
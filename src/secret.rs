@@ -0,0 +1,20 @@
+/// Overwrites `secret` with zeros before clearing it, so the old contents
+/// don't linger in the freed buffer
+pub fn scrub(secret: &mut String) {
+    // Zero bytes are valid UTF-8, so the string stays valid throughout
+    unsafe { secret.as_mut_vec() }.fill(0);
+    secret.clear();
+}
+
+/// Cuts `secret` down to `max` characters, zeroing what's cut off. Returns
+/// whether anything was.
+pub fn truncate(secret: &mut String, max: usize) -> bool {
+    let end = match secret.char_indices().nth(max) {
+        Some((end, _)) => end,
+        None => return false,
+    };
+    // Zeroing keeps the string valid, the cut lands on a char boundary
+    unsafe { secret.as_mut_vec()[end..].fill(0) };
+    secret.truncate(end);
+    true
+}
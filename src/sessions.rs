@@ -0,0 +1,245 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use egui::{Context, TextureHandle};
+use freedesktop_desktop_entry::DesktopEntry;
+
+use crate::{
+    config::{self, Config},
+    handoff, textures,
+};
+
+pub const WAYLAND_SESSIONS: &str = "/usr/share/wayland-sessions";
+pub const X_SESSIONS: &str = "/usr/share/xsessions";
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum SessionKind {
+    Wayland,
+    X11,
+}
+
+impl SessionKind {
+    fn from_path(path: &Path) -> Self {
+        if path.starts_with(X_SESSIONS) {
+            SessionKind::X11
+        } else {
+            SessionKind::Wayland
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionKind::Wayland => "Wayland",
+            SessionKind::X11 => "X11",
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub struct StrippedEntry<'a> {
+    /// Desktop file name without `.desktop`
+    pub id: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    pub exec: Cow<'a, str>,
+    pub kind: SessionKind,
+    pub comment: Option<Cow<'a, str>>,
+    pub icon: Option<Cow<'a, str>>,
+    /// Whether the program in `exec` could be found
+    pub installed: bool,
+}
+
+impl StrippedEntry<'_> {
+    /// Copies out of the desktop entry, so the file contents can be freed
+    pub fn into_owned(self) -> StrippedEntry<'static> {
+        StrippedEntry {
+            id: Cow::Owned(self.id.into_owned()),
+            name: Cow::Owned(self.name.into_owned()),
+            exec: Cow::Owned(self.exec.into_owned()),
+            kind: self.kind,
+            comment: self.comment.map(|c| Cow::Owned(c.into_owned())),
+            icon: self.icon.map(|i| Cow::Owned(i.into_owned())),
+            installed: self.installed,
+        }
+    }
+
+    /// The program `exec` runs, skipping `env` and variable assignments
+    pub fn program(&self) -> String {
+        exec_args(&self.exec)
+            .into_iter()
+            .find(|word| word != "env" && !word.contains('='))
+            .unwrap_or_default()
+    }
+
+    /// e.g. "sway: not installed"
+    pub fn missing_hint(&self) -> String {
+        format!("{}: not installed", self.program())
+    }
+
+    /// Resolves the entry's `Icon` through the icon theme, or as a path if
+    /// it is absolute
+    pub fn load_icon(&self, ctx: &Context) -> Option<TextureHandle> {
+        let icon = self.icon.as_deref()?;
+        if Path::new(icon).is_absolute() {
+            textures::load(ctx, icon)
+        } else {
+            textures::load(ctx, freedesktop_icons::lookup(icon).with_size(24).find()?)
+        }
+    }
+
+    /// Whether `session` names this entry, by desktop file or display name
+    pub fn is(&self, session: &str) -> bool {
+        self.id == session || self.name.eq_ignore_ascii_case(session)
+    }
+
+    /// The command line handed to greetd's `start_session`. Variables from
+    /// the `[env]` table, `locale` as `LANG` and the `NAME=value` pairs in
+    /// `extra_env` are set through `env(1)`, as greetd's IPC only takes a
+    /// command.
+    pub fn command(
+        &self,
+        config: &Config,
+        locale: Option<&str>,
+        extra_env: &[String],
+    ) -> Vec<String> {
+        let mut cmd = Vec::new();
+        if !config.env.is_empty() || locale.is_some() || !extra_env.is_empty() {
+            cmd.push("env".to_string());
+            cmd.extend(config.env.iter().map(|(k, v)| format!("{}={}", k, v)));
+            cmd.extend(locale.map(|locale| format!("LANG={}", locale)));
+            cmd.extend(extra_env.iter().cloned());
+        }
+        if config.systemd_scope {
+            cmd.extend(
+                ["systemd-run", "--user", "--scope", "--collect"]
+                    .iter()
+                    .map(|s| s.to_string()),
+            );
+        }
+        match config.wrapper.as_deref().unwrap_or(config::DEFAULT_WRAPPER) {
+            "" => cmd.extend(exec_args(&self.exec)),
+            wrapper => cmd.extend([wrapper.to_string(), self.exec.to_string()]),
+        }
+        if config.handoff.enabled || config.session_crash.enabled {
+            cmd = handoff::wrap(
+                cmd,
+                &config.handoff.scrub,
+                config.handoff.cap_open_files,
+                config.session_crash.enabled,
+            );
+        }
+        cmd
+    }
+}
+
+/// Reads the desktop entries of all installed sessions, to be decoded with
+/// [`DesktopEntry::decode`]
+pub fn read() -> Vec<(String, PathBuf)> {
+    freedesktop_desktop_entry::Iter::new(vec![
+        PathBuf::from(WAYLAND_SESSIONS),
+        PathBuf::from(X_SESSIONS),
+    ])
+    .filter_map(|path| Some((std::fs::read_to_string(&path).ok()?, path)))
+    .collect()
+}
+
+/// Strips the desktop entries down to what the greeter needs, sorted by kind,
+/// then by position in `priority`, then alphabetically. Entries sharing a
+/// name with one already seen are dropped, so Wayland sessions win.
+pub fn strip<'a>(
+    entries: &'a [DesktopEntry<'a>],
+    priority: &[String],
+    locale: Option<&str>,
+) -> Vec<StrippedEntry<'a>> {
+    let mut environments: Vec<StrippedEntry> = entries
+        .iter()
+        .filter_map(|f| {
+            Some(StrippedEntry {
+                id: Cow::Borrowed(f.appid),
+                name: f.name(locale)?,
+                exec: Cow::Borrowed(f.exec()?),
+                kind: SessionKind::from_path(f.path),
+                comment: f.comment(locale),
+                icon: f.icon().map(Cow::Borrowed),
+                installed: false,
+            })
+        })
+        .collect();
+    for env in &mut environments {
+        env.installed = is_installed(&env.program());
+    }
+
+    environments.sort_by(|a, b| {
+        let rank = |e: &StrippedEntry| {
+            priority
+                .iter()
+                .position(|p| *p == e.name)
+                .unwrap_or(priority.len())
+        };
+        a.kind
+            .cmp(&b.kind)
+            .then_with(|| rank(a).cmp(&rank(b)))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    let mut seen = Vec::new();
+    environments.retain(|e| {
+        if seen.contains(&e.name) {
+            false
+        } else {
+            seen.push(e.name.clone());
+            true
+        }
+    });
+
+    environments
+}
+
+/// Splits a desktop entry's `Exec` into arguments the way the spec says:
+/// double quotes group words, and inside them `\` escapes `"`, `` ` ``, `$`
+/// and `\`. Field codes are dropped, as sessions get no files or URLs, and
+/// `%%` is a literal `%`.
+fn exec_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether `arg` was started, so `""` still makes an argument
+    let mut started = false;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            '\\' if quoted => arg.extend(chars.next()),
+            '%' => {
+                if chars.next() == Some('%') {
+                    arg.push('%');
+                }
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started || !arg.is_empty() {
+                    args.push(std::mem::take(&mut arg));
+                }
+                started = false;
+            }
+            c => arg.push(c),
+        }
+    }
+    if started || !arg.is_empty() {
+        args.push(arg);
+    }
+    args
+}
+
+/// Resolves `program` through `PATH` unless it is a path itself
+fn is_installed(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
@@ -0,0 +1,105 @@
+use std::{path::Path, time::Instant};
+
+use glow::HasContext;
+
+const VERTEX: &str = r#"
+void main() {
+    vec2 pos = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_PRELUDE: &str = r#"
+uniform float iTime;
+uniform vec3 iResolution;
+out vec4 eguigreeter_FragColor;
+"#;
+
+const FRAGMENT_MAIN: &str = r#"
+void main() {
+    mainImage(eguigreeter_FragColor, gl_FragCoord.xy);
+}
+"#;
+
+/// Links `fragment` with a vertex shader covering the screen with one
+/// triangle, for drawing with `draw_arrays(TRIANGLES, 0, 3)`. The GLSL
+/// version line is added to both.
+pub unsafe fn program(gl: &glow::Context, fragment: &str) -> Result<glow::Program, String> {
+    let header = if gl.get_parameter_string(glow::VERSION).contains("OpenGL ES") {
+        "#version 300 es\nprecision highp float;\n"
+    } else {
+        "#version 330 core\n"
+    };
+
+    let program = gl.create_program()?;
+    let shaders = [
+        (glow::VERTEX_SHADER, format!("{}{}", header, VERTEX)),
+        (glow::FRAGMENT_SHADER, format!("{}{}", header, fragment)),
+    ]
+    .into_iter()
+    .map(|(kind, source)| {
+        let shader = gl.create_shader(kind)?;
+        gl.shader_source(shader, &source);
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            return Err(gl.get_shader_info_log(shader));
+        }
+        gl.attach_shader(program, shader);
+        Ok(shader)
+    })
+    .collect::<Result<Vec<_>, String>>()?;
+
+    gl.link_program(program);
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+    if !gl.get_program_link_status(program) {
+        return Err(gl.get_program_info_log(program));
+    }
+    Ok(program)
+}
+
+/// A fullscreen fragment shader background, taking shadertoy style
+/// `mainImage`, `iTime` and `iResolution`
+pub struct Shader {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    time: Option<glow::UniformLocation>,
+    resolution: Option<glow::UniformLocation>,
+    started: Instant,
+}
+
+impl Shader {
+    pub unsafe fn new(gl: &glow::Context, path: impl AsRef<Path>) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let program = program(
+            gl,
+            &format!("{}{}{}", FRAGMENT_PRELUDE, source, FRAGMENT_MAIN),
+        )?;
+
+        Ok(Shader {
+            program,
+            vertex_array: gl.create_vertex_array()?,
+            time: gl.get_uniform_location(program, "iTime"),
+            resolution: gl.get_uniform_location(program, "iResolution"),
+            started: Instant::now(),
+        })
+    }
+
+    pub unsafe fn paint(&self, gl: &glow::Context, width: u32, height: u32) {
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.use_program(Some(self.program));
+        gl.uniform_1_f32(self.time.as_ref(), self.started.elapsed().as_secs_f32());
+        gl.uniform_3_f32(self.resolution.as_ref(), width as f32, height as f32, 1.0);
+        gl.bind_vertex_array(Some(self.vertex_array));
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        gl.bind_vertex_array(None);
+        gl.use_program(None);
+    }
+
+    pub unsafe fn destroy(&self, gl: &glow::Context) {
+        gl.delete_vertex_array(self.vertex_array);
+        gl.delete_program(self.program);
+    }
+}
@@ -0,0 +1,78 @@
+use std::process::{Command, Stdio};
+
+use egui::{output::OutputEvent, WidgetType};
+
+/// Speaks `text` through speech-dispatcher. `spd-say` is reaped on a
+/// separate thread.
+pub fn say(text: &str) {
+    let child = Command::new("spd-say")
+        .args(["--", text])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("Failed to run spd-say: {}", e),
+    }
+}
+
+/// Reads prompts and errors aloud while turned on
+pub struct Speech {
+    enabled: bool,
+    last_title: String,
+}
+
+impl Speech {
+    pub fn new(enabled: bool) -> Self {
+        Speech {
+            enabled,
+            last_title: String::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        say(if self.enabled {
+            "Speech on"
+        } else {
+            "Speech off"
+        });
+    }
+
+    pub fn say(&self, text: &str) {
+        if self.enabled && !text.trim().is_empty() {
+            say(text);
+        }
+    }
+
+    /// Speaks the login window's title when it changes, errors end up there
+    pub fn title(&mut self, title: &str) {
+        if title != self.last_title {
+            self.last_title = title.to_string();
+            self.say(title);
+        }
+    }
+
+    /// Speaks the names of widgets egui reports as clicked, focused or
+    /// changed. Text fields are skipped, they would read passwords out.
+    pub fn widget_events(&self, events: &[OutputEvent]) {
+        for event in events {
+            let info = match event {
+                OutputEvent::Clicked(info)
+                | OutputEvent::FocusGained(info)
+                | OutputEvent::ValueChanged(info) => info,
+                _ => continue,
+            };
+            if info.typ != WidgetType::TextEdit {
+                self.say(&info.description());
+            }
+        }
+    }
+}
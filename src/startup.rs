@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+/// How many frames `--bench-startup` draws after the first one
+pub const BENCH_FRAMES: usize = 120;
+
+/// Times the phases of startup, printed to stderr at the first frame when
+/// `EGUIGREETER_TRACE_STARTUP` is set or with `--bench-startup`
+pub struct Trace {
+    started: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+    /// Work done on other threads while the phases ran
+    background: Vec<(&'static str, Duration)>,
+}
+
+impl Trace {
+    pub fn new(bench: bool) -> Option<Self> {
+        if !bench {
+            std::env::var_os("EGUIGREETER_TRACE_STARTUP")?;
+        }
+        let now = Instant::now();
+        Some(Trace {
+            started: now,
+            last: now,
+            phases: Vec::new(),
+            background: Vec::new(),
+        })
+    }
+
+    /// Ends the phase named `phase`, which started at the previous mark
+    pub fn mark(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    pub fn finish(mut self) {
+        self.mark("first frame");
+        for (phase, took) in &self.phases {
+            eprintln!("startup: {:>16} {:>8.1?}", phase, took);
+        }
+        eprintln!("startup: {:>16} {:>8.1?}", "total", self.started.elapsed());
+        for (work, took) in &self.background {
+            eprintln!("startup: {:>16} {:>8.1?} (in the background)", work, took);
+        }
+    }
+}
+
+/// Marks the end of a phase when tracing
+pub fn mark(trace: &mut Option<Trace>, phase: &'static str) {
+    if let Some(trace) = trace {
+        trace.mark(phase);
+    }
+}
+
+/// Notes how long `work` took on another thread when tracing
+pub fn background(trace: &mut Option<Trace>, work: &'static str, took: Duration) {
+    if let Some(trace) = trace {
+        trace.background.push((work, took));
+    }
+}
+
+/// Frame times after the first frame, for `--bench-startup`
+#[derive(Default)]
+pub struct FrameBench {
+    /// Running egui, which is what laying out the greeter costs
+    ui: Vec<Duration>,
+    /// Everything from the redraw request to the buffer swap
+    frame: Vec<Duration>,
+}
+
+impl FrameBench {
+    /// Notes a frame. Returns whether there are enough of them.
+    pub fn frame(&mut self, ui: Duration, frame: Duration) -> bool {
+        self.ui.push(ui);
+        self.frame.push(frame);
+        self.frame.len() >= BENCH_FRAMES
+    }
+
+    pub fn finish(self) {
+        for (name, mut times) in [("ui", self.ui), ("frame", self.frame)] {
+            if times.is_empty() {
+                continue;
+            }
+            times.sort();
+            let mean = times.iter().sum::<Duration>() / times.len() as u32;
+            let quantile = |q: f64| times[((times.len() - 1) as f64 * q) as usize];
+            eprintln!(
+                "frames:  {:>16} {:>8.1?} mean {:>8.1?} p50 {:>8.1?} p95 {:>8.1?} max",
+                name,
+                mean,
+                quantile(0.5),
+                quantile(0.95),
+                times[times.len() - 1],
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{palette::Palette, seat};
+
+pub const STATE_DIR: &str = "/var/lib/eguigreeter";
+
+/// Settings changed at the greeter itself rather than in the config, kept
+/// across restarts, separately for each seat
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Multiplier on top of the display's own scale factor
+    pub ui_scale: f32,
+    pub palette: Palette,
+    pub high_contrast: bool,
+    /// No fading, shaking or animated widgets
+    pub reduce_motion: bool,
+    /// Whether prompts are read aloud, once toggled at the greeter
+    pub speech: Option<bool>,
+    /// Whether the on-screen keyboard is shown, once toggled at the greeter
+    pub on_screen_keyboard: Option<bool>,
+    pub last_username: Option<String>,
+    /// Desktop file name of the session last started
+    pub last_session: Option<String>,
+    /// Keeps [`State::save`] from writing anything, e.g. during a replay
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            ui_scale: 1.0,
+            palette: Palette::default(),
+            high_contrast: false,
+            reduce_motion: false,
+            speech: None,
+            on_screen_keyboard: None,
+            last_username: None,
+            last_session: None,
+            read_only: false,
+        }
+    }
+}
+
+impl State {
+    fn path() -> PathBuf {
+        Path::new(STATE_DIR).join(seat::name()).join("state.toml")
+    }
+
+    /// Where the state was kept before it was per seat, which seats without
+    /// their own start from
+    fn shared_path() -> PathBuf {
+        Path::new(STATE_DIR).join("state.toml")
+    }
+
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .or_else(|_| std::fs::read_to_string(Self::shared_path()))
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// The accessibility settings that are on, as `A11Y_*` variables for the
+    /// session to pick up
+    pub fn accessibility_env(&self, speech: bool, on_screen_keyboard: bool) -> Vec<String> {
+        let mut env: Vec<String> = [
+            ("A11Y_HIGH_CONTRAST", self.high_contrast),
+            ("A11Y_REDUCE_MOTION", self.reduce_motion),
+            ("A11Y_SCREEN_READER", speech),
+            ("A11Y_ON_SCREEN_KEYBOARD", on_screen_keyboard),
+        ]
+        .iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| format!("{}=1", name))
+        .collect();
+        if self.ui_scale != 1.0 {
+            env.push(format!("A11Y_TEXT_SCALE={}", self.ui_scale));
+        }
+        env
+    }
+
+    pub fn save(&self) {
+        if self.read_only {
+            return;
+        }
+        let _ = std::fs::create_dir_all(Self::path().parent().unwrap());
+        if let Err(e) = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|s| std::fs::write(Self::path(), s).map_err(|e| e.to_string()))
+        {
+            eprintln!("Failed to save {}: {}", Self::path().display(), e);
+        }
+    }
+}
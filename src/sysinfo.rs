@@ -0,0 +1,45 @@
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
+
+fn uptime() -> Option<String> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs = uptime.split_whitespace().next()?.parse::<f64>().ok()? as u64;
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    Some(match days {
+        0 => format!("up {}:{:02}", hours, minutes),
+        1 => format!("up 1 day, {}:{:02}", hours, minutes),
+        _ => format!("up {} days, {}:{:02}", days, hours, minutes),
+    })
+}
+
+fn kernel() -> Option<String> {
+    let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    Some(format!("Linux {}", release.trim()))
+}
+
+fn boot_time(offset: UtcOffset) -> Option<String> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let btime = stat
+        .lines()
+        .find_map(|l| l.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    let booted = OffsetDateTime::from_unix_timestamp(btime)
+        .ok()?
+        .to_offset(offset);
+    booted
+        .format(format_description!(
+            "booted [year]-[month]-[day] [hour]:[minute]"
+        ))
+        .ok()
+}
+
+/// Uptime, kernel version and boot time, for checking at the login screen
+/// whether a machine picked up its updates
+pub fn info_line(offset: UtcOffset) -> String {
+    [uptime(), kernel(), boot_time(offset)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
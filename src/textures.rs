@@ -0,0 +1,28 @@
+use std::path::Path;
+
+use egui::{pos2, Color32, ColorImage, Context, LayerId, Rect, TextureHandle};
+
+pub fn load(ctx: &Context, path: impl AsRef<Path>) -> Option<TextureHandle> {
+    let path = path.as_ref();
+    let image = image::open(path).ok()?.into_rgba8();
+    Some(ctx.load_texture(
+        path.to_string_lossy(),
+        ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.as_raw(),
+        ),
+    ))
+}
+
+/// Paints `texture` behind every window, scaled to cover the whole screen
+pub fn paint_cover(ctx: &Context, texture: &TextureHandle) {
+    let screen = ctx.input().screen_rect();
+    let size = texture.size_vec2();
+    let scale = (screen.width() / size.x).max(screen.height() / size.y);
+    ctx.layer_painter(LayerId::background()).image(
+        texture.id(),
+        Rect::from_center_size(screen.center(), size * scale),
+        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+        Color32::WHITE,
+    );
+}
@@ -0,0 +1,297 @@
+use std::{path::PathBuf, time::Duration};
+
+use egui::{
+    style::WidgetVisuals, Color32, Context, FontData, FontDefinitions, FontFamily, FontId,
+    Rounding, Stroke, TextStyle, Visuals,
+};
+use serde::Deserialize;
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+/// Returns `visuals` with every color multiplied by `alpha`
+pub fn faded(visuals: &Visuals, alpha: f32) -> Visuals {
+    let color = |c: Color32| c.linear_multiply(alpha);
+    let stroke = |s: Stroke| Stroke::new(s.width, color(s.color));
+    let widget = |w: &mut WidgetVisuals| {
+        w.bg_fill = color(w.bg_fill);
+        w.bg_stroke = stroke(w.bg_stroke);
+        w.fg_stroke = stroke(w.fg_stroke);
+    };
+
+    let mut visuals = visuals.clone();
+    visuals.override_text_color = visuals.override_text_color.map(color);
+    widget(&mut visuals.widgets.noninteractive);
+    widget(&mut visuals.widgets.inactive);
+    widget(&mut visuals.widgets.hovered);
+    widget(&mut visuals.widgets.active);
+    widget(&mut visuals.widgets.open);
+    visuals.selection.bg_fill = color(visuals.selection.bg_fill);
+    visuals.selection.stroke = stroke(visuals.selection.stroke);
+    visuals.hyperlink_color = color(visuals.hyperlink_color);
+    visuals.faint_bg_color = color(visuals.faint_bg_color);
+    visuals.extreme_bg_color = color(visuals.extreme_bg_color);
+    visuals.code_bg_color = color(visuals.code_bg_color);
+    visuals.window_shadow.color = color(visuals.window_shadow.color);
+    visuals.popup_shadow.color = color(visuals.popup_shadow.color);
+    visuals
+}
+
+/// Returns `visuals` with the accent set to `color` and the window background
+/// tinted towards it
+pub fn tinted(visuals: &Visuals, [r, g, b]: [u8; 3]) -> Visuals {
+    let accent = Color32::from_rgb(r, g, b);
+    let mix = |c: Color32| {
+        let lerp = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * 0.2) as u8;
+        Color32::from_rgba_premultiplied(lerp(c.r(), r), lerp(c.g(), g), lerp(c.b(), b), c.a())
+    };
+
+    let mut visuals = visuals.clone();
+    visuals.selection.bg_fill = accent;
+    visuals.hyperlink_color = accent;
+    visuals.widgets.noninteractive.bg_fill = mix(visuals.widgets.noninteractive.bg_fill);
+    visuals.widgets.inactive.bg_fill = mix(visuals.widgets.inactive.bg_fill);
+    visuals
+}
+
+/// Returns `visuals` as white on black with thick outlines, keeping only the
+/// accent for the selection
+pub fn high_contrast(visuals: &Visuals) -> Visuals {
+    let widget = |w: &mut WidgetVisuals, fill: Color32| {
+        w.bg_fill = fill;
+        w.bg_stroke = Stroke::new(2.0, Color32::WHITE);
+        w.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+    };
+
+    let mut visuals = visuals.clone();
+    visuals.dark_mode = true;
+    visuals.override_text_color = Some(Color32::WHITE);
+    widget(&mut visuals.widgets.noninteractive, Color32::BLACK);
+    widget(&mut visuals.widgets.inactive, Color32::BLACK);
+    widget(&mut visuals.widgets.hovered, Color32::from_gray(60));
+    widget(&mut visuals.widgets.active, Color32::from_gray(90));
+    widget(&mut visuals.widgets.open, Color32::from_gray(60));
+    visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::YELLOW;
+    visuals.faint_bg_color = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.code_bg_color = Color32::BLACK;
+    visuals
+}
+
+/// A standalone theme file, referenced from the config with
+/// `theme = "/usr/share/eguigreeter/themes/<name>.toml"`, or one of the
+/// built-in themes with `theme = "<name>"`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Theme {
+    /// Start from egui's light visuals instead of the dark ones
+    pub light: bool,
+    pub colors: Colors,
+    pub fonts: Fonts,
+    pub layout: Layout,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Colors {
+    pub text: Option<[u8; 3]>,
+    /// Selections and links
+    pub accent: Option<[u8; 3]>,
+    pub window: Option<[u8; 3]>,
+    pub widget: Option<[u8; 3]>,
+    pub hovered: Option<[u8; 3]>,
+    /// The clocks, weather and other text drawn straight on the background
+    pub overlay: Option<[u8; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Fonts {
+    /// TTF or OTF file used in place of egui's proportional font
+    pub file: Option<PathBuf>,
+    /// Size of body text and buttons
+    pub size: Option<f32>,
+    pub clock_size: f32,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts {
+            file: None,
+            size: None,
+            clock_size: 48.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Layout {
+    /// Corner radius of windows and widgets
+    pub rounding: Option<f32>,
+    /// Space between widgets as `[x, y]`
+    pub spacing: Option<[f32; 2]>,
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color32 {
+    Color32::from_rgb(r, g, b)
+}
+
+impl Theme {
+    /// Loads one of the built-in themes by name, or a theme file otherwise
+    pub fn load(path: &str) -> Option<Self> {
+        if let Some(theme) = Self::builtin(path) {
+            return Some(theme);
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| eprintln!("Failed to read theme {}: {}", path, e))
+            .ok()?;
+        toml::from_str(&contents)
+            .map_err(|e| eprintln!("Invalid theme {}: {}", path, e))
+            .ok()
+    }
+
+    /// `dark`, `light`, `high-contrast` or `solarized`
+    pub fn builtin(name: &str) -> Option<Self> {
+        let theme = match name {
+            "dark" => Theme::default(),
+            "light" => Theme {
+                light: true,
+                colors: Colors {
+                    overlay: Some([20, 20, 20]),
+                    ..Colors::default()
+                },
+                ..Theme::default()
+            },
+            "high-contrast" => Theme {
+                colors: Colors {
+                    text: Some([255, 255, 255]),
+                    accent: Some([255, 215, 0]),
+                    window: Some([0, 0, 0]),
+                    widget: Some([0, 0, 0]),
+                    hovered: Some([60, 60, 60]),
+                    overlay: Some([255, 255, 255]),
+                },
+                fonts: Fonts {
+                    size: Some(18.0),
+                    clock_size: 56.0,
+                    ..Fonts::default()
+                },
+                layout: Layout {
+                    rounding: Some(0.0),
+                    ..Layout::default()
+                },
+                ..Theme::default()
+            },
+            "solarized" => Theme {
+                colors: Colors {
+                    text: Some([131, 148, 150]),
+                    accent: Some([38, 139, 210]),
+                    window: Some([0, 43, 54]),
+                    widget: Some([7, 54, 66]),
+                    hovered: Some([88, 110, 117]),
+                    overlay: Some([238, 232, 213]),
+                },
+                ..Theme::default()
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = if self.light {
+            Visuals::light()
+        } else {
+            Visuals::dark()
+        };
+        if let Some(text) = self.colors.text {
+            visuals.override_text_color = Some(rgb(text));
+        }
+        if let Some(accent) = self.colors.accent {
+            visuals.selection.bg_fill = rgb(accent);
+            visuals.hyperlink_color = rgb(accent);
+        }
+        if let Some(window) = self.colors.window {
+            visuals.widgets.noninteractive.bg_fill = rgb(window);
+        }
+        if let Some(widget) = self.colors.widget {
+            visuals.widgets.inactive.bg_fill = rgb(widget);
+        }
+        if let Some(hovered) = self.colors.hovered {
+            visuals.widgets.hovered.bg_fill = rgb(hovered);
+            visuals.widgets.active.bg_fill = rgb(hovered);
+        }
+        if let Some(rounding) = self.layout.rounding {
+            visuals.window_rounding = Rounding::same(rounding);
+            for widget in [
+                &mut visuals.widgets.noninteractive,
+                &mut visuals.widgets.inactive,
+                &mut visuals.widgets.hovered,
+                &mut visuals.widgets.active,
+                &mut visuals.widgets.open,
+            ] {
+                widget.rounding = Rounding::same(rounding);
+            }
+        }
+        visuals
+    }
+
+    pub fn overlay_color(&self) -> Color32 {
+        self.colors.overlay.map(rgb).unwrap_or(Color32::WHITE)
+    }
+
+    /// Applies the fonts and spacing on top of egui's defaults, the visuals are
+    /// left to the caller so they can be faded
+    pub fn apply(&self, ctx: &Context, fallbacks: &[crate::fonts::Fallback]) {
+        let mut fonts = FontDefinitions::default();
+        if let Some(file) = &self.fonts.file {
+            match std::fs::read(file) {
+                Ok(font) => {
+                    fonts
+                        .font_data
+                        .insert("theme".to_string(), FontData::from_owned(font));
+                    fonts
+                        .families
+                        .entry(FontFamily::Proportional)
+                        .or_default()
+                        .insert(0, "theme".to_string());
+                }
+                Err(e) => eprintln!("Failed to read font {}: {}", file.display(), e),
+            }
+        }
+        crate::fonts::add_fallbacks(&mut fonts, fallbacks);
+        ctx.set_fonts(fonts);
+        let mut style = egui::Style::default();
+        if let Some(size) = self.fonts.size {
+            for text_style in [TextStyle::Body, TextStyle::Button] {
+                style
+                    .text_styles
+                    .insert(text_style, FontId::proportional(size));
+            }
+        }
+        if let Some([x, y]) = self.layout.spacing {
+            style.spacing.item_spacing = egui::vec2(x, y);
+        }
+        ctx.set_style(style);
+    }
+}
+
+/// Sends [`UserEvent::ThemeChanged`] whenever the theme file at `path` is
+/// modified, so theme authors see their changes without restarting
+pub fn watch(path: String, proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut last = modified();
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let current = modified();
+            if current != last {
+                last = current;
+                if proxy.send_event(UserEvent::ThemeChanged).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,84 @@
+use std::{
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+
+use crate::config::Fleet;
+
+fn verify_signature(path: &Path, public_key: &Path) -> Result<(), String> {
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+    let key = PublicKey::from_bytes(&read(public_key)?)
+        .map_err(|e| format!("Invalid public key {}: {}", public_key.display(), e))?;
+    let signature_path = format!("{}.sig", path.display());
+    let signature = Signature::try_from(read(Path::new(&signature_path))?.as_slice())
+        .map_err(|e| format!("Invalid signature {}: {}", signature_path, e))?;
+    key.verify(&read(path)?, &signature)
+        .map_err(|_| format!("{} doesn't match its signature", path.display()))
+}
+
+/// Checks `path` is owned by root and only writable by root
+pub fn root_only(path: &Path) -> Result<(), String> {
+    let metadata = path
+        .metadata()
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if metadata.uid() != 0 {
+        return Err(format!("{} isn't owned by root", path.display()));
+    }
+    if metadata.permissions().mode() & 0o022 != 0 {
+        return Err(format!("{} is writable by other users", path.display()));
+    }
+    Ok(())
+}
+
+/// Checks `path` can be trusted in fleet mode: it resolves to somewhere in
+/// `dir`, it and every directory between them are owned by root and only
+/// writable by root, and with a public key configured, it's a file signed
+/// by `<path>.sig`. Directories aren't signed, only what's picked out of
+/// them.
+fn check_in(path: &Path, dir: &Path, fleet: &Fleet) -> Result<(), String> {
+    let trusted = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", dir.display(), e))?;
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+    if !path.starts_with(&trusted) {
+        return Err(format!(
+            "{} is outside of {}",
+            path.display(),
+            trusted.display()
+        ));
+    }
+    for ancestor in path.ancestors().take_while(|a| a.starts_with(&trusted)) {
+        root_only(ancestor)?;
+    }
+    match &fleet.public_key {
+        Some(public_key) if path.is_file() => verify_signature(&path, public_key),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `path` may be loaded, reporting why not on stderr. Everything is
+/// allowed outside of fleet mode.
+pub fn allowed(path: &Path, fleet: Option<&Fleet>, what: &str) -> bool {
+    match fleet {
+        Some(fleet) => allowed_in(path, &fleet.trusted_dir, fleet, what),
+        None => true,
+    }
+}
+
+/// Like [`allowed`] for things that live in `dir` rather than the trusted
+/// directory
+pub fn allowed_in(path: &Path, dir: &Path, fleet: &Fleet, what: &str) -> bool {
+    match check_in(path, dir, fleet) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Refusing to load {} {}: {}", what, path.display(), e);
+            false
+        }
+    }
+}
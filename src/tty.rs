@@ -0,0 +1,142 @@
+use std::io::{BufRead, Write};
+
+use freedesktop_desktop_entry::DesktopEntry;
+use greetd_client::{AuthMessageType, Greetd, Response};
+
+use crate::{config::Config, sessions, state};
+
+fn prompt(message: &str, echo: bool) -> String {
+    print!("{}", message);
+    std::io::stdout().flush().unwrap();
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    let restore = !echo && unsafe { libc::tcgetattr(0, &mut termios) } == 0;
+    if restore {
+        let mut silent = termios;
+        silent.c_lflag &= !libc::ECHO;
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &silent) };
+    }
+    let mut line = String::new();
+    let read = std::io::stdin().lock().read_line(&mut line);
+    if restore {
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &termios) };
+        println!();
+    }
+    if matches!(read, Ok(0) | Err(_)) {
+        // Nobody is there to answer, let greetd start us over
+        std::process::exit(1);
+    }
+    line.trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// A plain line-based prompt on the TTY, used when the graphics stack fails
+/// to come up so nobody gets locked out by it
+pub fn run(config: &Config, default_username: Option<&str>, default_session: Option<&str>) {
+    eprintln!("Graphics failed to start, falling back to a text prompt");
+
+    let environments_raw = sessions::read();
+    let environments_serialized: Vec<DesktopEntry> = environments_raw
+        .iter()
+        .filter_map(|(bytes, path)| DesktopEntry::decode(path, bytes).ok())
+        .collect();
+    let locale = config.locale();
+    let environments: Vec<_> = sessions::strip(
+        &environments_serialized,
+        &config.session_priority,
+        locale.as_deref(),
+    )
+    .into_iter()
+    .filter(|env| env.installed)
+    .collect();
+    if environments.is_empty() {
+        eprintln!("No sessions installed");
+        return;
+    }
+
+    let mut greetd = Greetd::new().unwrap();
+    let mut responses: calloop::EventLoop<Option<Response>> =
+        calloop::EventLoop::try_new().unwrap();
+    responses
+        .handle()
+        .insert_source(greetd.event_source(), |response, _, slot| {
+            *slot = Some(response)
+        })
+        .unwrap();
+    let mut next_response = || {
+        let mut slot = None;
+        while slot.is_none() {
+            responses.dispatch(None, &mut slot).unwrap();
+        }
+        slot.unwrap()
+    };
+
+    loop {
+        let username = match default_username {
+            Some(username) => {
+                println!("Logging in as {}", username);
+                username.to_string()
+            }
+            None => prompt("login: ", true),
+        };
+        if username.is_empty() {
+            continue;
+        }
+        greetd.create_session(&username).unwrap();
+
+        let mut starting = false;
+        loop {
+            match next_response() {
+                Response::AuthMessage {
+                    auth_message_type,
+                    auth_message,
+                } => {
+                    let answer = match auth_message_type {
+                        AuthMessageType::Visible => Some(prompt(&auth_message, true)),
+                        AuthMessageType::Secret => Some(prompt(&auth_message, false)),
+                        AuthMessageType::Info | AuthMessageType::Error => {
+                            println!("{}", auth_message);
+                            None
+                        }
+                    };
+                    greetd.authentication_response(answer.as_deref()).unwrap();
+                }
+                Response::Success if starting => return,
+                Response::Success => {
+                    for (i, env) in environments.iter().enumerate() {
+                        println!("{}) {}", i + 1, env.name);
+                    }
+                    let default = default_session
+                        .and_then(|name| environments.iter().position(|env| env.name == name))
+                        .unwrap_or(0);
+                    let choice = prompt(&format!("session [{}]: ", default + 1), true);
+                    let index = choice
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| n.checked_sub(1))
+                        .filter(|i| *i < environments.len())
+                        .unwrap_or(default);
+                    // Neither speech nor the on-screen keyboard run on the TTY
+                    let a11y_env = if config.accessibility_env {
+                        state::State::load().accessibility_env(false, false)
+                    } else {
+                        Vec::new()
+                    };
+                    let cmd = environments[index].command(config, locale.as_deref(), &a11y_env);
+                    greetd
+                        .start_session(&cmd.iter().map(String::as_str).collect::<Vec<_>>())
+                        .unwrap();
+                    starting = true;
+                }
+                Response::Finish => return,
+                Response::Error { description, .. } => {
+                    println!("{}", description);
+                    if starting {
+                        greetd.cancel_session().unwrap();
+                        next_response();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+};
+
+use serde::Deserialize;
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::OwnedObjectPath,
+};
+
+use crate::{backend::event_loop::EventLoopProxy, config, users::Passwd, UserEvent};
+
+/// What the greeter shows and preselects for a user, every part optional
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct UserInfo {
+    pub full_name: Option<String>,
+    pub avatar: Option<PathBuf>,
+    /// Session name or desktop file name, e.g. `sway`
+    pub session: Option<String>,
+    /// Passed to the session as `LANG`
+    pub locale: Option<String>,
+    /// Whether secrets are entered on the PIN pad
+    pub pin_pad: Option<bool>,
+    /// Whether secrets are entered as an unlock pattern
+    pub pattern_unlock: Option<bool>,
+}
+
+impl UserInfo {
+    /// Fills in what `self` is missing from `other`
+    fn or(self, other: UserInfo) -> UserInfo {
+        UserInfo {
+            full_name: self.full_name.or(other.full_name),
+            avatar: self.avatar.or(other.avatar),
+            session: self.session.or(other.session),
+            locale: self.locale.or(other.locale),
+            pin_pad: self.pin_pad.or(other.pin_pad),
+            pattern_unlock: self.pattern_unlock.or(other.pattern_unlock),
+        }
+    }
+}
+
+/// A source of user information. `None` means the user is unknown to it.
+pub trait Provider: Send {
+    fn lookup(&self, username: &str) -> Option<UserInfo>;
+}
+
+/// The GECOS full name and `~/.face`
+struct PasswdProvider;
+
+impl Provider for PasswdProvider {
+    fn lookup(&self, username: &str) -> Option<UserInfo> {
+        let user = Passwd::lookup(username)?;
+        Some(UserInfo {
+            full_name: user
+                .gecos
+                .split(',')
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+            avatar: user.face(),
+            session: None,
+            locale: None,
+            pin_pad: None,
+            pattern_unlock: None,
+        })
+    }
+}
+
+/// What the desktop's user settings saved through AccountsService
+struct AccountsService;
+
+impl Provider for AccountsService {
+    fn lookup(&self, username: &str) -> Option<UserInfo> {
+        let result = Connection::system().and_then(|conn| {
+            let path: OwnedObjectPath = Proxy::new(
+                &conn,
+                "org.freedesktop.Accounts",
+                "/org/freedesktop/Accounts",
+                "org.freedesktop.Accounts",
+            )?
+            .call("FindUserByName", &(username,))?;
+            let user = Proxy::new(
+                &conn,
+                "org.freedesktop.Accounts",
+                path.as_str(),
+                "org.freedesktop.Accounts.User",
+            )?;
+            let property = |name: &str| {
+                user.get_property::<String>(name)
+                    .ok()
+                    .filter(|value| !value.is_empty())
+            };
+            Ok(UserInfo {
+                full_name: property("RealName"),
+                avatar: property("IconFile")
+                    .map(PathBuf::from)
+                    .filter(|path| path.is_file()),
+                session: property("Session").or_else(|| property("XSession")),
+                locale: property("Language"),
+                pin_pad: None,
+                pattern_unlock: None,
+            })
+        });
+        match result {
+            Ok(info) => Some(info),
+            // Users AccountsService doesn't know are errors too
+            Err(zbus::Error::MethodError(..)) => None,
+            Err(e) => {
+                eprintln!("Failed to look {} up in AccountsService: {}", username, e);
+                None
+            }
+        }
+    }
+}
+
+/// Entries written out in the config
+struct Table(BTreeMap<String, UserInfo>);
+
+impl Provider for Table {
+    fn lookup(&self, username: &str) -> Option<UserInfo> {
+        self.0.get(username).cloned()
+    }
+}
+
+/// The configured providers, in the order they're asked. They're asked on a
+/// worker thread since some go over D-Bus, and each user only once.
+pub struct Providers {
+    requests: Sender<String>,
+    /// `None` while the worker is still asking
+    cache: Arc<Mutex<HashMap<String, Option<UserInfo>>>>,
+}
+
+impl Providers {
+    pub fn new(config: &config::UserData, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let providers: Vec<Box<dyn Provider>> = config
+            .providers
+            .iter()
+            .map(|provider| -> Box<dyn Provider> {
+                match provider {
+                    config::UserDataProvider::Passwd => Box::new(PasswdProvider),
+                    config::UserDataProvider::AccountsService => Box::new(AccountsService),
+                    config::UserDataProvider::Table => Box::new(Table(config.users.clone())),
+                }
+            })
+            .collect();
+        let cache = Arc::new(Mutex::new(HashMap::new()));
+        let (requests, received) = mpsc::channel::<String>();
+        let shared = cache.clone();
+        std::thread::spawn(move || {
+            for username in received {
+                let info = providers
+                    .iter()
+                    .filter_map(|provider| provider.lookup(&username))
+                    .fold(UserInfo::default(), UserInfo::or);
+                shared.lock().unwrap().insert(username, Some(info));
+                let _ = proxy.send_event(UserEvent::Repaint);
+            }
+        });
+        Providers { requests, cache }
+    }
+
+    /// Everything the providers know about `username`, earlier providers
+    /// winning where they disagree. `None` until the worker has asked them,
+    /// the greeter is repainted once it has.
+    pub fn lookup(&self, username: &str) -> Option<UserInfo> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(username) {
+            Some(info) => info.clone(),
+            None => {
+                cache.insert(username.to_string(), None);
+                let _ = self.requests.send(username.to_string());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use crate::config::UsernamePolicy;
+
+pub struct Passwd {
+    pub name: String,
+    pub uid: u32,
+    pub gecos: String,
+    pub home: PathBuf,
+}
+
+impl Passwd {
+    /// Looks `username` up in `/etc/passwd`
+    pub fn lookup(username: &str) -> Option<Self> {
+        std::fs::read_to_string("/etc/passwd")
+            .ok()?
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                if name != username {
+                    return None;
+                }
+                let uid = fields.nth(1)?.parse().ok()?;
+                let gecos = fields.nth(1)?;
+                let home = fields.next()?;
+                Some(Passwd {
+                    name: name.to_string(),
+                    uid,
+                    gecos: gecos.to_string(),
+                    home: PathBuf::from(home),
+                })
+            })
+    }
+
+    /// The user's `~/.face` or `~/.face.icon`, if either exists
+    pub fn face(&self) -> Option<PathBuf> {
+        [".face", ".face.icon"]
+            .iter()
+            .map(|f| self.home.join(f))
+            .find(|p| p.is_file())
+    }
+}
+
+/// Applies `policy` to a typed username. Returns the username to log in with
+/// and a hint if it was changed, or the reason it was refused.
+pub fn normalize(
+    username: &str,
+    policy: &UsernamePolicy,
+) -> Result<(String, Option<&'static str>), &'static str> {
+    let mut normalized = username.to_string();
+    let mut hint = None;
+    if policy.trim && normalized.trim() != normalized {
+        normalized = normalized.trim().to_string();
+        hint = Some("Removed spaces around the username");
+    }
+    if policy.lowercase && normalized.chars().any(char::is_uppercase) {
+        normalized = normalized.to_lowercase();
+        hint = Some("Usernames are lowercase, logging in as lowercase");
+    }
+    if policy.reject_spaces && normalized.contains(char::is_whitespace) {
+        return Err("Usernames can't contain spaces");
+    }
+    if normalized.is_empty() {
+        return Err("Enter a username");
+    }
+    allowed(&normalized, policy)?;
+    Ok((normalized, hint))
+}
+
+/// Why [`allowed`] refuses an account
+pub const REFUSED: &str = "This account can't log in here";
+
+/// Checks `username` against the policy's allow and deny lists and minimum
+/// UID, before greetd is asked to create a session. Users missing from
+/// `/etc/passwd` pass the UID check, as they may come from the network.
+pub fn allowed(username: &str, policy: &UsernamePolicy) -> Result<(), &'static str> {
+    if policy.deny_users.iter().any(|u| u == username) {
+        return Err(REFUSED);
+    }
+    if !policy.allow_users.is_empty() && !policy.allow_users.iter().any(|u| u == username) {
+        return Err(REFUSED);
+    }
+    if let Some(min_uid) = policy.min_uid {
+        if Passwd::lookup(username)
+            .map(|user| user.uid < min_uid)
+            .unwrap_or(false)
+        {
+            return Err(REFUSED);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_and_lowercases() {
+        let policy = UsernamePolicy {
+            lowercase: true,
+            ..UsernamePolicy::default()
+        };
+        assert_eq!(
+            normalize("  alice ", &policy),
+            Ok((
+                "alice".to_string(),
+                Some("Removed spaces around the username")
+            ))
+        );
+        assert_eq!(
+            normalize("Alice", &policy),
+            Ok((
+                "alice".to_string(),
+                Some("Usernames are lowercase, logging in as lowercase")
+            ))
+        );
+        assert_eq!(
+            normalize("alice", &UsernamePolicy::default()),
+            Ok(("alice".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_usernames() {
+        let policy = UsernamePolicy::default();
+        assert_eq!(
+            normalize("al ice", &policy),
+            Err("Usernames can't contain spaces")
+        );
+        assert_eq!(normalize("   ", &policy), Err("Enter a username"));
+    }
+
+    #[test]
+    fn applies_the_allow_and_deny_lists() {
+        let policy = UsernamePolicy {
+            deny_users: vec!["guest".to_string()],
+            ..UsernamePolicy::default()
+        };
+        assert_eq!(allowed("guest", &policy), Err(REFUSED));
+        assert_eq!(allowed("alice", &policy), Ok(()));
+        assert_eq!(normalize("guest", &policy), Err(REFUSED));
+
+        let policy = UsernamePolicy {
+            allow_users: vec!["alice".to_string()],
+            ..UsernamePolicy::default()
+        };
+        assert_eq!(allowed("alice", &policy), Ok(()));
+        assert_eq!(allowed("bob", &policy), Err(REFUSED));
+    }
+
+    #[test]
+    fn only_checks_the_uid_of_local_users() {
+        let policy = UsernamePolicy {
+            min_uid: Some(1000),
+            ..UsernamePolicy::default()
+        };
+        assert_eq!(allowed("root", &policy), Err(REFUSED));
+        assert_eq!(allowed("no-such-user-anywhere", &policy), Ok(()));
+    }
+}
@@ -0,0 +1,715 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::UnixListener,
+        net::UnixStream,
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use glow::HasContext;
+
+use crate::{
+    backend::{
+        dpi::PhysicalPosition,
+        event::{
+            DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+            TouchPhase, VirtualKeyCode, WindowEvent,
+        },
+        event_loop::EventLoopProxy,
+    },
+    sas::LETTERS,
+    UserEvent,
+};
+
+/// RFB's `DesktopSize` pseudo-encoding, for telling viewers about resizes
+const DESKTOP_SIZE: i32 = -223;
+
+/// The last frame painted, as RGBA rows from the top
+#[derive(Default)]
+struct Frame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    serial: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    frame: Mutex<Frame>,
+    painted: Condvar,
+    viewers: AtomicUsize,
+    /// Viewers waiting for a frame, which is only read back while there are any
+    wanted: AtomicUsize,
+    /// Whether a frame was painted without being captured
+    missed: AtomicBool,
+}
+
+/// Shows the greeter to VNC viewers on a Unix socket and takes their input,
+/// so an admin can tunnel in over SSH to help someone at the login screen
+pub struct Server {
+    shared: Arc<Shared>,
+    view_only: bool,
+}
+
+/// Counts a viewer as waiting for a frame while it's alive
+struct Wanting<'a>(&'a AtomicUsize);
+
+impl<'a> Wanting<'a> {
+    fn new(wanted: &'a AtomicUsize) -> Self {
+        wanted.fetch_add(1, Ordering::SeqCst);
+        Wanting(wanted)
+    }
+}
+
+impl Drop for Wanting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PixelFormat {
+    bits_per_pixel: u8,
+    big_endian: bool,
+    max: [u16; 3],
+    shift: [u8; 3],
+}
+
+impl PixelFormat {
+    const DEFAULT: Self = PixelFormat {
+        bits_per_pixel: 32,
+        big_endian: false,
+        max: [255; 3],
+        shift: [16, 8, 0],
+    };
+
+    /// Only true color formats whose channels fit in the pixel are taken
+    fn parse(b: &[u8; 16]) -> io::Result<Self> {
+        let u16_at = |at: usize| u16::from_be_bytes([b[at], b[at + 1]]);
+        let format = PixelFormat {
+            bits_per_pixel: b[0],
+            big_endian: b[2] != 0,
+            max: [u16_at(4), u16_at(6), u16_at(8)],
+            shift: [b[10], b[11], b[12]],
+        };
+        let fits = (0..3).all(|c| {
+            let bits = 16 - format.max[c].leading_zeros();
+            format.shift[c] < format.bits_per_pixel
+                && format.shift[c] as u32 + bits <= format.bits_per_pixel as u32
+        });
+        if !matches!(format.bits_per_pixel, 8 | 16 | 32) || b[3] == 0 || !fits {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported pixel format",
+            ));
+        }
+        Ok(format)
+    }
+
+    fn bytes(&self) -> [u8; 16] {
+        let mut b = [0; 16];
+        b[0] = self.bits_per_pixel;
+        b[1] = 24;
+        b[2] = self.big_endian as u8;
+        b[3] = 1;
+        for c in 0..3 {
+            b[4 + c * 2..6 + c * 2].copy_from_slice(&self.max[c].to_be_bytes());
+            b[10 + c] = self.shift[c];
+        }
+        b
+    }
+
+    fn encode(&self, rgba: &[u8], out: &mut Vec<u8>) {
+        let bytes = (self.bits_per_pixel / 8).max(1) as usize;
+        for pixel in rgba.chunks_exact(4) {
+            let value = (0..3).fold(0u32, |value, c| {
+                let scaled = pixel[c] as u32 * self.max[c] as u32 / 255;
+                value | scaled << self.shift[c]
+            });
+            let le = value.to_le_bytes();
+            if self.big_endian {
+                out.extend(le[..bytes].iter().rev());
+            } else {
+                out.extend(&le[..bytes]);
+            }
+        }
+    }
+}
+
+/// What the reading half of a connection passes on to the writing half
+enum Request {
+    Format(PixelFormat),
+    Resizable(bool),
+    Update { incremental: bool },
+}
+
+impl Server {
+    /// Listens on `path`, only the greeter's own user can connect. `tty`
+    /// sends keys as terminal input, like the DRM backend reads them.
+    pub fn start(
+        path: PathBuf,
+        view_only: bool,
+        tty: bool,
+        proxy: EventLoopProxy<UserEvent>,
+    ) -> Option<Self> {
+        let _ = std::fs::remove_file(&path);
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir);
+        }
+        // The socket's permissions are all the authentication there is, so
+        // it mustn't be reachable even for a moment before they're set
+        let umask = unsafe { libc::umask(0o077) };
+        let listener = UnixListener::bind(&path);
+        unsafe { libc::umask(umask) };
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to listen on {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+            eprintln!("Failed to restrict {}: {}", path.display(), e);
+            return None;
+        }
+
+        let shared = Arc::new(Shared::default());
+        let server_shared = shared.clone();
+        let proxy = Mutex::new(proxy);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = server_shared.clone();
+                let proxy = proxy.lock().unwrap().clone();
+                std::thread::spawn(move || {
+                    shared.viewers.fetch_add(1, Ordering::SeqCst);
+                    let _ = proxy.send_event(UserEvent::Repaint);
+                    if let Err(e) = serve(stream, &shared, view_only, tty, &proxy) {
+                        eprintln!("VNC viewer disconnected: {}", e);
+                    }
+                    shared.viewers.fetch_sub(1, Ordering::SeqCst);
+                    let _ = proxy.send_event(UserEvent::Repaint);
+                });
+            }
+        });
+        Some(Server { shared, view_only })
+    }
+
+    /// What to tell whoever's at the screen about remote viewers, if any
+    /// are connected
+    pub fn status(&self) -> Option<&'static str> {
+        if self.shared.viewers.load(Ordering::SeqCst) == 0 {
+            None
+        } else if self.view_only {
+            Some("A remote viewer is watching the screen")
+        } else {
+            Some("A remote viewer is watching and can control the screen")
+        }
+    }
+
+    /// Copies the finished frame out of the back buffer if a viewer is
+    /// waiting for one
+    pub unsafe fn painted(&self, gl: &glow::Context, width: u32, height: u32) {
+        if self.shared.wanted.load(Ordering::SeqCst) == 0 {
+            self.shared.missed.store(true, Ordering::SeqCst);
+            return;
+        }
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        gl.read_pixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(&mut pixels),
+        );
+        let mut frame = self.shared.frame.lock().unwrap();
+        // GL counts rows from the bottom
+        frame.pixels.clear();
+        frame
+            .pixels
+            .extend(pixels.rchunks_exact(width as usize * 4).flatten());
+        frame.width = width;
+        frame.height = height;
+        frame.serial += 1;
+        self.shared.painted.notify_all();
+    }
+}
+
+fn serve(
+    mut stream: UnixStream,
+    shared: &Arc<Shared>,
+    view_only: bool,
+    tty: bool,
+    proxy: &EventLoopProxy<UserEvent>,
+) -> io::Result<()> {
+    stream.write_all(b"RFB 003.008\n")?;
+    let mut version = [0; 12];
+    stream.read_exact(&mut version)?;
+    // 3.3 viewers don't get to choose, the server names the security type
+    if &version == b"RFB 003.003\n" {
+        stream.write_all(&1u32.to_be_bytes())?;
+    } else {
+        // Only "None", the socket's permissions are the authentication
+        stream.write_all(&[1, 1])?;
+        let mut chosen = [0];
+        stream.read_exact(&mut chosen)?;
+        stream.write_all(&0u32.to_be_bytes())?;
+    }
+    let mut shared_flag = [0];
+    stream.read_exact(&mut shared_flag)?;
+
+    let (width, height) = {
+        let _wanting = Wanting::new(&shared.wanted);
+        let frame = shared
+            .painted
+            .wait_while(shared.frame.lock().unwrap(), |frame| frame.serial == 0)
+            .unwrap();
+        (frame.width, frame.height)
+    };
+    let name = b"eguigreeter";
+    let mut init = Vec::new();
+    init.extend((width as u16).to_be_bytes());
+    init.extend((height as u16).to_be_bytes());
+    init.extend(PixelFormat::DEFAULT.bytes());
+    init.extend((name.len() as u32).to_be_bytes());
+    init.extend(name);
+    stream.write_all(&init)?;
+
+    let (requests, received) = mpsc::channel();
+    let writer = stream.try_clone()?;
+    let writer_shared = shared.clone();
+    let writer_proxy = proxy.clone();
+    std::thread::spawn(move || {
+        send_frames(
+            writer,
+            &writer_shared,
+            &writer_proxy,
+            (width, height),
+            received,
+        )
+    });
+
+    let mut input = Input::new(tty);
+    loop {
+        let mut kind = [0];
+        stream.read_exact(&mut kind)?;
+        match kind[0] {
+            0 => {
+                let mut b = [0; 19];
+                stream.read_exact(&mut b)?;
+                let format: &[u8; 16] = b[3..].try_into().unwrap();
+                let _ = requests.send(Request::Format(PixelFormat::parse(format)?));
+            }
+            2 => {
+                let mut b = [0; 3];
+                stream.read_exact(&mut b)?;
+                let mut encodings = vec![0; u16::from_be_bytes([b[1], b[2]]) as usize * 4];
+                stream.read_exact(&mut encodings)?;
+                let resizable = encodings
+                    .chunks_exact(4)
+                    .any(|e| i32::from_be_bytes(e.try_into().unwrap()) == DESKTOP_SIZE);
+                let _ = requests.send(Request::Resizable(resizable));
+            }
+            3 => {
+                let mut b = [0; 9];
+                stream.read_exact(&mut b)?;
+                let _ = requests.send(Request::Update {
+                    incremental: b[0] != 0,
+                });
+            }
+            4 => {
+                let mut b = [0; 7];
+                stream.read_exact(&mut b)?;
+                let keysym = u32::from_be_bytes([b[3], b[4], b[5], b[6]]);
+                if !view_only {
+                    for event in input.key(b[0] != 0, keysym) {
+                        let _ = proxy.send_event(UserEvent::Remote(event));
+                    }
+                }
+            }
+            5 => {
+                let mut b = [0; 5];
+                stream.read_exact(&mut b)?;
+                let x = u16::from_be_bytes([b[1], b[2]]);
+                let y = u16::from_be_bytes([b[3], b[4]]);
+                if !view_only {
+                    for event in input.pointer(b[0], x, y) {
+                        let _ = proxy.send_event(UserEvent::Remote(event));
+                    }
+                }
+            }
+            6 => {
+                let mut b = [0; 7];
+                stream.read_exact(&mut b)?;
+                let length = u32::from_be_bytes([b[3], b[4], b[5], b[6]]);
+                // Pasting into the greeter isn't supported
+                io::copy(&mut (&mut stream).take(length as u64), &mut io::sink())?;
+            }
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown message type {}", other),
+                ))
+            }
+        }
+    }
+}
+
+/// Sends a frame whenever the viewer asked for one and there's a new one,
+/// until the viewer goes away
+fn send_frames(
+    mut stream: UnixStream,
+    shared: &Shared,
+    proxy: &EventLoopProxy<UserEvent>,
+    mut size: (u32, u32),
+    requests: mpsc::Receiver<Request>,
+) -> io::Result<()> {
+    let mut format = PixelFormat::DEFAULT;
+    let mut resizable = false;
+    let mut sent = 0;
+    let mut pending: Option<bool> = None;
+    let mut wanting = None;
+    let mut out = Vec::new();
+    loop {
+        let request = match pending {
+            // Keep an eye out for the viewer leaving while waiting for a frame
+            Some(_) => match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(mpsc::TryRecvError::Empty) => None,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            },
+            None => Some(requests.recv().map_err(|_| io::ErrorKind::BrokenPipe)?),
+        };
+        match request {
+            Some(Request::Format(new)) => format = new,
+            Some(Request::Resizable(new)) => resizable = new,
+            Some(Request::Update { incremental }) => {
+                pending = Some(pending.unwrap_or(true) && incremental);
+                if wanting.is_none() {
+                    wanting = Some(Wanting::new(&shared.wanted));
+                    // Frames painted while nobody was waiting weren't kept
+                    if !incremental || shared.missed.swap(false, Ordering::SeqCst) {
+                        let _ = proxy.send_event(UserEvent::Repaint);
+                    }
+                }
+            }
+            None => {}
+        }
+        let incremental = match pending {
+            Some(incremental) => incremental,
+            None => continue,
+        };
+
+        let (frame, _) = shared
+            .painted
+            .wait_timeout_while(
+                shared.frame.lock().unwrap(),
+                Duration::from_millis(250),
+                |frame| incremental && frame.serial == sent,
+            )
+            .unwrap();
+        if incremental && frame.serial == sent {
+            continue;
+        }
+        pending = None;
+        wanting = None;
+        sent = frame.serial;
+
+        out.clear();
+        out.extend([0, 0]);
+        let resize = resizable && (frame.width, frame.height) != size;
+        out.extend((1 + resize as u16).to_be_bytes());
+        if resize {
+            size = (frame.width, frame.height);
+            for value in [0, 0, size.0 as u16, size.1 as u16] {
+                out.extend(value.to_be_bytes());
+            }
+            out.extend(DESKTOP_SIZE.to_be_bytes());
+        }
+        // Viewers that can't be resized see whatever fits
+        let (width, height) = (size.0.min(frame.width), size.1.min(frame.height));
+        for value in [0, 0, width as u16, height as u16] {
+            out.extend(value.to_be_bytes());
+        }
+        out.extend(0i32.to_be_bytes());
+        for row in frame
+            .pixels
+            .chunks_exact(frame.width as usize * 4)
+            .take(height as usize)
+        {
+            format.encode(&row[..width as usize * 4], &mut out);
+        }
+        drop(frame);
+        stream.write_all(&out)?;
+    }
+}
+
+/// Turns RFB key and pointer events into the window events a local keyboard
+/// and mouse would produce
+struct Input {
+    tty: bool,
+    modifiers: ModifiersState,
+    buttons: u8,
+    position: PhysicalPosition<f64>,
+}
+
+fn key_code(keysym: u32) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    Some(match keysym {
+        0xff08 => Back,
+        0xff09 => Tab,
+        0xff0d => Return,
+        0xff1b => Escape,
+        0xffff => Delete,
+        0xff50 => Home,
+        0xff51 => Left,
+        0xff52 => Up,
+        0xff53 => Right,
+        0xff54 => Down,
+        0xff57 => End,
+        0xff61 => Snapshot,
+        0xff63 => Insert,
+        0xffbe..=0xffc9 => {
+            [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12][(keysym - 0xffbe) as usize]
+        }
+        _ => {
+            let c = char::from_u32(keysym)?.to_ascii_lowercase();
+            c.is_ascii_lowercase()
+                .then(|| LETTERS[(c as u8 - b'a') as usize])?
+        }
+    })
+}
+
+fn modifier(keysym: u32) -> Option<ModifiersState> {
+    Some(match keysym {
+        0xffe1 | 0xffe2 => ModifiersState::SHIFT,
+        0xffe3 | 0xffe4 => ModifiersState::CTRL,
+        0xffe7..=0xffea => ModifiersState::ALT,
+        0xffeb | 0xffec => ModifiersState::LOGO,
+        _ => return None,
+    })
+}
+
+/// The text a keysym types, Latin-1 keysyms are their own code points
+fn text(keysym: u32) -> Option<char> {
+    match keysym {
+        0x20..=0x7e | 0xa0..=0xff => char::from_u32(keysym),
+        0x0100_0000..=0x0110_ffff => char::from_u32(keysym - 0x0100_0000),
+        _ => None,
+    }
+}
+
+impl Input {
+    fn new(tty: bool) -> Self {
+        Input {
+            tty,
+            modifiers: ModifiersState::empty(),
+            buttons: 0,
+            position: PhysicalPosition::new(0.0, 0.0),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn key(&mut self, down: bool, keysym: u32) -> Vec<WindowEvent<'static>> {
+        if let Some(modifier) = modifier(keysym) {
+            self.modifiers.set(modifier, down);
+            return vec![WindowEvent::ModifiersChanged(self.modifiers)];
+        }
+        if self.tty {
+            // Terminals only send characters, on key presses
+            let c = match keysym {
+                0xff0d => Some('\r'),
+                0xff08 => Some('\x7F'),
+                0xff09 => Some('\t'),
+                0xff1b => Some('\x1b'),
+                _ => text(keysym).map(|c| {
+                    if self.modifiers.ctrl() && c.is_ascii_alphabetic() {
+                        (c.to_ascii_lowercase() as u8 - b'a' + 1) as char
+                    } else {
+                        c
+                    }
+                }),
+            };
+            return c
+                .filter(|_| down)
+                .map(WindowEvent::ReceivedCharacter)
+                .into_iter()
+                .collect();
+        }
+
+        let mut events = vec![WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state: if down {
+                    ElementState::Pressed
+                } else {
+                    ElementState::Released
+                },
+                virtual_keycode: key_code(keysym),
+                modifiers: self.modifiers,
+            },
+            is_synthetic: false,
+        }];
+        if down && !self.modifiers.ctrl() && !self.modifiers.logo() {
+            events.extend(text(keysym).map(WindowEvent::ReceivedCharacter));
+        }
+        events
+    }
+
+    #[allow(deprecated)]
+    fn pointer(&mut self, buttons: u8, x: u16, y: u16) -> Vec<WindowEvent<'static>> {
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut events = Vec::new();
+        let position = PhysicalPosition::new(x as f64, y as f64);
+        if position != self.position {
+            self.position = position;
+            events.push(WindowEvent::CursorMoved {
+                device_id,
+                position,
+                modifiers: self.modifiers,
+            });
+        }
+        let changed = buttons ^ self.buttons;
+        for (bit, button) in [
+            (0, MouseButton::Left),
+            (1, MouseButton::Middle),
+            (2, MouseButton::Right),
+        ] {
+            if changed & 1 << bit != 0 {
+                events.push(WindowEvent::MouseInput {
+                    device_id,
+                    state: if buttons & 1 << bit != 0 {
+                        ElementState::Pressed
+                    } else {
+                        ElementState::Released
+                    },
+                    button,
+                    modifiers: self.modifiers,
+                });
+            }
+        }
+        // The wheel comes as presses of buttons 4 and 5
+        for (bit, lines) in [(3, 1.0), (4, -1.0)] {
+            if buttons & changed & 1 << bit != 0 {
+                events.push(WindowEvent::MouseWheel {
+                    device_id,
+                    delta: MouseScrollDelta::LineDelta(0.0, lines),
+                    phase: TouchPhase::Moved,
+                    modifiers: self.modifiers,
+                });
+            }
+        }
+        self.buttons = buttons;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGB565 as big endian 16 bit pixels
+    fn rgb565() -> [u8; 16] {
+        PixelFormat {
+            bits_per_pixel: 16,
+            big_endian: true,
+            max: [31, 63, 31],
+            shift: [11, 5, 0],
+        }
+        .bytes()
+    }
+
+    #[test]
+    fn parses_what_it_sends() {
+        let format = PixelFormat::parse(&PixelFormat::DEFAULT.bytes()).unwrap();
+        assert_eq!(format.bits_per_pixel, 32);
+        assert!(!format.big_endian);
+        assert_eq!(format.max, [255; 3]);
+        assert_eq!(format.shift, [16, 8, 0]);
+        let format = PixelFormat::parse(&rgb565()).unwrap();
+        assert_eq!(format.max, [31, 63, 31]);
+    }
+
+    #[test]
+    fn refuses_unsupported_formats() {
+        let mut b = PixelFormat::DEFAULT.bytes();
+        b[0] = 24;
+        assert!(PixelFormat::parse(&b).is_err());
+        // Colour maps
+        let mut b = PixelFormat::DEFAULT.bytes();
+        b[3] = 0;
+        assert!(PixelFormat::parse(&b).is_err());
+        // Red would be shifted out of the pixel
+        let mut b = rgb565();
+        b[10] = 12;
+        assert!(PixelFormat::parse(&b).is_err());
+    }
+
+    #[test]
+    fn encodes_pixels() {
+        let mut out = Vec::new();
+        PixelFormat::DEFAULT.encode(&[1, 2, 3, 255], &mut out);
+        assert_eq!(out, [3, 2, 1, 0]);
+
+        let format = PixelFormat::parse(&rgb565()).unwrap();
+        let mut out = Vec::new();
+        format.encode(&[255, 0, 0, 255, 255, 255, 255, 0], &mut out);
+        assert_eq!(out, [0xf8, 0x00, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn types_keys_like_a_keyboard() {
+        let mut input = Input::new(false);
+        let events = input.key(true, 'a' as u32);
+        assert!(matches!(
+            events[..],
+            [
+                WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::A),
+                        ..
+                    },
+                    ..
+                },
+                WindowEvent::ReceivedCharacter('a'),
+            ]
+        ));
+        // No text while ctrl is held, or on release
+        assert!(matches!(
+            input.key(true, 0xffe3)[..],
+            [WindowEvent::ModifiersChanged(m)] if m == ModifiersState::CTRL
+        ));
+        assert_eq!(input.key(true, 'a' as u32).len(), 1);
+        assert_eq!(input.key(false, 'a' as u32).len(), 1);
+    }
+
+    #[test]
+    fn types_keys_like_a_terminal() {
+        let mut input = Input::new(true);
+        assert!(matches!(
+            input.key(true, 0xff0d)[..],
+            [WindowEvent::ReceivedCharacter('\r')]
+        ));
+        assert!(input.key(false, 0xff0d).is_empty());
+        input.key(true, 0xffe3);
+        assert!(matches!(
+            input.key(true, 'C' as u32)[..],
+            [WindowEvent::ReceivedCharacter('\x03')]
+        ));
+        // Keys that type nothing on a terminal aren't sent
+        assert!(input.key(true, 0xffbe).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use crate::{backend::event_loop::EventLoopProxy, UserEvent};
+
+/// The kernel's own uevents, before udev has seen them
+const UEVENT_KERNEL_GROUP: u32 = 1;
+
+/// Opens a netlink socket of `protocol` listening to `groups`
+fn socket(protocol: libc::c_int, groups: u32) -> std::io::Result<File> {
+    unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            protocol,
+        );
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        let mut addr = std::mem::zeroed::<libc::sockaddr_nl>();
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let addr_ptr = (&addr as *const libc::sockaddr_nl).cast();
+        let len = std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        if libc::bind(fd.as_raw_fd(), addr_ptr, len) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(File::from(fd))
+    }
+}
+
+/// Whether a uevent is a screen or an input device showing up, such as a
+/// monitor plugged in or a remote console attaching its keyboard
+fn arrived(uevent: &[u8]) -> bool {
+    let mut fields = uevent.split(|&b| b == 0);
+    let action = match fields
+        .next()
+        .and_then(|head| head.split(|&b| b == b'@').next())
+    {
+        Some(action) => action,
+        None => return false,
+    };
+    let subsystem = fields.find_map(|field| field.strip_prefix(b"SUBSYSTEM="));
+    match (action, subsystem) {
+        (b"add", Some(b"input")) => true,
+        // Connectors being plugged in show up as a change of the card
+        (b"add" | b"change", Some(b"drm")) => true,
+        _ => false,
+    }
+}
+
+/// The links in a batch of rtnetlink messages, by index, and whether each is
+/// up and running
+fn links(buf: &[u8]) -> Vec<(i32, bool)> {
+    const HEADER: usize = std::mem::size_of::<libc::nlmsghdr>();
+    let mut links = Vec::new();
+    let mut rest = buf;
+    while rest.len() >= HEADER {
+        let header = unsafe { std::ptr::read_unaligned(rest.as_ptr().cast::<libc::nlmsghdr>()) };
+        let len = header.nlmsg_len as usize;
+        if len < HEADER || len > rest.len() {
+            break;
+        }
+        let body = &rest[HEADER..len];
+        // ifinfomsg: family, padding, type, index, flags
+        if header.nlmsg_type == libc::RTM_NEWLINK && body.len() >= 12 {
+            let index = i32::from_ne_bytes(body[4..8].try_into().unwrap());
+            let flags = u32::from_ne_bytes(body[8..12].try_into().unwrap());
+            links.push((index, flags & libc::IFF_RUNNING as u32 != 0));
+        }
+        // Messages are aligned to 4 bytes
+        rest = &rest[((len + 3) & !3).min(rest.len())..];
+    }
+    links
+}
+
+/// Sends [`UserEvent::Wake`] when a screen or an input device shows up, or
+/// when a network link comes up, each watched on a separate thread. Both
+/// can mean someone is about to log in from elsewhere.
+pub fn watch(proxy: EventLoopProxy<UserEvent>) {
+    match socket(libc::NETLINK_KOBJECT_UEVENT, UEVENT_KERNEL_GROUP) {
+        Ok(mut socket) => {
+            let proxy = proxy.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 8192];
+                while let Ok(len) = socket.read(&mut buf) {
+                    if arrived(&buf[..len]) && proxy.send_event(UserEvent::Wake).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        Err(e) => eprintln!("Failed to watch for devices: {}", e),
+    }
+    match socket(libc::NETLINK_ROUTE, libc::RTMGRP_LINK as u32) {
+        Ok(mut socket) => {
+            std::thread::spawn(move || {
+                // A link is only known to come up once it was seen down
+                let mut running = HashMap::new();
+                let mut buf = [0; 8192];
+                while let Ok(len) = socket.read(&mut buf) {
+                    for (index, up) in links(&buf[..len]) {
+                        let was = running.insert(index, up);
+                        if up && was == Some(false) && proxy.send_event(UserEvent::Wake).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        Err(e) => eprintln!("Failed to watch network links: {}", e),
+    }
+}
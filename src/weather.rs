@@ -0,0 +1,40 @@
+use std::{path::Path, time::Duration};
+
+use crate::{backend::event_loop::EventLoopProxy, cache::CACHE_DIR, config::Weather, UserEvent};
+
+fn cache_path() -> std::path::PathBuf {
+    Path::new(CACHE_DIR).join("weather.txt")
+}
+
+/// The last report fetched, so the widget has something to show offline
+pub fn cached() -> Option<String> {
+    std::fs::read_to_string(cache_path())
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Fetches the configured provider's plain text report every
+/// `config.interval` seconds on a separate thread, sending
+/// [`UserEvent::Weather`] for each new report
+pub fn watch(config: &Weather, proxy: EventLoopProxy<UserEvent>) {
+    let url = config.url.replace("{location}", &config.location);
+    let interval = Duration::from_secs(config.interval.max(60));
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(10))
+        .build();
+    std::thread::spawn(move || loop {
+        match agent.get(&url).call().and_then(|r| Ok(r.into_string()?)) {
+            Ok(report) => {
+                let report = report.trim().to_string();
+                let _ = std::fs::create_dir_all(CACHE_DIR);
+                let _ = std::fs::write(cache_path(), &report);
+                if proxy.send_event(UserEvent::Weather(report)).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Failed to fetch the weather: {}", e),
+        }
+        std::thread::sleep(interval);
+    });
+}